@@ -0,0 +1,49 @@
+//! Precompiles the embedded tiktoken vocabularies into the binary token table of
+//! `src/vocabulary.rs`, so loading them at runtime needs no base64 decoding, parsing
+//! or indexing.
+
+#[path = "src/vocabulary.rs"]
+#[allow(dead_code)]
+mod vocabulary;
+
+use base64::{Engine as _, engine::general_purpose};
+use std::path::PathBuf;
+use vocabulary::{TokenTable, Vocabulary};
+
+/// Encodings whose `<name>.tiktoken` rank file is embedded in the library
+const EMBEDDED_ENCODINGS: [&str; 4] = ["o200k_base", "cl100k_base", "p50k_base", "r50k_base"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/vocabulary.rs");
+    for name in EMBEDDED_ENCODINGS {
+        println!("cargo:rerun-if-changed={}.tiktoken", name);
+    }
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED_VOCAB").is_none() {
+        return;
+    }
+    
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    for name in EMBEDDED_ENCODINGS {
+        let file = format!("{}.tiktoken", name);
+        let text = std::fs::read_to_string(&file).unwrap_or_else(|e| panic!("Cannot read {}: {}", file, e));
+        
+        let tokens = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                let invalid = |what: &str| -> ! { panic!("{}, line {}: {}", file, index + 1, what) };
+                let (token, rank) = line.split_once(' ').unwrap_or_else(|| invalid("expected '<base64 token> <rank>'"));
+                let token = general_purpose::STANDARD.decode(token).unwrap_or_else(|_| invalid("invalid base64 token"));
+                let rank: u32 = rank.trim().parse().unwrap_or_else(|_| invalid("invalid rank"));
+                (token, rank)
+            })
+            .collect();
+        let table = Vocabulary::from_tokens(tokens)
+            .and_then(TokenTable::new)
+            .unwrap_or_else(|e| panic!("{}: {}", file, e));
+        
+        std::fs::write(out_dir.join(format!("{}.bpe", name)), table.to_bytes())
+            .unwrap_or_else(|e| panic!("Cannot write the {} binary vocabulary: {}", name, e));
+    }
+}
@@ -0,0 +1,105 @@
+//! Sharded cache of BPE merge results for regex pieces
+//!
+//! Pieces that are not a single token (rare words, inflected and non-English words,
+//! numbers with units) repeat throughout a document, and merging them is the
+//! expensive part of encoding. Pieces that are a token are never cached.
+
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap as HashMap, FxHasher};
+use std::hash::{Hash, Hasher};
+use crate::tiktoken_core::Rank;
+
+/// Number of independently locked shards (threads rarely wait on each other)
+const SHARDS: usize = 32;
+
+/// Pieces longer than this are not cached (they are rare and use the most memory)
+pub const MAX_CACHED_PIECE_LEN: usize = 64;
+
+/// Piece -> tokens cache shared by all threads of a tokenizer
+/// 
+/// Each shard keeps two generations of entries: a hit in the older generation moves
+/// the entry to the current one, and when the current generation is full it becomes
+/// the older one (dropping the previous older one). This approximates LRU eviction
+/// with O(1) operations and bounds the cache to twice its capacity.
+pub struct BpeCache {
+    shards: Vec<Mutex<Shard>>,
+    shard_capacity: usize,
+}
+
+#[derive(Default)]
+struct Shard {
+    current: HashMap<Box<[u8]>, Box<[Rank]>>,
+    previous: HashMap<Box<[u8]>, Box<[Rank]>>,
+}
+
+impl BpeCache {
+    /// Create a cache holding at least `capacity` pieces
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::new(Shard::default())).collect(),
+            shard_capacity: capacity.div_ceil(SHARDS).max(1),
+        }
+    }
+    
+    /// Apply `f` to the cached tokens of `piece`, if any
+    pub fn get_with<R>(&self, piece: &[u8], f: impl FnOnce(&[Rank]) -> R) -> Option<R> {
+        let mut shard = self.shard(piece).lock();
+        if let Some(tokens) = shard.current.get(piece) {
+            return Some(f(tokens));
+        }
+        
+        // Recently used again: keep it through the next rotation
+        let (piece, tokens) = shard.previous.remove_entry(piece)?;
+        let result = f(&tokens);
+        self.insert_into(&mut shard, piece, tokens);
+        Some(result)
+    }
+    
+    /// Remember the tokens of `piece` (pieces above `MAX_CACHED_PIECE_LEN` are ignored)
+    pub fn insert(&self, piece: &[u8], tokens: &[Rank]) {
+        if piece.len() > MAX_CACHED_PIECE_LEN {
+            return;
+        }
+        let mut shard = self.shard(piece).lock();
+        self.insert_into(&mut shard, piece.into(), tokens.into());
+    }
+    
+    fn insert_into(&self, shard: &mut Shard, piece: Box<[u8]>, tokens: Box<[Rank]>) {
+        if shard.current.len() >= self.shard_capacity {
+            shard.previous = std::mem::take(&mut shard.current);
+        }
+        shard.current.insert(piece, tokens);
+    }
+    
+    fn shard(&self, piece: &[u8]) -> &Mutex<Shard> {
+        let mut hasher = FxHasher::default();
+        piece.hash(&mut hasher);
+        // High bits: FxHash mixes the input into them best
+        &self.shards[(hasher.finish() >> 32) as usize % SHARDS]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_cache_eviction() {
+        let cache = BpeCache::new(SHARDS); // One entry per shard and generation
+        cache.insert(b"alpha", &[1, 2]);
+        assert_eq!(cache.get_with(b"alpha", <[Rank]>::to_vec), Some(vec![1, 2]));
+        assert_eq!(cache.get_with(b"beta", <[Rank]>::len), None);
+        
+        // Pieces of the same shard rotate "alpha" out after two generations
+        let shard = |piece: &[u8]| cache.shard(piece) as *const _;
+        let mut same_shard = (0..).map(|i: u32| format!("piece{}", i)).filter(|piece| shard(piece.as_bytes()) == shard(b"alpha"));
+        cache.insert(same_shard.next().unwrap().as_bytes(), &[3]);
+        assert!(cache.get_with(b"alpha", |_| ()).is_some()); // Promoted back to the current generation
+        cache.insert(same_shard.next().unwrap().as_bytes(), &[4]);
+        cache.insert(same_shard.next().unwrap().as_bytes(), &[5]);
+        assert!(cache.get_with(b"alpha", |_| ()).is_none());
+        
+        cache.insert(&[b'x'; MAX_CACHED_PIECE_LEN + 1], &[6]);
+        assert!(cache.get_with(&[b'x'; MAX_CACHED_PIECE_LEN + 1], |_| ()).is_none());
+    }
+}
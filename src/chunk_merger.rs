@@ -1,5 +1,5 @@
 use crate::semantic_segmenter::Segment;
-use crate::tiktoken_core::CoreBPE;
+use crate::tiktoken_core::{CoreBPE, PieceCounts};
 use crate::error::ProcessingError;
 use log::debug;
 
@@ -8,9 +8,10 @@ use log::debug;
 pub struct SemanticChunk {
     pub text: String,
     pub token_count: usize,
-    pub start_offset: usize,
+    pub start_offset: usize, // Byte span of the merged segments in the segmented text
     pub end_offset: usize,
     pub segments: Vec<usize>, // Indices of original segments that form this chunk
+    pub token_counts: Option<PieceCounts>, // Per-piece token counts of `text`, when already computed
 }
 
 /// Merges semantic segments into optimal chunks
@@ -20,13 +21,17 @@ pub struct SemanticChunk {
 /// 2. Keep adding segments while under token limit
 /// 3. When limit would be exceeded, finalize chunk and start new one
 /// 4. Ensures chunks are semantically coherent and efficiently sized
-pub struct ChunkMerger {
+/// 
+/// Each segment is encoded at most once; the size of a chunk extended by a segment
+/// is computed from both token counts, re-encoding only the text around the join
+/// (see [`CoreBPE::join_piece_counts`]), so merging is linear in the page length.
+pub struct ChunkMerger<'a> {
     target_tokens: usize,
-    tokenizer: CoreBPE,
+    tokenizer: &'a CoreBPE,
 }
 
-impl ChunkMerger {
-    pub fn new(target_tokens: usize, tokenizer: CoreBPE) -> Self {
+impl<'a> ChunkMerger<'a> {
+    pub fn new(target_tokens: usize, tokenizer: &'a CoreBPE) -> Self {
         Self {
             target_tokens,
             tokenizer,
@@ -41,6 +46,24 @@ impl ChunkMerger {
     /// 3. Ensure no chunk exceeds target_tokens
     /// 4. Handle edge cases (very large segments, empty segments)
     pub fn merge_segments(&self, segments: Vec<Segment>) -> Result<Vec<SemanticChunk>, ProcessingError> {
+        self.merge_with(segments, |current_text, _, segment| format!("{} {}", current_text, segment.text))
+    }
+    
+    /// Merge segments that are spans of `source`, taking each chunk's text
+    /// straight from `source` so the separators between segments (blank lines,
+    /// code indentation, table rows) are kept as written
+    pub fn merge_spans(&self, segments: Vec<Segment>, source: &str) -> Result<Vec<SemanticChunk>, ProcessingError> {
+        self.merge_with(segments, |_, current_start, segment| source[current_start..segment.end_offset].to_string())
+    }
+    
+    /// Greedy merge loop; `join(current_text, current_start_offset, segment)` builds
+    /// the text of the current chunk extended by `segment` (it must start with the
+    /// current text and end with the segment text)
+    fn merge_with(
+        &self,
+        segments: Vec<Segment>,
+        join: impl Fn(&str, usize, &Segment) -> String,
+    ) -> Result<Vec<SemanticChunk>, ProcessingError> {
         debug!("Merging {} segments into chunks (target: {} tokens)", segments.len(), self.target_tokens);
         
         if segments.is_empty() {
@@ -49,46 +72,49 @@ impl ChunkMerger {
         
         let mut chunks = Vec::new();
         let mut current_chunk_text = String::new();
+        let mut current_counts = PieceCounts::default();
         let mut current_chunk_segments = Vec::new();
         let mut current_start_offset = 0;
         let mut current_end_offset = 0;
         
-        for (i, segment) in segments.iter().enumerate() {
-            // Calculate potential new chunk text
-            let potential_text = if current_chunk_text.is_empty() {
-                segment.text.clone()
-            } else {
-                format!("{} {}", current_chunk_text, segment.text)
-            };
+        for (i, mut segment) in segments.into_iter().enumerate() {
+            // Token counts of the segment alone: from segmentation, or encoded once here
+            let segment_counts = segment.token_counts
+                .take()
+                .unwrap_or_else(|| self.tokenizer.piece_counts(&segment.text));
+            
+            // First segment of a chunk
+            if current_chunk_text.is_empty() {
+                current_chunk_text = segment.text;
+                current_counts = segment_counts;
+                current_chunk_segments = vec![i];
+                current_start_offset = segment.start_offset;
+                current_end_offset = segment.end_offset;
+                continue;
+            }
             
-            // Check token count of potential chunk
-            let potential_tokens = self.tokenizer.encode_ordinary(&potential_text).len();
+            // Calculate potential new chunk text and its token count
+            let potential_text = join(&current_chunk_text, current_start_offset, &segment);
+            let potential_counts = self.tokenizer.join_piece_counts(&potential_text, &current_counts, &segment_counts);
             
             // If adding this segment would exceed target, finalize current chunk
-            if potential_tokens > self.target_tokens && !current_chunk_text.is_empty() {
-                // Finalize current chunk
-                let chunk_tokens = self.tokenizer.encode_ordinary(&current_chunk_text).len();
+            if potential_counts.token_count() > self.target_tokens {
                 chunks.push(SemanticChunk {
-                    text: current_chunk_text.clone(),
-                    token_count: chunk_tokens,
+                    text: std::mem::replace(&mut current_chunk_text, segment.text),
+                    token_count: current_counts.token_count(),
                     start_offset: current_start_offset,
                     end_offset: current_end_offset,
-                    segments: current_chunk_segments.clone(),
+                    segments: std::mem::replace(&mut current_chunk_segments, vec![i]),
+                    token_counts: Some(std::mem::replace(&mut current_counts, segment_counts)),
                 });
                 
                 // Start new chunk with current segment
-                current_chunk_text = segment.text.clone();
-                current_chunk_segments = vec![i];
                 current_start_offset = segment.start_offset;
                 current_end_offset = segment.end_offset;
             } else {
                 // Add segment to current chunk
-                if current_chunk_text.is_empty() {
-                    current_chunk_text = segment.text.clone();
-                    current_start_offset = segment.start_offset;
-                } else {
-                    current_chunk_text = potential_text;
-                }
+                current_chunk_text = potential_text;
+                current_counts = potential_counts;
                 current_chunk_segments.push(i);
                 current_end_offset = segment.end_offset;
             }
@@ -96,13 +122,13 @@ impl ChunkMerger {
         
         // Finalize last chunk
         if !current_chunk_text.is_empty() {
-            let chunk_tokens = self.tokenizer.encode_ordinary(&current_chunk_text).len();
             chunks.push(SemanticChunk {
                 text: current_chunk_text,
-                token_count: chunk_tokens,
+                token_count: current_counts.token_count(),
                 start_offset: current_start_offset,
                 end_offset: current_end_offset,
                 segments: current_chunk_segments,
+                token_counts: Some(current_counts),
             });
         }
         
@@ -120,12 +146,12 @@ impl ChunkMerger {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::semantic_segmenter::{Segment, SemanticSegmenter};
+    use crate::semantic_segmenter::Segment;
 
     #[test]
     fn test_chunk_merging() {
         let tokenizer = CoreBPE::new_o200k_base().unwrap();
-        let merger = ChunkMerger::new(50, tokenizer);
+        let merger = ChunkMerger::new(50, &tokenizer);
         
         let segments = vec![
             Segment {
@@ -133,12 +159,14 @@ mod tests {
                 start_offset: 0,
                 end_offset: 15,
                 semantic_level: 3,
+                token_counts: None,
             },
             Segment {
                 text: "Second sentence.".to_string(),
                 start_offset: 16,
                 end_offset: 32,
                 semantic_level: 3,
+                token_counts: None,
             },
         ];
         
@@ -146,4 +174,35 @@ mod tests {
         assert!(!chunks.is_empty());
         assert!(chunks[0].token_count > 0);
     }
+    
+    #[test]
+    fn test_incremental_counts_match_encoding() {
+        let tokenizer = CoreBPE::new_o200k_base().unwrap();
+        let merger = ChunkMerger::new(12, &tokenizer);
+        
+        // Joins that change the split around the boundary ("don" + "'t", digits, spaces)
+        let source = "I don't know 12 345 6789.   Über café's naïve résumé! Tokens:  x y z";
+        let segments: Vec<Segment> = source
+            .split_inclusive(' ')
+            .scan(0, |offset, piece| {
+                let start = *offset;
+                *offset += piece.len();
+                Some(Segment {
+                    text: piece.to_string(),
+                    start_offset: start,
+                    end_offset: *offset,
+                    semantic_level: 5,
+                    token_counts: None,
+                })
+            })
+            .collect();
+        
+        for chunks in [merger.merge_segments(segments.clone()).unwrap(), merger.merge_spans(segments, source).unwrap()] {
+            assert!(chunks.len() > 1);
+            for chunk in &chunks {
+                assert_eq!(chunk.token_count, tokenizer.encode_ordinary(&chunk.text).len(), "{:?}", chunk.text);
+                assert!(chunk.token_count <= 12);
+            }
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use crate::chunk_merger::SemanticChunk;
-use crate::tiktoken_core::CoreBPE;
+use crate::tiktoken_core::{CoreBPE, PieceCounts};
 use crate::error::ProcessingError;
 use crate::chunking::ChunkMetadata;
 use log::debug;
@@ -10,13 +10,16 @@ use log::debug;
 /// 1. For each chunk after the first, include last N tokens from previous chunk
 /// 2. Updates token counts to reflect overlap
 /// 3. Maintains semantic boundaries when possible
-pub struct ChunkOverlapper {
+/// 
+/// Chunks are not encoded again: counts come from the merger's piece counts joined
+/// with the overlap, and only the tail of each chunk is encoded for the next overlap.
+pub struct ChunkOverlapper<'a> {
     overlap_tokens: usize,
-    tokenizer: CoreBPE,
+    tokenizer: &'a CoreBPE,
 }
 
-impl ChunkOverlapper {
-    pub fn new(overlap_tokens: usize, tokenizer: CoreBPE) -> Self {
+impl<'a> ChunkOverlapper<'a> {
+    pub fn new(overlap_tokens: usize, tokenizer: &'a CoreBPE) -> Self {
         Self {
             overlap_tokens,
             tokenizer,
@@ -45,29 +48,46 @@ impl ChunkOverlapper {
         let mut final_chunks = Vec::new();
         let mut previous_chunk_tokens: Option<Vec<u32>> = None;
         
-        for (chunk_id, semantic_chunk) in semantic_chunks.iter().enumerate() {
-            let chunk_text = if chunk_id == 0 || previous_chunk_tokens.is_none() {
-                // First chunk or no previous chunk - no overlap needed
-                semantic_chunk.text.clone()
-            } else {
+        for (chunk_id, mut semantic_chunk) in semantic_chunks.into_iter().enumerate() {
+            // Token counts of the chunk alone: from merging, or encoded once here
+            let chunk_counts = semantic_chunk.token_counts
+                .take()
+                .unwrap_or_else(|| self.tokenizer.piece_counts(&semantic_chunk.text));
+            let (chunk_text, chunk_counts) = match &previous_chunk_tokens {
                 // Add overlap from previous chunk
-                self.add_overlap_to_chunk(semantic_chunk, &previous_chunk_tokens.as_ref().unwrap())?
+                Some(previous_tokens) if chunk_id > 0 => {
+                    self.add_overlap_to_chunk(&semantic_chunk, chunk_counts, previous_tokens)?
+                }
+                // First chunk or no previous chunk - no overlap needed
+                _ => (semantic_chunk.text, chunk_counts),
             };
             
-            // Tokenize the final chunk text to get accurate count
-            let chunk_tokens = self.tokenizer.encode_ordinary(&chunk_text);
-            let token_count = chunk_tokens.len();
+            // Accurate count of the final chunk text
+            let token_count = chunk_counts.token_count();
             
-            // Store tokens for next iteration's overlap
-            previous_chunk_tokens = Some(chunk_tokens);
+            // Store the last tokens for next iteration's overlap
+            previous_chunk_tokens = Some(self.tokenizer.encode_suffix(&chunk_text, &chunk_counts, self.overlap_tokens));
             
             // Convert to ChunkMetadata format
             final_chunks.push(ChunkMetadata {
                 page: page_num,
+                page_label: None,
                 chunk_id,
+                content_id: String::new(),
+                document_id: String::new(),
                 text: chunk_text,
                 source: source.to_string(),
                 token_count,
+                // Span of the chunk's own content; the overlap belongs to the previous chunk
+                start_offset: semantic_chunk.start_offset,
+                end_offset: semantic_chunk.end_offset,
+                bboxes: Vec::new(),
+                section: None,
+                anchor: None,
+                chapter: None,
+                spine_index: None,
+                start_line: None,
+                end_line: None,
             });
             
             debug!("Chunk {}: {} tokens (with overlap)", chunk_id, token_count);
@@ -78,41 +98,60 @@ impl ChunkOverlapper {
     }
     
     /// Add overlap tokens from previous chunk to current chunk
+    /// 
+    /// `current_counts` are the piece counts of the current chunk text; returns the
+    /// combined text with its piece counts. `previous_tokens` may be just the tail of
+    /// the previous chunk's tokens, as long as it holds at least `overlap_tokens`.
     fn add_overlap_to_chunk(
         &self,
         current_chunk: &SemanticChunk,
+        current_counts: PieceCounts,
         previous_tokens: &[u32],
-    ) -> Result<String, ProcessingError> {
+    ) -> Result<(String, PieceCounts), ProcessingError> {
         if self.overlap_tokens == 0 || previous_tokens.is_empty() {
-            return Ok(current_chunk.text.clone());
+            return Ok((current_chunk.text.clone(), current_counts));
         }
         
-        // Get the last N tokens from previous chunk
-        let overlap_start = if previous_tokens.len() > self.overlap_tokens {
-            previous_tokens.len() - self.overlap_tokens
-        } else {
-            0
-        };
+        // Get the last N tokens from previous chunk, moved forward to a character
+        // boundary so the overlap never starts inside a multi-byte character
+        let overlap_start = self.tokenizer.ceil_char_boundary(
+            previous_tokens,
+            previous_tokens.len().saturating_sub(self.overlap_tokens),
+        );
         
         let overlap_tokens = &previous_tokens[overlap_start..];
         
         // Decode overlap tokens back to text
-        let overlap_text = self.tokenizer.decode(overlap_tokens)
+        let overlap_text = self.tokenizer.decode_lossy(overlap_tokens)
             .map_err(|e| ProcessingError::ChunkingError(
                 format!("Failed to decode overlap tokens: {}", e)
             ))?;
         
         // Combine overlap with current chunk
         // Add a space separator if both parts have content
-        let combined_text = if overlap_text.trim().is_empty() {
-            current_chunk.text.clone()
-        } else if current_chunk.text.trim().is_empty() {
-            overlap_text
+        if overlap_text.trim().is_empty() {
+            return Ok((current_chunk.text.clone(), current_counts));
+        }
+        if current_chunk.text.trim().is_empty() {
+            let counts = self.tokenizer.piece_counts(&overlap_text);
+            return Ok((overlap_text, counts));
+        }
+        
+        let overlap_text = overlap_text.trim();
+        let current_text = current_chunk.text.trim();
+        let current_counts = if current_text.len() == current_chunk.text.len() {
+            current_counts
         } else {
-            format!("{} {}", overlap_text.trim(), current_chunk.text.trim())
+            self.tokenizer.piece_counts(current_text)
         };
+        let combined_text = format!("{} {}", overlap_text, current_text);
+        let combined_counts = self.tokenizer.join_piece_counts(
+            &combined_text,
+            &self.tokenizer.piece_counts(overlap_text),
+            &current_counts,
+        );
         
-        Ok(combined_text)
+        Ok((combined_text, combined_counts))
     }
 }
 
@@ -124,7 +163,7 @@ mod tests {
     #[test]
     fn test_overlap_addition() {
         let tokenizer = CoreBPE::new_o200k_base().unwrap();
-        let overlapper = ChunkOverlapper::new(5, tokenizer);
+        let overlapper = ChunkOverlapper::new(5, &tokenizer);
         
         let chunks = vec![
             SemanticChunk {
@@ -133,6 +172,7 @@ mod tests {
                 start_offset: 0,
                 end_offset: 30,
                 segments: vec![0],
+                token_counts: None,
             },
             SemanticChunk {
                 text: "Second chunk with different content.".to_string(),
@@ -140,6 +180,7 @@ mod tests {
                 start_offset: 31,
                 end_offset: 67,
                 segments: vec![1],
+                token_counts: None,
             },
         ];
         
@@ -149,4 +190,31 @@ mod tests {
         assert!(result[1].text.len() > result[0].text.len()); // Second chunk should have overlap
         assert!(result[1].token_count > 6); // Should include overlap tokens
     }
+    
+    #[test]
+    fn test_overlap_starts_on_character_boundary() {
+        let tokenizer = CoreBPE::new_o200k_base().unwrap();
+        let previous = "Ends with rare letters 𝔷𝔶𝔵";
+        let previous_tokens = tokenizer.encode_ordinary(previous);
+        let chunk = SemanticChunk {
+            text: "Next chunk.".to_string(),
+            token_count: 3,
+            start_offset: 0,
+            end_offset: 11,
+            segments: vec![0],
+            token_counts: None,
+        };
+        
+        // Every overlap size either splits a character or lands between two
+        for overlap in 1..=previous_tokens.len() {
+            let overlapper = ChunkOverlapper::new(overlap, &tokenizer);
+            let counts = tokenizer.piece_counts(&chunk.text);
+            let (text, counts) = overlapper.add_overlap_to_chunk(&chunk, counts, &previous_tokens).unwrap();
+            assert_eq!(counts.token_count(), tokenizer.encode_ordinary(&text).len());
+            assert!(!text.contains('\u{FFFD}'), "overlap {} gave {:?}", overlap, text);
+            assert!(text.ends_with("Next chunk."));
+            let overlap_text = text.trim_end_matches("Next chunk.").trim();
+            assert!(previous.ends_with(overlap_text));
+        }
+    }
 }
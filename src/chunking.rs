@@ -1,25 +1,54 @@
 use crate::error::ProcessingError;
 use crate::tiktoken_core::CoreBPE;
+use crate::encodings::EncodingKind;
 use crate::semantic_chunker::SemanticChunker;
+use crate::code_segmenter::CodeLanguage;
+use crate::text_preprocessor::NormalizationOptions;
 use serde::{Serialize, Deserialize};
+use std::sync::Arc;
 use log::debug;
 
+/// Line rectangle of a chunk on a page, in PDF user-space coordinates
+/// 
+/// `(x0, y0)` is the lower-left and `(x1, y1)` the upper-right corner
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub page: usize, // 1-based physical page number
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
 /// Metadata structure for each text chunk
 /// 
 /// This represents the output format that will be converted to Python dictionaries
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkMetadata {
     pub page: usize,
-    pub chunk_id: usize,
+    pub page_label: Option<String>, // Logical page label from /PageLabels (e.g. "iv", "A-3")
+    pub chunk_id: usize,     // Index of the chunk on its page
+    pub content_id: String,  // Stable hash of source, page and text (see chunk_ids)
+    pub document_id: String, // SHA-256 fingerprint of the source file
     pub text: String,
     pub source: String,
     pub token_count: usize,  // Real tiktoken count
+    pub start_offset: usize, // Byte range of the input page text this chunk was drawn from
+    pub end_offset: usize,   // (excludes overlap prepended from the previous chunk)
+    pub bboxes: Vec<BoundingBox>, // One rectangle per text line covered by the chunk
+    pub section: Option<String>,  // Heading path ("Setup > Install") for structured documents
+    pub anchor: Option<String>,   // Closest preceding element id, for linking into HTML pages
+    pub chapter: Option<String>,  // Table of contents title (EPUB)
+    pub spine_index: Option<usize>, // 0-based position of the chapter document in the EPUB spine
+    pub start_line: Option<usize>,  // 1-based inclusive line range of the chunk (source code)
+    pub end_line: Option<usize>,
 }
 
 /// Chunking strategy options
 #[derive(Debug, Clone)]
 pub enum ChunkingStrategy {
     /// Simple token-based sliding window (original approach)
+    #[allow(dead_code)]
     SimpleToken,
     /// Semantic-aware chunking with recursive text splitting (new approach)
     SemanticAware,
@@ -35,25 +64,28 @@ pub enum ChunkingStrategy {
 pub struct TextChunker {
     chunk_size: usize,    // Target tokens per chunk (256)
     step_size: usize,     // How far to move the window (240 = 256 - 16)  
+    #[allow(dead_code)]
     overlap_size: usize,  // Overlap tokens (16)
-    tokenizer: CoreBPE,   // tiktoken tokenizer for accurate token counting
+    tokenizer: Arc<CoreBPE>, // tiktoken tokenizer for accurate token counting (process-wide instance)
     semantic_chunker: SemanticChunker, // New semantic-aware chunker
     strategy: ChunkingStrategy, // Which strategy to use
 }
 
 impl TextChunker {
     /// Create new text chunker with specified parameters and strategy
-    pub fn new(chunk_size: usize, overlap_size: usize) -> Result<Self, ProcessingError> {
+    /// 
+    /// Chunk sizes and overlaps are counted in tokens of `encoding`.
+    pub fn new(chunk_size: usize, overlap_size: usize, encoding: EncodingKind) -> Result<Self, ProcessingError> {
         let step_size = chunk_size.saturating_sub(overlap_size);
         
         debug!("Initializing text chunker: chunk_size={}, overlap={}, step_size={}", 
                chunk_size, overlap_size, step_size);
         
-        // Initialize tiktoken o200k_base tokenizer
-        let tokenizer = CoreBPE::new_o200k_base()?;
+        // Share the process-wide tiktoken tokenizer of the selected encoding
+        let tokenizer = CoreBPE::shared(encoding)?;
         
         // Initialize semantic chunker with same parameters
-        let semantic_chunker = SemanticChunker::new(chunk_size, overlap_size)?;
+        let semantic_chunker = SemanticChunker::new(chunk_size, overlap_size, encoding)?;
         
         Ok(TextChunker {
             chunk_size,
@@ -66,11 +98,25 @@ impl TextChunker {
     }
     
     /// Set chunking strategy
+    #[allow(dead_code)]
     pub fn with_strategy(mut self, strategy: ChunkingStrategy) -> Self {
         self.strategy = strategy;
         self
     }
     
+    /// Set the Unicode/typographic normalization used by semantic-aware chunking
+    pub fn with_normalization(mut self, normalization: NormalizationOptions) -> Self {
+        self.semantic_chunker = self.semantic_chunker.with_normalization(normalization);
+        self
+    }
+    
+    /// Count tokens with `tokenizer` (e.g. one loaded from a custom vocabulary file)
+    pub fn with_tokenizer(mut self, tokenizer: Arc<CoreBPE>) -> Self {
+        self.semantic_chunker = self.semantic_chunker.with_tokenizer(tokenizer.clone());
+        self.tokenizer = tokenizer;
+        self
+    }
+    
     /// Apply chunking logic to page text using selected strategy
     pub fn chunk_page_text(
         &self,
@@ -88,6 +134,44 @@ impl TextChunker {
         }
     }
     
+    /// Apply chunking to a markdown document, treating its headings, code blocks,
+    /// lists and tables as semantic units (simple strategy ignores the structure)
+    pub fn chunk_markdown(
+        &self,
+        page_num: usize,
+        text: &str,
+        source: &str,
+    ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        match self.strategy {
+            ChunkingStrategy::SimpleToken => {
+                self.chunk_page_text_simple(page_num, text, source)
+            }
+            ChunkingStrategy::SemanticAware => {
+                self.semantic_chunker.chunk_markdown(page_num, text, source)
+            }
+        }
+    }
+    
+    /// Apply chunking to source code, splitting along top-level units such as
+    /// functions and classes and reporting line ranges (simple strategy ignores
+    /// the structure)
+    pub fn chunk_code(
+        &self,
+        page_num: usize,
+        text: &str,
+        source: &str,
+        language: CodeLanguage,
+    ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        match self.strategy {
+            ChunkingStrategy::SimpleToken => {
+                self.chunk_page_text_simple(page_num, text, source)
+            }
+            ChunkingStrategy::SemanticAware => {
+                self.semantic_chunker.chunk_code(page_num, text, source, language)
+            }
+        }
+    }
+    
     /// Original simple token-based chunking (preserved for comparison)
     fn chunk_page_text_simple(
         &self,
@@ -109,10 +193,22 @@ impl TextChunker {
             
             return Ok(vec![ChunkMetadata {
                 page: page_num,
+                page_label: None,
                 chunk_id: 0,
+                content_id: String::new(),
+                document_id: String::new(),
                 text: text.to_string(),
                 source: source.to_string(),
                 token_count,
+                start_offset: 0,
+                end_offset: text.len(),
+                bboxes: Vec::new(),
+                section: None,
+                anchor: None,
+                chapter: None,
+                spine_index: None,
+                start_line: None,
+                end_line: None,
             }]);
         }
         
@@ -123,17 +219,30 @@ impl TextChunker {
         let mut chunk_id = 0;
         let mut start_token_idx = 0;
         
+        // Byte offset of every token boundary, so each window maps back onto the page text
+        let mut token_offsets = Vec::with_capacity(token_count + 1);
+        token_offsets.push(0);
+        for &token in &tokens {
+            let last = token_offsets[token_offsets.len() - 1];
+            token_offsets.push(last + self.tokenizer.token_byte_len(token));
+        }
+        
         // Sliding window loop over tokens
+        // Window edges are snapped to character boundaries: byte-level tokens of one
+        // character (emoji, CJK, accented letters) always stay in the same window
         while start_token_idx < token_count {
-            // Calculate end token index for current chunk
-            let end_token_idx = std::cmp::min(start_token_idx + self.chunk_size, token_count);
+            // Calculate end token index for current chunk (the window shrinks to stay within chunk_size)
+            let mut end_token_idx = self.tokenizer.floor_char_boundary(&tokens, start_token_idx + self.chunk_size);
+            if end_token_idx <= start_token_idx {
+                end_token_idx = self.tokenizer.ceil_char_boundary(&tokens, start_token_idx + 1);
+            }
             
             // Extract tokens for current chunk
             let chunk_tokens = &tokens[start_token_idx..end_token_idx];
             let chunk_token_count = chunk_tokens.len();
             
-            // Decode tokens back to text
-            let chunk_text = self.tokenizer.decode(chunk_tokens)
+            // Decode tokens back to text (whole characters only, so nothing is replaced)
+            let chunk_text = self.tokenizer.decode_lossy(chunk_tokens)
                 .map_err(|e| ProcessingError::ChunkingError(
                     format!("Failed to decode tokens for page {} chunk {}: {}", page_num, chunk_id, e)
                 ))?;
@@ -144,10 +253,22 @@ impl TextChunker {
             // Create chunk metadata
             chunks.push(ChunkMetadata {
                 page: page_num,
+                page_label: None,
                 chunk_id,
+                content_id: String::new(),
+                document_id: String::new(),
                 text: chunk_text,
                 source: source.to_string(),
                 token_count: chunk_token_count,
+                start_offset: token_offsets[start_token_idx],
+                end_offset: token_offsets[end_token_idx],
+                bboxes: Vec::new(),
+                section: None,
+                anchor: None,
+                chapter: None,
+                spine_index: None,
+                start_line: None,
+                end_line: None,
             });
             
             // Break if we've reached the end
@@ -156,8 +277,13 @@ impl TextChunker {
             }
             
             // Move window forward by step_size (240 tokens for 256-16)
-            // This creates the 16-token overlap
-            start_token_idx += self.step_size;
+            // This creates the 16-token overlap (a little more when snapped back)
+            let next_start_idx = self.tokenizer.floor_char_boundary(&tokens, start_token_idx + self.step_size);
+            start_token_idx = if next_start_idx > start_token_idx {
+                next_start_idx
+            } else {
+                self.tokenizer.ceil_char_boundary(&tokens, start_token_idx + 1)
+            };
             chunk_id += 1;
             
             // Safety check to prevent infinite loops
@@ -171,4 +297,28 @@ impl TextChunker {
         debug!("Page {} simple token-based chunking complete: {} chunks generated", page_num, chunks.len());
         Ok(chunks)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_simple_windows_keep_characters_whole() {
+        let chunker = TextChunker::new(7, 2, EncodingKind::O200kBase)
+            .unwrap()
+            .with_strategy(ChunkingStrategy::SimpleToken);
+        // Rare 4-byte characters are split into byte-level tokens by o200k_base
+        let text = "Grüße 𝔷𝔶𝔵 𝕬𝕭𝕮 ok 🜁🜂🜃🜄 done".repeat(3);
+        
+        let chunks = chunker.chunk_page_text(1, &text, "test.txt").unwrap();
+        assert!(chunks.len() > 3);
+        for chunk in &chunks {
+            assert_eq!(chunk.text, text[chunk.start_offset..chunk.end_offset]);
+            assert!(!chunk.text.contains('\u{FFFD}'));
+        }
+        assert_eq!(chunks[0].start_offset, 0);
+        assert_eq!(chunks.last().unwrap().end_offset, text.len());
+        assert!(chunks.windows(2).all(|pair| pair[1].start_offset <= pair[0].end_offset));
+    }
+}
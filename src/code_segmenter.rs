@@ -0,0 +1,325 @@
+use std::ops::Range;
+use log::debug;
+
+/// Programming languages recognized for code-aware chunking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeLanguage {
+    Rust,
+    C,
+    Cpp,
+    CSharp,
+    Java,
+    Kotlin,
+    Scala,
+    Swift,
+    Go,
+    JavaScript,
+    TypeScript,
+    Php,
+    Python,
+    Ruby,
+    Shell,
+}
+
+impl CodeLanguage {
+    /// Detect the language from a file extension (case-insensitive)
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let language = match extension.to_ascii_lowercase().as_str() {
+            "rs" => CodeLanguage::Rust,
+            "c" | "h" => CodeLanguage::C,
+            "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => CodeLanguage::Cpp,
+            "cs" => CodeLanguage::CSharp,
+            "java" => CodeLanguage::Java,
+            "kt" | "kts" => CodeLanguage::Kotlin,
+            "scala" => CodeLanguage::Scala,
+            "swift" => CodeLanguage::Swift,
+            "go" => CodeLanguage::Go,
+            "js" | "jsx" | "mjs" | "cjs" => CodeLanguage::JavaScript,
+            "ts" | "tsx" | "mts" | "cts" => CodeLanguage::TypeScript,
+            "php" => CodeLanguage::Php,
+            "py" | "pyi" => CodeLanguage::Python,
+            "rb" => CodeLanguage::Ruby,
+            "sh" | "bash" | "zsh" => CodeLanguage::Shell,
+            _ => return None,
+        };
+        Some(language)
+    }
+    
+    fn syntax(self) -> Syntax {
+        use CodeLanguage::*;
+        let c_like = Syntax {
+            line_comments: &["//"],
+            block_comment: Some(("/*", "*/")),
+            quotes: &['"'],
+            char_literals: true,
+            triple_quotes: false,
+            indented_blocks: false,
+        };
+        match self {
+            Rust | C | Cpp | CSharp | Java | Kotlin | Scala => c_like,
+            Swift => Syntax { char_literals: false, triple_quotes: true, ..c_like },
+            Go => Syntax { quotes: &['"', '`'], ..c_like },
+            JavaScript | TypeScript => Syntax { quotes: &['"', '\'', '`'], char_literals: false, ..c_like },
+            Php => Syntax { line_comments: &["//", "#"], quotes: &['"', '\''], char_literals: false, ..c_like },
+            Python => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: &['"', '\''],
+                char_literals: false,
+                triple_quotes: true,
+                indented_blocks: true,
+            },
+            Ruby => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: &['"', '\''],
+                char_literals: false,
+                triple_quotes: false,
+                indented_blocks: true,
+            },
+            Shell => Syntax { line_comments: &["#"], block_comment: None, quotes: &['"', '\''], char_literals: false, ..c_like },
+        }
+    }
+}
+
+/// Lexical rules needed to balance brackets without a full parser
+#[derive(Debug, Clone, Copy)]
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],   // String delimiters
+    char_literals: bool,       // `'x'` is a character literal (a lone `'` is a lifetime/label)
+    triple_quotes: bool,       // `"""` strings
+    indented_blocks: bool,     // Indented lines continue the block above (Python, Ruby)
+}
+
+/// Lines at top level that still belong to the unit before them
+const CONTINUATION_KEYWORDS: [&str; 12] = [
+    "else", "elif", "elsif", "except", "finally", "catch", "rescue", "ensure", "end", "fi", "done", "esac",
+];
+
+/// Where the scanner is between lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Code,
+    BlockComment,
+    Str { delimiter: char, triple: bool },
+}
+
+/// Bracket depth and open literal carried from one line to the next
+#[derive(Debug, Clone, Copy)]
+struct ScanState {
+    depth: usize,
+    mode: Mode,
+}
+
+/// Line-based source code scanner
+/// 
+/// Splits source files into top-level units (functions, classes, impl blocks,
+/// import groups, ...) without parsing the language:
+/// 1. Brackets are balanced across lines, skipping strings, character literals
+///    and comments, so blank lines inside a body never split it
+/// 2. A blank line at bracket depth 0 ends the current unit, unless the next line
+///    continues it (`else`, `except`, a closing bracket, or an indented line in
+///    indentation-based languages)
+/// 3. Comments and attributes directly above a declaration stay with it
+/// 
+/// Units too large for one chunk are split further at blank lines, then per line.
+pub struct CodeSegmenter;
+
+impl CodeSegmenter {
+    pub fn new() -> Self {
+        CodeSegmenter
+    }
+    
+    /// Split source code into top-level units (byte ranges without trailing line breaks)
+    pub fn units(&self, text: &str, language: CodeLanguage) -> Vec<Range<usize>> {
+        let syntax = language.syntax();
+        let mut state = ScanState { depth: 0, mode: Mode::Code };
+        let mut units: Vec<Range<usize>> = Vec::new();
+        let mut current: Option<Range<usize>> = None;
+        let mut blank_at_top_level = false;
+        
+        for range in line_ranges(text, 0..text.len()) {
+            let line = &text[range.clone()];
+            if line.trim().is_empty() {
+                blank_at_top_level |= state.depth == 0 && state.mode == Mode::Code;
+                continue;
+            }
+            if blank_at_top_level && starts_unit(line, syntax) {
+                units.extend(current.take());
+            }
+            blank_at_top_level = false;
+            
+            match current.as_mut() {
+                Some(unit) => unit.end = range.end,
+                None => current = Some(range),
+            }
+            scan_line(line, syntax, &mut state);
+        }
+        units.extend(current);
+        
+        debug!("{:?} source split into {} top-level units", language, units.len());
+        units
+    }
+    
+    /// Runs of non-blank lines within `range`, regardless of nesting
+    pub fn blocks(&self, text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut blocks: Vec<Range<usize>> = Vec::new();
+        let mut current: Option<Range<usize>> = None;
+        for line in line_ranges(text, range) {
+            if text[line.clone()].trim().is_empty() {
+                blocks.extend(current.take());
+            } else {
+                match current.as_mut() {
+                    Some(block) => block.end = line.end,
+                    None => current = Some(line),
+                }
+            }
+        }
+        blocks.extend(current);
+        blocks
+    }
+    
+    /// Non-blank lines within `range`
+    pub fn lines(&self, text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+        line_ranges(text, range).filter(|line| !text[line.clone()].trim().is_empty()).collect()
+    }
+}
+
+/// Byte ranges of the lines of `text[range]`, without their line breaks
+fn line_ranges(text: &str, range: Range<usize>) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = range.start;
+    text[range.clone()].split_inclusive('\n').map(move |raw| {
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let line_range = start..start + line.len();
+        start += raw.len();
+        line_range
+    })
+}
+
+/// Whether a top-level line after a blank line begins a new unit
+fn starts_unit(line: &str, syntax: Syntax) -> bool {
+    let trimmed = line.trim_start();
+    if syntax.indented_blocks && trimmed.len() != line.len() {
+        return false;
+    }
+    let word = trimmed.split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or("");
+    !CONTINUATION_KEYWORDS.contains(&word) && !trimmed.starts_with(['}', ')', ']'])
+}
+
+/// Advance the bracket depth and literal state over one line
+fn scan_line(line: &str, syntax: Syntax, state: &mut ScanState) {
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let c = rest.chars().next().expect("index on a char boundary");
+        match state.mode {
+            Mode::BlockComment => {
+                let (_, close) = syntax.block_comment.expect("block comment without syntax");
+                match rest.find(close) {
+                    Some(end) => {
+                        state.mode = Mode::Code;
+                        i += end + close.len();
+                    }
+                    None => i = line.len(),
+                }
+                continue;
+            }
+            Mode::Str { delimiter, triple } => {
+                if c == '\\' {
+                    // Skip the escaped character (or the line continuation)
+                    i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+                    continue;
+                }
+                if triple {
+                    if rest.starts_with(&delimiter.to_string().repeat(3)) {
+                        state.mode = Mode::Code;
+                        i += 3;
+                        continue;
+                    }
+                } else if c == delimiter {
+                    state.mode = Mode::Code;
+                }
+            }
+            Mode::Code => {
+                if syntax.line_comments.iter().any(|marker| rest.starts_with(marker)) {
+                    return;
+                }
+                if let Some((open, _)) = syntax.block_comment
+                    && rest.starts_with(open)
+                {
+                    state.mode = Mode::BlockComment;
+                    i += open.len();
+                    continue;
+                }
+                if syntax.quotes.contains(&c) {
+                    let triple = syntax.triple_quotes && rest.starts_with(&c.to_string().repeat(3));
+                    state.mode = Mode::Str { delimiter: c, triple };
+                    i += if triple { 3 } else { 1 };
+                    continue;
+                }
+                if c == '\'' && syntax.char_literals {
+                    i += char_literal_len(rest);
+                    continue;
+                }
+                match c {
+                    '{' | '(' | '[' => state.depth += 1,
+                    '}' | ')' | ']' => state.depth = state.depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+        i += c.len_utf8();
+    }
+}
+
+/// Length of a character literal (`'x'`, `'\n'`, `'\u{1F600}'`) at the start of
+/// `text`, or 1 for a quote that opens none (Rust lifetimes and loop labels)
+fn char_literal_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => text[1..].char_indices()
+            .skip(2)
+            .take(12)
+            .find(|(_, c)| *c == '\'')
+            .map_or(1, |(end, _)| end + 2),
+        Some((_, c)) if c != '\'' => match chars.next() {
+            Some((end, '\'')) => end + 1,
+            _ => 1,
+        },
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn unit_texts(text: &str, language: CodeLanguage) -> Vec<&str> {
+        CodeSegmenter::new().units(text, language).into_iter().map(|r| &text[r]).collect()
+    }
+    
+    #[test]
+    fn test_code_units() {
+        let rust = "use std::fmt;\nuse std::io;\n\n/// Docs\n#[derive(Debug)]\nstruct A<'a> {\n    s: &'a str,\n\n    c: char,\n}\n\n\
+                    impl<'a> A<'a> {\n    fn f(&self) -> char {\n        let brace = '{';\n        // }\n\n        \"}\\\"\".len();\n        brace\n    }\n}\n\n\
+                    /* } */\nfn main() {}";
+        assert_eq!(unit_texts(rust, CodeLanguage::Rust), vec![
+            "use std::fmt;\nuse std::io;",
+            "/// Docs\n#[derive(Debug)]\nstruct A<'a> {\n    s: &'a str,\n\n    c: char,\n}",
+            "impl<'a> A<'a> {\n    fn f(&self) -> char {\n        let brace = '{';\n        // }\n\n        \"}\\\"\".len();\n        brace\n    }\n}",
+            "/* } */\nfn main() {}",
+        ]);
+        
+        let python = "import os\n\n@cache\ndef f(x):\n    \"\"\"Doc\n\n    (\"\"\"\n\n    return x\n\n\nif x:\n    pass\n\nelse:\n    pass\n";
+        assert_eq!(unit_texts(python, CodeLanguage::Python), vec![
+            "import os",
+            "@cache\ndef f(x):\n    \"\"\"Doc\n\n    (\"\"\"\n\n    return x",
+            "if x:\n    pass\n\nelse:\n    pass",
+        ]);
+        
+        assert_eq!(CodeLanguage::from_extension("TSX"), Some(CodeLanguage::TypeScript));
+        assert_eq!(CodeLanguage::from_extension("txt"), None);
+    }
+}
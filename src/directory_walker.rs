@@ -0,0 +1,165 @@
+use crate::error::ProcessingError;
+use crate::pdf_processor::FileKind;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use log::{debug, info, warn};
+
+/// Include/exclude glob rules for directory ingestion
+/// 
+/// Patterns are matched against paths relative to the walked root, with `/`
+/// separators on every platform. `*` does not cross directories, `**` does:
+/// `*.pdf` only matches files in the root, `**/*.pdf` matches them anywhere.
+/// Without include patterns every file is a candidate. Directories matching an
+/// exclude pattern (`**/node_modules`, or `**/node_modules/**`) are not entered.
+pub struct FileRules {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    exclude_dirs: GlobSet, // Exclude patterns with a trailing `/**` dropped, matched against directories
+}
+
+impl FileRules {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, ProcessingError> {
+        let include = if include.is_empty() { None } else { Some(glob_set(include.iter().map(String::as_str))?) };
+        let exclude_set = glob_set(exclude.iter().map(String::as_str))?;
+        let exclude_dirs = glob_set(exclude.iter().map(|pattern| pattern.strip_suffix("/**").unwrap_or(pattern)))?;
+        
+        Ok(Self { include, exclude: exclude_set, exclude_dirs })
+    }
+    
+    fn is_included(&self, relative: &str) -> bool {
+        self.include.as_ref().is_none_or(|include| include.is_match(relative))
+    }
+}
+
+/// Compile glob patterns into one set; an invalid pattern is an argument error
+fn glob_set<'a>(patterns: impl Iterator<Item = &'a str>) -> Result<GlobSet, ProcessingError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob: Glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| ProcessingError::InvalidArgument(format!("Invalid glob pattern '{}': {}", pattern, e)))?;
+        builder.add(glob);
+    }
+    builder.build()
+        .map_err(|e| ProcessingError::InvalidArgument(format!("Invalid glob patterns: {}", e)))
+}
+
+/// A candidate file that was not processed, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub path: String, // Relative to the walked root, `/`-separated
+    pub reason: String,
+}
+
+/// Files selected from a directory tree
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryListing {
+    /// Supported files to process: relative path (`/`-separated) and full path, sorted
+    pub files: Vec<(String, PathBuf)>,
+    /// Included files that are excluded or of an unsupported type
+    pub skipped: Vec<SkippedFile>,
+    /// Entries the walk could not read: relative path (or root) and error message
+    pub failed: Vec<(String, String)>,
+}
+
+/// Walk `root` and select the files matching `rules`
+/// 
+/// Process:
+/// 1. Walk the tree in file name order without following symlinks, pruning
+///    excluded directories
+/// 2. Keep files matching an include pattern (all files without includes)
+/// 3. Report included files that match an exclude pattern or have no reader
+///    (see [`FileKind`]) as skipped, and unreadable entries as failed
+pub fn list_files(root: &Path, rules: &FileRules) -> Result<DirectoryListing, ProcessingError> {
+    if !root.is_dir() {
+        return Err(ProcessingError::InvalidArgument(format!("{} is not a directory", root.display())));
+    }
+    info!("Scanning directory: {}", root.display());
+    
+    let mut listing = DirectoryListing::default();
+    let walker = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !rules.exclude_dirs.is_match(relative_path(root, entry.path()))
+        });
+    
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = e.path().map_or_else(|| ".".to_string(), |path| relative_path(root, path));
+                warn!("Cannot read {}: {}", path, e);
+                listing.failed.push((path, e.to_string()));
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        
+        let relative = relative_path(root, entry.path());
+        if !rules.is_included(&relative) {
+            continue;
+        }
+        let reason = if rules.exclude.is_match(&relative) {
+            Some("excluded by pattern".to_string())
+        } else {
+            FileKind::from_path(entry.path()).err().map(|_| "unsupported file type".to_string())
+        };
+        match reason {
+            Some(reason) => {
+                debug!("Skipping {}: {}", relative, reason);
+                listing.skipped.push(SkippedFile { path: relative, reason });
+            }
+            None => listing.files.push((relative, entry.into_path())),
+        }
+    }
+    
+    info!("Selected {} files ({} skipped, {} unreadable)", listing.files.len(), listing.skipped.len(), listing.failed.len());
+    Ok(listing)
+}
+
+/// Path relative to `root` with `/` separators
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_list_files() {
+        let root = std::env::temp_dir().join(format!("myrustchunker-walk-{}", std::process::id()));
+        for file in ["a.pdf", "notes/b.md", "notes/draft-1.md", "notes/c.png", "node_modules/x/d.md", "build/e.md"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "text").unwrap();
+        }
+        
+        let include = ["**/*.pdf", "**/*.md", "notes/*.png"].map(String::from);
+        let exclude = ["**/node_modules/**", "**/draft-*", "build"].map(String::from);
+        let listing = list_files(&root, &FileRules::new(&include, &exclude).unwrap());
+        std::fs::remove_dir_all(&root).unwrap();
+        let listing = listing.unwrap();
+        
+        let files: Vec<&str> = listing.files.iter().map(|(relative, _)| relative.as_str()).collect();
+        assert_eq!(files, vec!["a.pdf", "notes/b.md"]);
+        assert_eq!(listing.skipped, vec![
+            SkippedFile { path: "notes/c.png".to_string(), reason: "unsupported file type".to_string() },
+            SkippedFile { path: "notes/draft-1.md".to_string(), reason: "excluded by pattern".to_string() },
+        ]);
+        assert!(listing.failed.is_empty());
+        
+        assert!(FileRules::new(&["[".to_string()], &[]).is_err());
+    }
+}
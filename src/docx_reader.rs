@@ -0,0 +1,477 @@
+use crate::error::ProcessingError;
+use crate::markdown_segmenter::MarkdownSegmenter;
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+use zip::result::ZipError;
+use log::{debug, info};
+
+/// WordprocessingML main namespace
+const W_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+/// Reads Word (.docx) documents as markdown-structured text
+/// 
+/// A .docx file is a zip archive of WordprocessingML parts. The body of
+/// `word/document.xml` is walked in document order and rendered as markdown,
+/// so the markdown-aware chunker sees the same structure the author did:
+/// - Paragraphs with a heading style (`Heading1`..`Heading6`, `Title`, or a
+///   style with an outline level) become `#`..`######` headings
+/// - Numbered or bulleted paragraphs (`w:numPr`, directly or via their style)
+///   become list items, indented by list level
+/// - Tables become pipe tables with the first row as header
+/// - All other paragraphs become plain paragraphs, with line-leading markdown
+///   (`#`, `- `, `1. `, `|`, fences) escaped so they stay paragraphs
+pub struct DocxReader;
+
+impl DocxReader {
+    pub fn new() -> Self {
+        DocxReader
+    }
+    
+    /// Open a .docx file and convert its body to markdown
+    pub fn read_markdown(&self, path: &Path) -> Result<String, ProcessingError> {
+        info!("Reading DOCX: {}", path.display());
+        
+        let file = std::fs::File::open(path)?;
+        let mut archive = ZipArchive::new(file)
+            .map_err(|e| ProcessingError::DocumentLoadError(format!("{} is not a valid .docx archive: {}", path.display(), e)))?;
+        
+        let document = read_part(&mut archive, "word/document.xml")?.ok_or_else(|| {
+            ProcessingError::DocumentLoadError(format!("{} has no word/document.xml part", path.display()))
+        })?;
+        let styles = read_part(&mut archive, "word/styles.xml")?;
+        let numbering = read_part(&mut archive, "word/numbering.xml")?;
+        
+        docx_to_markdown(&document, styles.as_deref(), numbering.as_deref())
+            .map_err(|e| ProcessingError::DocumentLoadError(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// Read one archive member as UTF-8 text; a missing member is `None`
+pub(crate) fn read_part<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Option<String>, ProcessingError> {
+    let mut part = match archive.by_name(name) {
+        Ok(part) => part,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(ProcessingError::DocumentLoadError(format!("Failed to read {}: {}", name, e))),
+    };
+    let mut xml = String::new();
+    part.read_to_string(&mut xml)?;
+    Ok(Some(xml))
+}
+
+/// Convert the WordprocessingML parts of a document to markdown
+/// 
+/// `styles_xml` and `numbering_xml` are optional; without them headings are
+/// recognized from built-in style ids and every list is rendered as bullets.
+pub fn docx_to_markdown(
+    document_xml: &str,
+    styles_xml: Option<&str>,
+    numbering_xml: Option<&str>,
+) -> Result<String, String> {
+    let document = Document::parse(document_xml).map_err(|e| format!("invalid document.xml: {}", e))?;
+    let styles = match styles_xml {
+        Some(xml) => StyleMap::parse(&Document::parse(xml).map_err(|e| format!("invalid styles.xml: {}", e))?),
+        None => StyleMap::default(),
+    };
+    let numbering = match numbering_xml {
+        Some(xml) => Numbering::parse(&Document::parse(xml).map_err(|e| format!("invalid numbering.xml: {}", e))?),
+        None => Numbering::default(),
+    };
+    
+    let body = document
+        .root_element()
+        .children()
+        .find(|n| is_w(n, "body"))
+        .ok_or("document.xml has no w:body")?;
+    
+    let mut writer = MarkdownWriter {
+        styles: &styles,
+        numbering: &numbering,
+        markdown: MarkdownSegmenter::new(),
+        blocks: Vec::new(),
+        list_lines: Vec::new(),
+        counters: HashMap::new(),
+    };
+    writer.body(body);
+    writer.flush_list();
+    
+    debug!("Converted DOCX body into {} markdown blocks", writer.blocks.len());
+    Ok(writer.blocks.join("\n\n"))
+}
+
+/// Whether `node` is the WordprocessingML element `name`
+fn is_w(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some(W_NS)
+}
+
+/// First child element named `name`
+fn w_child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| is_w(n, name))
+}
+
+/// `w:val` attribute of the child element `name`
+fn w_val<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    w_child(node, name)?.attribute((W_NS, "val"))
+}
+
+/// Paragraph style properties relevant to structure
+#[derive(Debug, Clone, Default)]
+struct StyleInfo {
+    heading_level: Option<usize>,
+    numbering: Option<(String, usize)>, // (numId, ilvl) from the style's own w:numPr
+    based_on: Option<String>,
+}
+
+/// Paragraph styles from `word/styles.xml`, keyed by style id
+#[derive(Debug, Default)]
+struct StyleMap {
+    styles: HashMap<String, StyleInfo>,
+}
+
+impl StyleMap {
+    fn parse(document: &Document) -> Self {
+        let mut styles = HashMap::new();
+        for style in document.root_element().children().filter(|n| is_w(n, "style")) {
+            if style.attribute((W_NS, "type")) != Some("paragraph") {
+                continue;
+            }
+            let Some(id) = style.attribute((W_NS, "styleId")) else {
+                continue;
+            };
+            
+            let name = w_val(style, "name").unwrap_or(id);
+            let outline_level = w_child(style, "pPr")
+                .and_then(|ppr| w_val(ppr, "outlineLvl"))
+                .and_then(|level| level.parse::<usize>().ok())
+                .filter(|&level| level < 9)
+                .map(|level| level + 1);
+            let info = StyleInfo {
+                heading_level: builtin_heading_level(name).or(outline_level),
+                numbering: w_child(style, "pPr").and_then(numbering_properties),
+                based_on: w_val(style, "basedOn").map(str::to_string),
+            };
+            styles.insert(id.to_string(), info);
+        }
+        StyleMap { styles }
+    }
+    
+    /// Heading level of a style, following `w:basedOn` chains; unknown style ids
+    /// fall back to the built-in names (`Heading2`, `Title`)
+    fn heading_level(&self, style_id: &str) -> Option<usize> {
+        let mut id = style_id;
+        for _ in 0..16 {
+            let Some(info) = self.styles.get(id) else {
+                return builtin_heading_level(id);
+            };
+            if let Some(level) = info.heading_level {
+                return Some(level);
+            }
+            id = info.based_on.as_deref()?;
+        }
+        None
+    }
+    
+    /// List numbering a style applies to its paragraphs, following `w:basedOn`
+    fn numbering(&self, style_id: &str) -> Option<(String, usize)> {
+        let mut id = style_id;
+        for _ in 0..16 {
+            let info = self.styles.get(id)?;
+            if let Some(numbering) = &info.numbering {
+                return Some(numbering.clone());
+            }
+            id = info.based_on.as_deref()?;
+        }
+        None
+    }
+}
+
+/// Heading level for built-in style names/ids ("heading 1", "Heading1", "Title")
+fn builtin_heading_level(name: &str) -> Option<usize> {
+    let name = name.to_ascii_lowercase().replace(' ', "");
+    if name == "title" {
+        return Some(1);
+    }
+    name.strip_prefix("heading")
+        .and_then(|level| level.parse::<usize>().ok())
+        .filter(|level| (1..=9).contains(level))
+        .map(|level| level.min(6))
+}
+
+/// `(numId, ilvl)` of a `w:pPr` element's `w:numPr`; numId 0 means "no list"
+fn numbering_properties(ppr: Node) -> Option<(String, usize)> {
+    let num_pr = w_child(ppr, "numPr")?;
+    let num_id = w_val(num_pr, "numId")?;
+    if num_id == "0" {
+        return None;
+    }
+    let level = w_val(num_pr, "ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+    Some((num_id.to_string(), level))
+}
+
+/// List formats from `word/numbering.xml`: whether a (numId, ilvl) is numbered
+#[derive(Debug, Default)]
+struct Numbering {
+    ordered: HashMap<(String, usize), bool>,
+}
+
+impl Numbering {
+    fn parse(document: &Document) -> Self {
+        let root = document.root_element();
+        
+        // abstractNumId -> ilvl -> numbered?
+        let mut abstract_formats: HashMap<&str, HashMap<usize, bool>> = HashMap::new();
+        for abstract_num in root.children().filter(|n| is_w(n, "abstractNum")) {
+            let Some(id) = abstract_num.attribute((W_NS, "abstractNumId")) else {
+                continue;
+            };
+            let levels = abstract_num
+                .children()
+                .filter(|n| is_w(n, "lvl"))
+                .filter_map(|lvl| {
+                    let level = lvl.attribute((W_NS, "ilvl"))?.parse().ok()?;
+                    let format = w_val(lvl, "numFmt").unwrap_or("bullet");
+                    Some((level, !matches!(format, "bullet" | "none")))
+                })
+                .collect();
+            abstract_formats.insert(id, levels);
+        }
+        
+        let mut ordered = HashMap::new();
+        for num in root.children().filter(|n| is_w(n, "num")) {
+            let (Some(num_id), Some(abstract_id)) = (num.attribute((W_NS, "numId")), w_val(num, "abstractNumId")) else {
+                continue;
+            };
+            for (level, is_ordered) in abstract_formats.get(abstract_id).into_iter().flatten() {
+                ordered.insert((num_id.to_string(), *level), *is_ordered);
+            }
+        }
+        Numbering { ordered }
+    }
+    
+    fn is_ordered(&self, num_id: &str, level: usize) -> bool {
+        self.ordered.get(&(num_id.to_string(), level)).copied().unwrap_or(false)
+    }
+}
+
+/// Accumulates markdown blocks while walking the document body
+struct MarkdownWriter<'a> {
+    styles: &'a StyleMap,
+    numbering: &'a Numbering,
+    markdown: MarkdownSegmenter,                 // Escapes markup in plain paragraphs
+    blocks: Vec<String>,
+    list_lines: Vec<String>,                     // Items of the list being built
+    counters: HashMap<(String, usize), usize>,   // Next number per (numId, ilvl)
+}
+
+impl MarkdownWriter<'_> {
+    /// Walk block-level content (body, structured document tags)
+    fn body(&mut self, container: Node) {
+        for node in container.children() {
+            if is_w(&node, "p") {
+                self.paragraph(node);
+            } else if is_w(&node, "tbl") {
+                self.flush_list();
+                self.table(node);
+            } else if is_w(&node, "sdt")
+                && let Some(content) = w_child(node, "sdtContent")
+            {
+                self.body(content);
+            }
+        }
+    }
+    
+    fn paragraph(&mut self, paragraph: Node) {
+        let text = paragraph_text(paragraph);
+        let text = text.trim();
+        if text.is_empty() {
+            // Spacing paragraphs do not interrupt lists
+            return;
+        }
+        
+        let ppr = w_child(paragraph, "pPr");
+        let style = ppr.and_then(|ppr| w_val(ppr, "pStyle"));
+        
+        if let Some(level) = style.and_then(|style| self.styles.heading_level(style)) {
+            self.flush_list();
+            self.blocks.push(format!("{} {}", "#".repeat(level), single_line(text)));
+            return;
+        }
+        
+        let numbering = ppr
+            .and_then(numbering_properties)
+            .or_else(|| style.and_then(|style| self.styles.numbering(style)));
+        match numbering {
+            Some((num_id, level)) => {
+                let marker = if self.numbering.is_ordered(&num_id, level) {
+                    // Deeper levels restart whenever a shallower item appears
+                    self.counters.retain(|(id, l), _| id != &num_id || *l <= level);
+                    let counter = self.counters.entry((num_id, level)).or_insert(0);
+                    *counter += 1;
+                    format!("{}.", counter)
+                } else {
+                    "-".to_string()
+                };
+                self.list_lines.push(format!("{}{} {}", "  ".repeat(level), marker, single_line(text)));
+            }
+            None => {
+                self.flush_list();
+                self.blocks.push(self.markdown.escape_paragraph(text));
+            }
+        }
+    }
+    
+    fn table(&mut self, table: Node) {
+        let rows: Vec<Vec<String>> = table
+            .children()
+            .filter(|n| is_w(n, "tr"))
+            .map(|row| {
+                let mut cells = Vec::new();
+                for cell in row.children().filter(|n| is_w(n, "tc")) {
+                    cells.push(cell_text(cell));
+                    // Horizontally merged cells keep the column grid aligned
+                    let span = w_child(cell, "tcPr")
+                        .and_then(|tcpr| w_val(tcpr, "gridSpan"))
+                        .and_then(|span| span.parse::<usize>().ok())
+                        .unwrap_or(1);
+                    cells.extend(std::iter::repeat_n(String::new(), span.saturating_sub(1)));
+                }
+                cells
+            })
+            .filter(|cells| !cells.is_empty())
+            .collect();
+        
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 || rows.iter().flatten().all(|cell| cell.is_empty()) {
+            return;
+        }
+        
+        let render = |cells: &[String]| {
+            let padded = (0..columns).map(|i| cells.get(i).map(String::as_str).unwrap_or(""));
+            format!("| {} |", padded.collect::<Vec<_>>().join(" | "))
+        };
+        let mut lines = vec![render(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+        lines.extend(rows[1..].iter().map(|row| render(row)));
+        self.blocks.push(lines.join("\n"));
+    }
+    
+    /// Emit the pending list as one block
+    fn flush_list(&mut self) {
+        if !self.list_lines.is_empty() {
+            self.blocks.push(self.list_lines.join("\n"));
+            self.list_lines.clear();
+            self.counters.clear();
+        }
+    }
+}
+
+/// Text of a paragraph's runs (including hyperlinks, insertions and text boxes)
+fn paragraph_text(paragraph: Node) -> String {
+    let mut text = String::new();
+    for node in paragraph.descendants() {
+        // Run content only; `w:tab` also appears as a tab stop definition in w:pPr
+        let in_run = node.parent().is_some_and(|parent| is_w(&parent, "r"));
+        if !node.is_element() || node.tag_name().namespace() != Some(W_NS) || !in_run {
+            continue;
+        }
+        match node.tag_name().name() {
+            "t" => text.push_str(node.text().unwrap_or("")),
+            "tab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            "noBreakHyphen" => text.push('-'),
+            "softHyphen" => text.push('\u{00AD}'),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Text of a table cell as a single pipe-table-safe line
+fn cell_text(cell: Node) -> String {
+    let paragraphs: Vec<String> = cell
+        .descendants()
+        .filter(|n| is_w(n, "p") && !n.ancestors().skip(1).any(|a| is_w(&a, "p")))
+        .map(|p| single_line(paragraph_text(p).trim()))
+        .filter(|text| !text.is_empty())
+        .collect();
+    paragraphs.join(" ").replace('|', "\\|")
+}
+
+/// Collapse line breaks and runs of whitespace into single spaces
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown_segmenter::BlockKind;
+    
+    #[test]
+    fn test_docx_to_markdown() {
+        let w = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main""#;
+        let styles = format!(
+            r#"<w:styles {w}>
+                <w:style w:type="paragraph" w:styleId="berschrift1"><w:name w:val="heading 1"/></w:style>
+                <w:style w:type="paragraph" w:styleId="Custom"><w:basedOn w:val="berschrift1"/></w:style>
+                <w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/></w:style>
+            </w:styles>"#
+        );
+        let numbering = format!(
+            r#"<w:numbering {w}>
+                <w:abstractNum w:abstractNumId="0">
+                    <w:lvl w:ilvl="0"><w:numFmt w:val="decimal"/></w:lvl>
+                    <w:lvl w:ilvl="1"><w:numFmt w:val="bullet"/></w:lvl>
+                </w:abstractNum>
+                <w:num w:numId="5"><w:abstractNumId w:val="0"/></w:num>
+            </w:numbering>"#
+        );
+        let item = |level: usize, text: &str| {
+            format!(
+                r#"<w:p><w:pPr><w:pStyle w:val="ListParagraph"/><w:numPr><w:ilvl w:val="{level}"/><w:numId w:val="5"/></w:numPr></w:pPr><w:r><w:t>{text}</w:t></w:r></w:p>"#
+            )
+        };
+        let document = format!(
+            r#"<w:document {w}><w:body>
+                <w:p><w:pPr><w:pStyle w:val="berschrift1"/></w:pPr><w:r><w:t>Intro</w:t></w:r></w:p>
+                <w:p><w:pPr><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:t xml:space="preserve">Hello </w:t></w:r><w:hyperlink><w:r><w:t>world</w:t></w:r></w:hyperlink></w:p>
+                {}{}{}
+                <w:p/>
+                <w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Data</w:t></w:r></w:p>
+                <w:tbl>
+                    <w:tr><w:tc><w:p><w:r><w:t>Name</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Value</w:t></w:r></w:p></w:tc></w:tr>
+                    <w:tr><w:tc><w:p><w:r><w:t>a|b</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>1</w:t></w:r></w:p></w:tc></w:tr>
+                </w:tbl>
+                <w:p><w:pPr><w:pStyle w:val="Custom"/></w:pPr><w:r><w:t>Inherited</w:t></w:r></w:p>
+            </w:body></w:document>"#,
+            item(0, "First"),
+            item(1, "Detail"),
+            item(0, "Second"),
+        );
+        
+        let markdown = docx_to_markdown(&document, Some(&styles), Some(&numbering)).unwrap();
+        assert_eq!(
+            markdown,
+            "# Intro\n\nHello world\n\n1. First\n  - Detail\n2. Second\n\n## Data\n\n\
+             | Name | Value |\n| --- | --- |\n| a\\|b | 1 |\n\n# Inherited"
+        );
+    }
+    
+    #[test]
+    fn test_plain_paragraph_markup_escaped() {
+        let w = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main""#;
+        let paragraph = |text: &str| format!("<w:p><w:r><w:t>{text}</w:t></w:r></w:p>");
+        let document = format!(
+            r#"<w:document {w}><w:body><w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Real</w:t></w:r></w:p>{}{}{}</w:body></w:document>"#,
+            paragraph("# not a heading"),
+            paragraph("1. not a list"),
+            paragraph("| not a table"),
+        );
+        
+        let markdown = docx_to_markdown(&document, None, None).unwrap();
+        assert_eq!(markdown, "# Real\n\n\\# not a heading\n\n1\\. not a list\n\n\\| not a table");
+        let kinds: Vec<BlockKind> = MarkdownSegmenter::new().blocks(&markdown).iter().map(|b| b.kind).collect();
+        assert_eq!(kinds, vec![BlockKind::Heading, BlockKind::Paragraph, BlockKind::Paragraph, BlockKind::Paragraph]);
+    }
+}
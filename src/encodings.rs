@@ -0,0 +1,300 @@
+//! Registry of the supported tiktoken encodings
+//!
+//! Each encoding is a vocabulary file, a pre-tokenization regex pattern and a set
+//! of special tokens. The vocabularies are embedded at compile time unless the
+//! `embedded-vocab` feature is disabled, and can be replaced at runtime by files in
+//! the `MYRUSTCHUNKER_VOCAB_DIR` directory.
+
+use rustc_hash::FxHashMap as HashMap;
+use crate::tiktoken_core::Rank;
+use crate::error::ProcessingError;
+use crate::o200k_vocab::{load_o200k_base_special_tokens, O200K_BASE_PATTERN};
+use crate::vocab_loader::{index_vocabulary, load_tiktoken_file, VOCAB_DIR_ENV};
+use crate::vocabulary::TokenTable;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use log::debug;
+
+/// cl100k_base pattern (GPT-4, GPT-3.5-turbo, text-embedding-3)
+/// 
+/// Letter runs are not split at case changes, contractions are matched case-insensitively
+/// and numbers are split into groups of at most three digits.
+pub const CL100K_BASE_PATTERN: &str = concat!(
+    r"(?i:'s|'t|'re|'ve|'m|'ll|'d)",
+    r"|[^\r\n\p{L}\p{N}]?\p{L}+",
+    r"|\p{N}{1,3}",
+    r"| ?[^\s\p{L}\p{N}]+[\r\n]*",
+    r"|\s*[\r\n]+",
+    r"|\s+(?!\S)",
+    r"|\s+",
+);
+
+/// GPT-2 pattern shared by r50k_base and p50k_base
+/// 
+/// Contractions are lowercase only and digit runs are not split.
+pub const R50K_BASE_PATTERN: &str = concat!(
+    r"'s|'t|'re|'ve|'m|'ll|'d",
+    r"| ?\p{L}+",
+    r"| ?\p{N}+",
+    r"| ?[^\s\p{L}\p{N}]+",
+    r"|\s+(?!\S)",
+    r"|\s+",
+);
+
+/// Supported tokenizer encodings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(clippy::enum_variant_names)] // Named after tiktoken's encodings
+pub enum EncodingKind {
+    /// GPT-4o, GPT-4.1, o1/o3/o4 and GPT-5 models
+    #[default]
+    O200kBase,
+    /// GPT-4, GPT-3.5-turbo and the text-embedding models
+    Cl100kBase,
+    /// Codex and text-davinci-002/003
+    P50kBase,
+    /// GPT-3 (davinci, curie, babbage, ada) and GPT-2
+    R50kBase,
+}
+
+impl EncodingKind {
+    /// Every registered encoding
+    pub const ALL: [EncodingKind; 4] = [
+        EncodingKind::O200kBase,
+        EncodingKind::Cl100kBase,
+        EncodingKind::P50kBase,
+        EncodingKind::R50kBase,
+    ];
+    
+    /// Name of the encoding as used by tiktoken ("cl100k_base")
+    pub fn name(self) -> &'static str {
+        match self {
+            EncodingKind::O200kBase => "o200k_base",
+            EncodingKind::Cl100kBase => "cl100k_base",
+            EncodingKind::P50kBase => "p50k_base",
+            EncodingKind::R50kBase => "r50k_base",
+        }
+    }
+    
+    /// Regex splitting text into pieces before byte pair encoding
+    pub fn pattern(self) -> &'static str {
+        match self {
+            EncodingKind::O200kBase => O200K_BASE_PATTERN,
+            EncodingKind::Cl100kBase => CL100K_BASE_PATTERN,
+            EncodingKind::P50kBase | EncodingKind::R50kBase => R50K_BASE_PATTERN,
+        }
+    }
+    
+    /// Number of ordinary tokens in the published vocabulary
+    pub fn vocab_size(self) -> usize {
+        match self {
+            EncodingKind::O200kBase => 199998,
+            EncodingKind::Cl100kBase => 100256,
+            EncodingKind::P50kBase => 50280,
+            EncodingKind::R50kBase => 50256,
+        }
+    }
+    
+    /// Load the ordinary token vocabulary with its token index
+    /// 
+    /// Process:
+    /// 1. `<name>.tiktoken` in the `MYRUSTCHUNKER_VOCAB_DIR` directory, when it exists
+    ///    (custom and fine-tuned vocabularies may differ in size from the published one)
+    /// 2. The vocabulary embedded at compile time (`embedded-vocab` feature, on by default),
+    ///    which build.rs precompiled to the binary form of [`TokenTable`]; it is used in
+    ///    place, without copying or re-indexing
+    pub fn load_token_table(self) -> Result<TokenTable, ProcessingError> {
+        if let Some(dir) = std::env::var_os(VOCAB_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            let path = PathBuf::from(dir).join(format!("{}.tiktoken", self.name()));
+            if path.is_file() {
+                return index_vocabulary(load_tiktoken_file(&path)?);
+            }
+            debug!("{} not found, using the embedded {} vocabulary", path.display(), self);
+        }
+        
+        let Some(data) = self.embedded_vocab() else {
+            return Err(ProcessingError::InvalidArgument(format!(
+                "No {} vocabulary: this build does not embed vocabularies; set {} to a directory containing {}.tiktoken",
+                self, VOCAB_DIR_ENV, self
+            )));
+        };
+        let table = TokenTable::from_bytes(data)
+            .map_err(|e| ProcessingError::SystemError(format!("Embedded {} vocabulary is corrupt: {}", self, e)))?;
+        if table.len() != self.vocab_size() {
+            return Err(ProcessingError::SystemError(format!(
+                "Embedded {} vocabulary has {} tokens, expected {}", self, table.len(), self.vocab_size()
+            )));
+        }
+        Ok(table)
+    }
+    
+    /// Precompiled vocabulary included in the library
+    #[cfg(feature = "embedded-vocab")]
+    fn embedded_vocab(self) -> Option<&'static [u8]> {
+        Some(match self {
+            EncodingKind::O200kBase => include_bytes!(concat!(env!("OUT_DIR"), "/o200k_base.bpe")),
+            EncodingKind::Cl100kBase => include_bytes!(concat!(env!("OUT_DIR"), "/cl100k_base.bpe")),
+            EncodingKind::P50kBase => include_bytes!(concat!(env!("OUT_DIR"), "/p50k_base.bpe")),
+            EncodingKind::R50kBase => include_bytes!(concat!(env!("OUT_DIR"), "/r50k_base.bpe")),
+        })
+    }
+    
+    #[cfg(not(feature = "embedded-vocab"))]
+    fn embedded_vocab(self) -> Option<&'static [u8]> {
+        None
+    }
+    
+    /// Special tokens and their ranks, which lie outside the ordinary vocabulary
+    pub fn special_tokens(self) -> HashMap<String, Rank> {
+        let tokens: &[(&str, Rank)] = match self {
+            EncodingKind::O200kBase => return load_o200k_base_special_tokens(),
+            EncodingKind::Cl100kBase => &[
+                ("<|endoftext|>", 100257),
+                ("<|fim_prefix|>", 100258),
+                ("<|fim_middle|>", 100259),
+                ("<|fim_suffix|>", 100260),
+                ("<|endofprompt|>", 100276),
+            ],
+            EncodingKind::P50kBase | EncodingKind::R50kBase => &[("<|endoftext|>", 50256)],
+        };
+        tokens.iter().map(|(token, rank)| (token.to_string(), *rank)).collect()
+    }
+}
+
+impl fmt::Display for EncodingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for EncodingKind {
+    type Err = ProcessingError;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let lowercase = name.to_ascii_lowercase();
+        EncodingKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == lowercase)
+            .ok_or_else(|| ProcessingError::InvalidArgument(format!(
+                "Unknown encoding '{}' (expected 'o200k_base', 'cl100k_base', 'p50k_base' or 'r50k_base')",
+                name
+            )))
+    }
+}
+
+/// Model names with their encoding, as in tiktoken's `encoding_for_model`
+const MODEL_TO_ENCODING: [(&str, EncodingKind); 43] = [
+    // Chat
+    ("gpt-5", EncodingKind::O200kBase),
+    ("gpt-4.1", EncodingKind::O200kBase),
+    ("gpt-4o", EncodingKind::O200kBase),
+    ("chatgpt-4o-latest", EncodingKind::O200kBase),
+    ("o1", EncodingKind::O200kBase),
+    ("o3", EncodingKind::O200kBase),
+    ("o4-mini", EncodingKind::O200kBase),
+    ("gpt-4", EncodingKind::Cl100kBase),
+    ("gpt-3.5-turbo", EncodingKind::Cl100kBase),
+    ("gpt-3.5", EncodingKind::Cl100kBase),
+    ("gpt-35-turbo", EncodingKind::Cl100kBase), // Azure deployment name
+    // Base
+    ("davinci-002", EncodingKind::Cl100kBase),
+    ("babbage-002", EncodingKind::Cl100kBase),
+    // Embeddings
+    ("text-embedding-ada-002", EncodingKind::Cl100kBase),
+    ("text-embedding-3-small", EncodingKind::Cl100kBase),
+    ("text-embedding-3-large", EncodingKind::Cl100kBase),
+    // Legacy completions
+    ("text-davinci-003", EncodingKind::P50kBase),
+    ("text-davinci-002", EncodingKind::P50kBase),
+    ("text-davinci-001", EncodingKind::R50kBase),
+    ("text-curie-001", EncodingKind::R50kBase),
+    ("text-babbage-001", EncodingKind::R50kBase),
+    ("text-ada-001", EncodingKind::R50kBase),
+    ("davinci", EncodingKind::R50kBase),
+    ("curie", EncodingKind::R50kBase),
+    ("babbage", EncodingKind::R50kBase),
+    ("ada", EncodingKind::R50kBase),
+    // Legacy code
+    ("code-davinci-002", EncodingKind::P50kBase),
+    ("code-davinci-001", EncodingKind::P50kBase),
+    ("code-cushman-002", EncodingKind::P50kBase),
+    ("code-cushman-001", EncodingKind::P50kBase),
+    ("davinci-codex", EncodingKind::P50kBase),
+    ("cushman-codex", EncodingKind::P50kBase),
+    // Legacy embeddings and search
+    ("text-similarity-davinci-001", EncodingKind::R50kBase),
+    ("text-similarity-curie-001", EncodingKind::R50kBase),
+    ("text-similarity-babbage-001", EncodingKind::R50kBase),
+    ("text-similarity-ada-001", EncodingKind::R50kBase),
+    ("text-search-davinci-doc-001", EncodingKind::R50kBase),
+    ("text-search-curie-doc-001", EncodingKind::R50kBase),
+    ("text-search-babbage-doc-001", EncodingKind::R50kBase),
+    ("text-search-ada-doc-001", EncodingKind::R50kBase),
+    ("code-search-babbage-code-001", EncodingKind::R50kBase),
+    ("code-search-ada-code-001", EncodingKind::R50kBase),
+    // GPT-2 has the same ranks as r50k_base
+    ("gpt2", EncodingKind::R50kBase),
+];
+
+/// Model name prefixes (dated snapshots, fine-tunes) with their encoding
+const MODEL_PREFIX_TO_ENCODING: [(&str, EncodingKind); 16] = [
+    ("gpt-5-", EncodingKind::O200kBase),
+    ("gpt-4.5-", EncodingKind::O200kBase),
+    ("gpt-4.1-", EncodingKind::O200kBase),
+    ("gpt-4o-", EncodingKind::O200kBase),
+    ("chatgpt-4o-", EncodingKind::O200kBase),
+    ("o1-", EncodingKind::O200kBase),
+    ("o3-", EncodingKind::O200kBase),
+    ("o4-mini-", EncodingKind::O200kBase),
+    ("gpt-4-", EncodingKind::Cl100kBase),
+    ("gpt-3.5-turbo-", EncodingKind::Cl100kBase),
+    ("gpt-35-turbo-", EncodingKind::Cl100kBase),
+    ("ft:gpt-4o", EncodingKind::O200kBase),
+    ("ft:gpt-4", EncodingKind::Cl100kBase),
+    ("ft:gpt-3.5-turbo", EncodingKind::Cl100kBase),
+    ("ft:davinci-002", EncodingKind::Cl100kBase),
+    ("ft:babbage-002", EncodingKind::Cl100kBase),
+];
+
+/// Encoding used by an OpenAI model ("gpt-4o" -> o200k_base, "gpt-4" -> cl100k_base)
+/// 
+/// Exact model names are looked up first, then the longest matching prefix, so
+/// dated snapshots ("gpt-4o-2024-08-06") and fine-tunes ("ft:gpt-4o-mini:org::id")
+/// resolve like their base model.
+pub fn encoding_for_model(model: &str) -> Result<EncodingKind, ProcessingError> {
+    if let Some((_, kind)) = MODEL_TO_ENCODING.iter().find(|(name, _)| *name == model) {
+        return Ok(*kind);
+    }
+    MODEL_PREFIX_TO_ENCODING
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, kind)| *kind)
+        .ok_or_else(|| ProcessingError::InvalidArgument(format!(
+            "Could not map model '{}' to an encoding; pass an encoding name such as 'o200k_base' instead",
+            model
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_encoding_registry() {
+        for kind in EncodingKind::ALL {
+            assert_eq!(kind.name().parse::<EncodingKind>().unwrap(), kind);
+        }
+        assert_eq!("CL100K_BASE".parse::<EncodingKind>().unwrap(), EncodingKind::Cl100kBase);
+        assert!("gpt2_base".parse::<EncodingKind>().is_err());
+        
+        assert_eq!(encoding_for_model("gpt-4o").unwrap(), EncodingKind::O200kBase);
+        assert_eq!(encoding_for_model("gpt-4o-2024-08-06").unwrap(), EncodingKind::O200kBase);
+        assert_eq!(encoding_for_model("ft:gpt-4o-mini:org::abc").unwrap(), EncodingKind::O200kBase);
+        assert_eq!(encoding_for_model("gpt-4-0613").unwrap(), EncodingKind::Cl100kBase);
+        assert_eq!(encoding_for_model("text-embedding-3-small").unwrap(), EncodingKind::Cl100kBase);
+        assert_eq!(encoding_for_model("text-davinci-003").unwrap(), EncodingKind::P50kBase);
+        assert_eq!(encoding_for_model("davinci").unwrap(), EncodingKind::R50kBase);
+        assert!(encoding_for_model("llama-3").is_err());
+    }
+}
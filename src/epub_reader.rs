@@ -0,0 +1,388 @@
+use crate::docx_reader::read_part;
+use crate::error::ProcessingError;
+use crate::html_reader::{HtmlDocument, HtmlReader};
+use roxmltree::{Document, Node};
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+use log::{debug, info, warn};
+
+/// One content document of the spine, converted to markdown
+#[derive(Debug, Clone)]
+pub struct EpubChapter {
+    pub spine_index: usize, // 0-based position in the OPF spine
+    pub href: String,       // Archive path of the XHTML content document
+    pub document: HtmlDocument,
+    /// `(byte offset in markdown, title)` of the navigation entries pointing into
+    /// this document, sorted by offset
+    pub titles: Vec<(usize, String)>,
+}
+
+impl EpubChapter {
+    /// Title of the closest navigation entry at or before a markdown byte offset
+    pub fn title_at(&self, offset: usize) -> Option<&str> {
+        let index = self.titles.partition_point(|(start, _)| *start <= offset);
+        index.checked_sub(1).map(|index| self.titles[index].1.as_str())
+    }
+}
+
+/// Navigation entry: target document, optional fragment id and title
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NavEntry {
+    path: String,
+    fragment: Option<String>,
+    title: String,
+}
+
+/// Where the table of contents lives
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NavSource {
+    /// EPUB 3 navigation document (XHTML with `<nav epub:type="toc">`)
+    Xhtml(String),
+    /// EPUB 2 NCX file
+    Ncx(String),
+}
+
+/// Reading order and table of contents from the OPF package document
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Package {
+    spine: Vec<(usize, String)>, // (spine position, archive path) of the XHTML content documents, in reading order
+    nav: Option<NavSource>,
+}
+
+/// Reads EPUB (2 and 3) books as markdown-structured chapters
+/// 
+/// Process:
+/// 1. Locate the OPF package document through `META-INF/container.xml`
+/// 2. Resolve the spine `itemref`s against the manifest to get the XHTML content
+///    documents in reading order (non-XHTML spine items such as SVG pages are skipped)
+/// 3. Read chapter titles from the EPUB 3 navigation document, or from the
+///    EPUB 2 NCX when there is none; entries pointing at a fragment (`ch1.xhtml#s2`)
+///    title the text from that element on
+/// 4. Convert each content document with [`HtmlReader`], keeping `<header>`/`<footer>`
+///    (chapters are not web pages and often wrap their title in a header); a document
+///    no navigation entry points into continues the title of the document before it
+pub struct EpubReader {
+    html_reader: HtmlReader,
+    nav_selector: Selector,
+    link_selector: Selector,
+}
+
+impl EpubReader {
+    pub fn new() -> Self {
+        Self {
+            html_reader: HtmlReader::new().with_site_chrome(false),
+            nav_selector: Selector::parse("nav").expect("Invalid nav selector"),
+            link_selector: Selector::parse("a[href]").expect("Invalid link selector"),
+        }
+    }
+    
+    /// Open an .epub file and convert its spine documents to markdown
+    pub fn read_chapters(&self, path: &Path) -> Result<Vec<EpubChapter>, ProcessingError> {
+        info!("Reading EPUB: {}", path.display());
+        
+        let file = std::fs::File::open(path)?;
+        let mut archive = ZipArchive::new(file)
+            .map_err(|e| ProcessingError::DocumentLoadError(format!("{} is not a valid .epub archive: {}", path.display(), e)))?;
+        self.read_archive(&mut archive)
+            .map_err(|e| match e {
+                ProcessingError::DocumentLoadError(message) => {
+                    ProcessingError::DocumentLoadError(format!("{}: {}", path.display(), message))
+                }
+                e => e,
+            })
+    }
+    
+    /// Convert the spine documents of an opened EPUB archive
+    pub fn read_archive<R: Read + Seek>(&self, archive: &mut ZipArchive<R>) -> Result<Vec<EpubChapter>, ProcessingError> {
+        let container = read_part(archive, "META-INF/container.xml")?
+            .ok_or_else(|| ProcessingError::DocumentLoadError("no META-INF/container.xml".to_string()))?;
+        let package_path = rootfile_path(&container).map_err(ProcessingError::DocumentLoadError)?;
+        let package_xml = read_part(archive, &package_path)?
+            .ok_or_else(|| ProcessingError::DocumentLoadError(format!("package document {} is missing", package_path)))?;
+        let package = parse_package(&package_xml, &package_path).map_err(ProcessingError::DocumentLoadError)?;
+        
+        let nav_entries = match &package.nav {
+            Some(NavSource::Xhtml(nav_path)) => match read_part(archive, nav_path)? {
+                Some(xhtml) => self.nav_entries(&xhtml, nav_path),
+                None => Vec::new(),
+            },
+            Some(NavSource::Ncx(ncx_path)) => match read_part(archive, ncx_path)? {
+                Some(ncx) => ncx_entries(&ncx, ncx_path).unwrap_or_else(|e| {
+                    warn!("Ignoring unreadable NCX {}: {}", ncx_path, e);
+                    Vec::new()
+                }),
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        if nav_entries.is_empty() {
+            warn!("EPUB has no table of contents; chunks will carry no chapter titles");
+        }
+        
+        let mut chapters = Vec::with_capacity(package.spine.len());
+        let mut current_title: Option<String> = None;
+        for (spine_index, href) in &package.spine {
+            let spine_index = *spine_index;
+            let Some(xhtml) = read_part(archive, href)? else {
+                warn!("Spine item {} is missing from the archive; skipping", href);
+                continue;
+            };
+            let document = self.html_reader.convert(&xhtml);
+            
+            // Place each navigation entry at the start of the element it targets
+            let mut titles: Vec<(usize, String)> = nav_entries
+                .iter()
+                .filter(|entry| entry.path == *href)
+                .map(|entry| {
+                    let offset = entry.fragment.as_ref()
+                        .and_then(|id| document.anchors.iter().find(|(_, anchor)| anchor == id))
+                        .map_or(0, |(offset, _)| *offset);
+                    (offset, entry.title.clone())
+                })
+                .collect();
+            titles.sort_by_key(|(offset, _)| *offset);
+            
+            // Text before the first entry continues the previous chapter
+            if titles.first().is_none_or(|(offset, _)| *offset > 0)
+                && let Some(title) = &current_title
+            {
+                titles.insert(0, (0, title.clone()));
+            }
+            if let Some((_, title)) = titles.last() {
+                current_title = Some(title.clone());
+            }
+            
+            debug!("Spine item {} ({}): {} bytes of markdown, {} titles", spine_index, href, document.markdown.len(), titles.len());
+            chapters.push(EpubChapter { spine_index, href: href.clone(), document, titles });
+        }
+        
+        info!("Read {} spine documents and {} navigation entries", chapters.len(), nav_entries.len());
+        Ok(chapters)
+    }
+    
+    /// Table of contents entries of an EPUB 3 navigation document
+    /// 
+    /// Uses the `<nav epub:type="toc">` element, or the first `<nav>` when none is
+    /// marked. Parsed as HTML, since navigation documents often use HTML entities.
+    fn nav_entries(&self, xhtml: &str, nav_path: &str) -> Vec<NavEntry> {
+        let document = Html::parse_document(xhtml);
+        let navs: Vec<ElementRef> = document.select(&self.nav_selector).collect();
+        let toc = navs
+            .iter()
+            .find(|nav| nav.value().attr("epub:type").is_some_and(|types| types.split_whitespace().any(|t| t == "toc")))
+            .or(navs.first());
+        
+        let Some(toc) = toc else {
+            return Vec::new();
+        };
+        toc.select(&self.link_selector)
+            .filter_map(|link| {
+                let title = link.text().collect::<Vec<_>>().join(" ");
+                let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+                let (path, fragment) = resolve_href(nav_path, link.value().attr("href")?);
+                (!title.is_empty()).then_some(NavEntry { path, fragment, title })
+            })
+            .collect()
+    }
+}
+
+/// Whether a node is an element with the given local name (namespaces vary between EPUB versions)
+fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// Archive path of the OPF package document named in `META-INF/container.xml`
+fn rootfile_path(container_xml: &str) -> Result<String, String> {
+    let container = Document::parse(container_xml).map_err(|e| format!("invalid container.xml: {}", e))?;
+    let rootfiles: Vec<Node> = container.descendants().filter(|n| is_element(n, "rootfile")).collect();
+    rootfiles
+        .iter()
+        .find(|n| n.attribute("media-type") == Some("application/oebps-package+xml"))
+        .or(rootfiles.first())
+        .and_then(|n| n.attribute("full-path"))
+        .map(|path| resolve_href("", path).0)
+        .ok_or_else(|| "container.xml names no package document".to_string())
+}
+
+/// Spine documents and navigation source of an OPF package document
+fn parse_package(package_xml: &str, package_path: &str) -> Result<Package, String> {
+    let package = Document::parse(package_xml).map_err(|e| format!("invalid package document: {}", e))?;
+    
+    // Manifest id -> (archive path, media type, properties)
+    let manifest: HashMap<&str, (String, &str, &str)> = package
+        .descendants()
+        .filter(|n| is_element(n, "item"))
+        .filter_map(|item| {
+            let path = resolve_href(package_path, item.attribute("href")?).0;
+            Some((item.attribute("id")?, (path, item.attribute("media-type").unwrap_or(""), item.attribute("properties").unwrap_or(""))))
+        })
+        .collect();
+    
+    let spine_node = package.descendants().find(|n| is_element(n, "spine"))
+        .ok_or_else(|| "package document has no spine".to_string())?;
+    let spine = spine_node
+        .children()
+        .filter(|n| is_element(n, "itemref"))
+        .enumerate()
+        .filter_map(|(index, itemref)| {
+            let idref = itemref.attribute("idref")?;
+            match manifest.get(idref) {
+                Some((path, media_type, _)) if matches!(*media_type, "application/xhtml+xml" | "text/html") => {
+                    Some((index, path.clone()))
+                }
+                Some((path, media_type, _)) => {
+                    debug!("Skipping spine item {} of type {}", path, media_type);
+                    None
+                }
+                None => {
+                    warn!("Spine references unknown manifest item '{}'", idref);
+                    None
+                }
+            }
+        })
+        .collect();
+    
+    // EPUB 3 navigation document first, then the EPUB 2 NCX (spine `toc` attribute or media type)
+    let nav = manifest
+        .values()
+        .find(|(_, _, properties)| properties.split_whitespace().any(|p| p == "nav"))
+        .map(|(path, _, _)| NavSource::Xhtml(path.clone()))
+        .or_else(|| {
+            spine_node.attribute("toc")
+                .and_then(|id| manifest.get(id))
+                .or_else(|| manifest.values().find(|(_, media_type, _)| *media_type == "application/x-dtbncx+xml"))
+                .map(|(path, _, _)| NavSource::Ncx(path.clone()))
+        });
+    
+    Ok(Package { spine, nav })
+}
+
+/// Table of contents entries of an EPUB 2 NCX file, in document order
+fn ncx_entries(ncx_xml: &str, ncx_path: &str) -> Result<Vec<NavEntry>, String> {
+    let ncx = Document::parse(ncx_xml).map_err(|e| format!("invalid NCX: {}", e))?;
+    let entries = ncx
+        .descendants()
+        .filter(|n| is_element(n, "navPoint"))
+        .filter_map(|point| {
+            let label = point.children().find(|n| is_element(n, "navLabel"))?;
+            let title = label.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect::<Vec<_>>().join(" ");
+            let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+            let src = point.children().find(|n| is_element(n, "content"))?.attribute("src")?;
+            let (path, fragment) = resolve_href(ncx_path, src);
+            (!title.is_empty()).then_some(NavEntry { path, fragment, title })
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Resolve an href relative to the archive path of the file containing it
+/// 
+/// Returns the normalized archive path (percent-decoded, `.`/`..` applied) and
+/// the fragment id, if any.
+fn resolve_href(base: &str, href: &str) -> (String, Option<String>) {
+    let (target, fragment) = match href.split_once('#') {
+        Some((target, fragment)) => (target, Some(percent_decode(fragment)).filter(|f| !f.is_empty())),
+        None => (href, None),
+    };
+    let target = percent_decode(target);
+    
+    // An empty target refers to the base document itself
+    if target.is_empty() {
+        return (base.to_string(), fragment);
+    }
+    let directory = if target.starts_with('/') { "" } else { base.rsplit_once('/').map_or("", |(directory, _)| directory) };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in directory.split('/').chain(target.split('/')) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    (segments.join("/"), fragment)
+}
+
+/// Decode `%XX` escapes in an href (invalid escapes are kept as written)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use zip::CompressionMethod;
+    
+    #[test]
+    fn test_read_epub_chapters() {
+        let files = [
+            ("META-INF/container.xml", r#"<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles>
+                <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#),
+            ("OEBPS/content.opf", r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <manifest>
+                    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                    <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="c1b" href="text/chapter1b.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="cover" href="cover.svg" media-type="image/svg+xml"/>
+                </manifest>
+                <spine><itemref idref="cover"/><itemref idref="c1"/><itemref idref="c1b"/></spine>
+            </package>"#),
+            ("OEBPS/nav.xhtml", r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+                <nav epub:type="landmarks"><a href="text/chapter1b.xhtml">Wrong</a></nav>
+                <nav epub:type="toc"><ol>
+                    <li><a href="text/chapter%201.xhtml">Chapter&nbsp;1</a><ol>
+                        <li><a href="text/chapter%201.xhtml#later">Later section</a></li></ol></li>
+                </ol></nav></body></html>"#),
+            ("OEBPS/text/chapter 1.xhtml", r#"<html><body><h1>One</h1><p>Opening.</p><h2 id="later">Later</h2><p>More.</p></body></html>"#),
+            ("OEBPS/text/chapter1b.xhtml", r#"<html><body><header><h1>Interlude</h1></header><p>Continued.</p></body></html>"#),
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, content) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        
+        let chapters = EpubReader::new().read_archive(&mut archive).unwrap();
+        
+        // The SVG cover is skipped; spine indices still count it
+        let summary: Vec<(usize, &str)> = chapters.iter().map(|c| (c.spine_index, c.href.as_str())).collect();
+        assert_eq!(summary, vec![(1, "OEBPS/text/chapter 1.xhtml"), (2, "OEBPS/text/chapter1b.xhtml")]);
+        
+        let first = &chapters[0];
+        assert_eq!(first.document.markdown, "# One\n\nOpening.\n\n## Later\n\nMore.");
+        let later = first.document.markdown.find("## Later").unwrap();
+        assert_eq!(first.title_at(0), Some("Chapter 1"));
+        assert_eq!(first.title_at(later), Some("Later section"));
+        assert_eq!(chapters[1].title_at(0), Some("Later section"));
+        assert_eq!(chapters[1].document.markdown, "# Interlude\n\nContinued."); // Header-wrapped title kept
+        
+        assert_eq!(resolve_href("OEBPS/text/a.xhtml", "../img/b%20c.png#x"), ("OEBPS/img/b c.png".to_string(), Some("x".to_string())));
+    }
+}
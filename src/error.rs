@@ -9,9 +9,27 @@ pub enum ProcessingError {
     #[error("PDF loading failed: {0}")]
     PdfLoadError(String),
     
+    #[error("Document loading failed: {0}")]
+    DocumentLoadError(String),
+    
+    #[error("PDF is password protected and no password was supplied: {0}")]
+    PasswordRequired(String),
+    
+    #[error("Incorrect password for PDF: {0}")]
+    IncorrectPassword(String),
+    
+    #[error("PDF permissions do not allow text extraction: {0}")]
+    ExtractionNotPermitted(String),
+    
     #[error("Text extraction failed on page {page}: {error}")]
     TextExtractionError { page: usize, error: String },
     
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    
+    #[error("Invalid vocabulary {file}, line {line}: {message}")]
+    InvalidVocabulary { file: String, line: usize, message: String },
+    
     #[error("Chunking failed: {0}")]
     ChunkingError(String),
     
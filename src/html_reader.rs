@@ -0,0 +1,362 @@
+use crate::markdown_segmenter::MarkdownSegmenter;
+use scraper::{ElementRef, Html, Selector};
+use log::debug;
+
+/// HTML converted to markdown, with the anchors found along the way
+#[derive(Debug, Clone, Default)]
+pub struct HtmlDocument {
+    pub markdown: String,
+    /// `(byte offset in markdown, id)` for every element with an `id` (or `<a name>`),
+    /// in document order; the offset is where that element's content starts
+    pub anchors: Vec<(usize, String)>,
+}
+
+impl HtmlDocument {
+    /// Nearest anchor at or before a markdown byte offset
+    pub fn anchor_at(&self, offset: usize) -> Option<&str> {
+        let index = self.anchors.partition_point(|(start, _)| *start <= offset);
+        index.checked_sub(1).map(|index| self.anchors[index].1.as_str())
+    }
+}
+
+/// Elements whose content is never text of the page
+const SKIPPED_TAGS: [&str; 13] = [
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe",
+    "nav", "aside", "form", "button", "select",
+];
+
+/// ARIA landmark roles of site chrome rather than content
+const BOILERPLATE_ROLES: [&str; 5] = ["navigation", "banner", "contentinfo", "complementary", "search"];
+
+/// Containers that start and end a block of text
+const BLOCK_TAGS: [&str; 22] = [
+    "html", "body", "main", "article", "section", "div", "header", "footer", "p", "blockquote",
+    "figure", "figcaption", "address", "details", "summary", "dl", "dt", "dd", "fieldset", "center",
+    "caption", "hr",
+];
+
+/// Converts HTML pages into markdown-structured text for the markdown-aware chunker
+/// 
+/// Process:
+/// 1. Pick the main content: `<main>` (or `role="main"`), else a lone `<article>`, else `<body>`
+/// 2. Drop boilerplate: scripts, styles, navigation, asides, forms, site headers/footers
+///    (unless disabled with [`with_site_chrome`](Self::with_site_chrome)) and hidden elements
+/// 3. Map `h1`-`h6` to headings, `p` to paragraphs, `ul`/`ol`/`li` to (nested) lists,
+///    `pre` to fenced code blocks and `table` to pipe tables; inline markup such as
+///    links keeps only its text, and markdown at the start of a paragraph is escaped
+/// 4. Record where every element with an `id` starts, so chunks can link back to
+///    the closest anchor
+pub struct HtmlReader {
+    main_selector: Selector,
+    article_selector: Selector,
+    markdown: MarkdownSegmenter,
+    strip_site_chrome: bool, // Drop headers/footers outside article/section/main
+}
+
+impl HtmlReader {
+    pub fn new() -> Self {
+        Self {
+            main_selector: Selector::parse("main, [role=main]").expect("Invalid main selector"),
+            article_selector: Selector::parse("article").expect("Invalid article selector"),
+            markdown: MarkdownSegmenter::new(),
+            strip_site_chrome: true,
+        }
+    }
+    
+    /// Whether `<header>`/`<footer>` outside `article`/`section`/`main` are dropped
+    /// as site chrome (default true)
+    /// 
+    /// Documents without site chrome, such as EPUB chapters that wrap their title
+    /// in `<body><header>`, should keep them.
+    pub fn with_site_chrome(mut self, strip: bool) -> Self {
+        self.strip_site_chrome = strip;
+        self
+    }
+    
+    /// Convert an HTML page to markdown
+    pub fn convert(&self, html: &str) -> HtmlDocument {
+        let document = Html::parse_document(html);
+        
+        let articles: Vec<ElementRef> = document.select(&self.article_selector).collect();
+        let content = document
+            .select(&self.main_selector)
+            .next()
+            .or_else(|| (articles.len() == 1).then(|| articles[0]))
+            .unwrap_or_else(|| document.root_element());
+        
+        let mut writer = HtmlWriter {
+            markdown: &self.markdown,
+            strip_site_chrome: self.strip_site_chrome,
+            output: HtmlDocument::default(),
+            paragraph: String::new(),
+        };
+        writer.element(content);
+        writer.flush_paragraph();
+        
+        debug!("Converted HTML into {} bytes of markdown with {} anchors", writer.output.markdown.len(), writer.output.anchors.len());
+        writer.output
+    }
+}
+
+/// Whether an element is boilerplate or hidden and should be dropped with its subtree
+fn is_skipped(element: ElementRef) -> bool {
+    let value = element.value();
+    let name = value.name();
+    if SKIPPED_TAGS.contains(&name) || value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    value.attr("role").is_some_and(|role| BOILERPLATE_ROLES.contains(&role))
+}
+
+/// Whether an element is a site-wide header or footer; those inside an article
+/// or section belong to the content
+fn is_site_chrome(element: ElementRef) -> bool {
+    matches!(element.value().name(), "header" | "footer")
+        && !element.ancestors().filter_map(ElementRef::wrap).any(|a| matches!(a.value().name(), "article" | "section" | "main"))
+}
+
+/// Whether an element starts and ends a block of text
+fn is_block(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "pre" | "ul" | "ol" | "li" | "table" | "tr" | "td" | "th")
+        || BLOCK_TAGS.contains(&name)
+}
+
+/// Collapse whitespace runs into single spaces
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Markdown output being built while walking the element tree
+struct HtmlWriter<'a> {
+    markdown: &'a MarkdownSegmenter, // Escapes markup in paragraph text
+    strip_site_chrome: bool,
+    output: HtmlDocument,
+    paragraph: String, // Inline text of the block being read
+}
+
+impl HtmlWriter<'_> {
+    fn element(&mut self, element: ElementRef) {
+        if is_skipped(element) || (self.strip_site_chrome && is_site_chrome(element)) {
+            return;
+        }
+        let value = element.value();
+        let name = value.name();
+        
+        // Blocks end the pending paragraph first, so their anchor lands after it
+        if is_block(name) {
+            self.flush_paragraph();
+        }
+        if let Some(id) = value.attr("id").or_else(|| if name == "a" { value.attr("name") } else { None }) {
+            let offset = self.output.markdown.len();
+            self.output.anchors.push((offset, id.to_string()));
+        }
+        
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush_paragraph();
+                let level = (name.as_bytes()[1] - b'0') as usize;
+                let text = collapse(&inline_text(element));
+                if !text.is_empty() {
+                    self.push_block(format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "pre" => {
+                self.flush_paragraph();
+                let code: String = element.text().collect();
+                let code = code.trim_matches('\n');
+                if !code.trim().is_empty() {
+                    self.push_block(format!("```\n{}\n```", code));
+                }
+            }
+            "ul" | "ol" => {
+                self.flush_paragraph();
+                let lines = list_lines(element, 0);
+                if !lines.is_empty() {
+                    self.push_block(lines.join("\n"));
+                }
+            }
+            "table" => {
+                self.flush_paragraph();
+                if let Some(table) = table_markdown(element) {
+                    self.push_block(table);
+                }
+            }
+            "br" => self.paragraph.push('\n'),
+            _ if BLOCK_TAGS.contains(&name) => {
+                self.flush_paragraph();
+                self.children(element);
+                self.flush_paragraph();
+            }
+            _ => self.children(element),
+        }
+    }
+    
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                self.paragraph.push_str(text);
+            } else if let Some(child) = ElementRef::wrap(child) {
+                self.element(child);
+            }
+        }
+    }
+    
+    /// Emit the inline text read so far as a paragraph
+    fn flush_paragraph(&mut self) {
+        let text = collapse(&self.paragraph);
+        self.paragraph.clear();
+        if !text.is_empty() {
+            let text = self.markdown.escape_paragraph(&text);
+            self.push_block(text);
+        }
+    }
+    
+    fn push_block(&mut self, block: String) {
+        let markdown = &mut self.output.markdown;
+        if !markdown.is_empty() {
+            markdown.push_str("\n\n");
+        }
+        markdown.push_str(&block);
+    }
+}
+
+/// Text of an element's subtree, minus boilerplate
+fn inline_text(element: ElementRef) -> String {
+    let mut text = String::new();
+    for child in element.children() {
+        if let Some(child_text) = child.value().as_text() {
+            text.push_str(child_text);
+        } else if let Some(child) = ElementRef::wrap(child)
+            && !is_skipped(child)
+        {
+            push_child_text(&mut text, child);
+        }
+    }
+    text
+}
+
+/// Append the text of a child element: block-level children and `<br>` are set
+/// apart by spaces, inline markup joins as written (`H<sub>2</sub>O` -> `H2O`)
+fn push_child_text(text: &mut String, child: ElementRef) {
+    let name = child.value().name();
+    if is_block(name) || name == "br" {
+        text.push(' ');
+        text.push_str(&inline_text(child));
+        text.push(' ');
+    } else {
+        text.push_str(&inline_text(child));
+    }
+}
+
+/// Markdown lines of a list, nested lists indented below their item
+fn list_lines(list: ElementRef, depth: usize) -> Vec<String> {
+    let ordered = list.value().name() == "ol";
+    let start = list.value().attr("start").and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
+    let items = list.children().filter_map(ElementRef::wrap).filter(|e| e.value().name() == "li" && !is_skipped(*e));
+    let mut lines = Vec::new();
+    
+    for (number, item) in (start..).zip(items) {
+        let mut text = String::new();
+        let mut nested = Vec::new();
+        for child in item.children() {
+            if let Some(child_text) = child.value().as_text() {
+                text.push_str(child_text);
+            } else if let Some(child) = ElementRef::wrap(child) {
+                match child.value().name() {
+                    "ul" | "ol" => nested.extend(list_lines(child, depth + 1)),
+                    _ if is_skipped(child) => {}
+                    _ => push_child_text(&mut text, child),
+                }
+            }
+        }
+        
+        let marker = if ordered { format!("{}.", number) } else { "-".to_string() };
+        let text = collapse(&text);
+        if !text.is_empty() {
+            lines.push(format!("{}{} {}", "  ".repeat(depth), marker, text));
+        }
+        lines.extend(nested);
+    }
+    lines
+}
+
+/// Pipe table for an HTML table (first row as header); nested tables are flattened to text
+fn table_markdown(table: ElementRef) -> Option<String> {
+    let rows: Vec<Vec<String>> = table
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| e.value().name() == "tr")
+        // Rows of this table only, not of tables nested in its cells
+        .filter(|row| row.ancestors().filter_map(ElementRef::wrap).find(|a| a.value().name() == "table") == Some(table))
+        .map(|row| {
+            let mut cells = Vec::new();
+            for cell in row.children().filter_map(ElementRef::wrap).filter(|c| matches!(c.value().name(), "td" | "th")) {
+                cells.push(collapse(&inline_text(cell)).replace('|', "\\|"));
+                let span = cell.value().attr("colspan").and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
+                cells.extend(std::iter::repeat_n(String::new(), span.clamp(1, 100) - 1));
+            }
+            cells
+        })
+        .filter(|cells| !cells.is_empty())
+        .collect();
+    
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if columns == 0 || rows.iter().flatten().all(|cell| cell.is_empty()) {
+        return None;
+    }
+    
+    let render = |cells: &[String]| {
+        let padded = (0..columns).map(|i| cells.get(i).map(String::as_str).unwrap_or(""));
+        format!("| {} |", padded.collect::<Vec<_>>().join(" | "))
+    };
+    let mut lines = vec![render(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|row| render(row)));
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"<html><head><title>T</title><style>p { color: red }</style></head><body>
+            <header><nav><a href="/">Home</a></nav></header>
+            <main>
+                <h1 id="guide">User <em>guide</em></h1>
+                <p>Read the <a href="/docs">full   docs</a> first.<script>track()</script></p>
+                <h2 id="install">Install</h2>
+                <ol><li>Download<ul><li>Linux</li><li>Mac</li></ul></li><li>Run</li></ol>
+                <pre>fn main() {
+    run();
+}</pre>
+                <table><tr><th>Key</th><th>Value</th></tr><tr><td>a|b</td><td>1</td></tr></table>
+            </main>
+            <footer>Copyright</footer>
+        </body></html>"#;
+        
+        let document = HtmlReader::new().convert(html);
+        assert_eq!(
+            document.markdown,
+            "# User guide\n\nRead the full docs first.\n\n## Install\n\n1. Download\n  - Linux\n  - Mac\n2. Run\n\n\
+             ```\nfn main() {\n    run();\n}\n```\n\n| Key | Value |\n| --- | --- |\n| a\\|b | 1 |"
+        );
+        
+        let install = document.markdown.find("## Install").unwrap();
+        assert_eq!(document.anchor_at(0), Some("guide"));
+        assert_eq!(document.anchor_at(install), Some("install"));
+        assert_eq!(document.anchor_at(document.markdown.len()), Some("install"));
+    }
+    
+    #[test]
+    fn test_inline_markup_joins_words() {
+        let html = "<body><h2>H<sub>2</sub>O</h2><p>un<em>believ</em>able<br>next</p>\
+                    <ul><li>x<sup>2</sup> and <b>y</b><div>block</div></li></ul><p># not a heading</p><p>1. not a list</p></body>";
+        
+        let document = HtmlReader::new().convert(html);
+        assert_eq!(
+            document.markdown,
+            "## H2O\n\nunbelievable next\n\n- x2 and y block\n\n\\# not a heading\n\n1\\. not a list"
+        );
+    }
+}
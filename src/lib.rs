@@ -1,5 +1,12 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyDeprecationWarning, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyBytes, PyDict};
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 mod pdf_processor;
 mod chunking;
@@ -7,14 +14,54 @@ mod parallel_processor;
 mod text_extractor;
 mod error;
 mod tiktoken_core;
+mod bpe_cache;
 mod o200k_vocab;
+mod encodings;
+mod vocab_loader;
+mod vocabulary;
 mod text_preprocessor;
 mod semantic_segmenter;
 mod chunk_merger;
 mod chunk_overlapper;
 mod semantic_chunker;
+mod pdf_backend;
+mod lopdf_backend;
+mod page_selection;
+mod markdown_segmenter;
+mod docx_reader;
+mod html_reader;
+mod epub_reader;
+mod code_segmenter;
+mod directory_walker;
+mod chunk_ids;
 
-use pdf_processor::PdfProcessor;
+use error::ProcessingError;
+use chunking::ChunkMetadata;
+use directory_walker::FileRules;
+use encodings::EncodingKind;
+use pdf_backend::BackendKind;
+use page_selection::{PageFilter, PageSelection};
+use pdf_processor::{PdfProcessor, ProcessOptions};
+use text_preprocessor::NormalizationOptions;
+use tiktoken_core::{CoreBPE, Rank};
+
+// Python exception types for errors callers are expected to handle specifically.
+// All subclass RuntimeError so existing `except RuntimeError` handlers keep working.
+create_exception!(myrustchunker, PasswordRequiredError, PyRuntimeError);
+create_exception!(myrustchunker, IncorrectPasswordError, PyRuntimeError);
+create_exception!(myrustchunker, ExtractionNotPermittedError, PyRuntimeError);
+
+/// Convert a pipeline error into the matching Python exception
+fn to_py_err(context: &str, error: ProcessingError) -> PyErr {
+    let message = format!("{}: {}", context, error);
+    match error {
+        ProcessingError::PasswordRequired(_) => PasswordRequiredError::new_err(message),
+        ProcessingError::IncorrectPassword(_) => IncorrectPasswordError::new_err(message),
+        ProcessingError::ExtractionNotPermitted(_) => ExtractionNotPermittedError::new_err(message),
+        ProcessingError::InvalidArgument(_) | ProcessingError::InvalidVocabulary { .. } => PyValueError::new_err(message),
+        _ => PyRuntimeError::new_err(message),
+    }
+}
 
 /// Python module initialization
 /// This is the entry point that Maturin uses to create the Python extension
@@ -25,6 +72,18 @@ fn myrustchunker(m: &Bound<'_, PyModule>) -> PyResult<()> {
     
     // Register the main processing function
     m.add_function(wrap_pyfunction!(process_pdf, m)?)?;
+    m.add_function(wrap_pyfunction!(process_file, m)?)?;
+    m.add_function(wrap_pyfunction!(process_directory, m)?)?;
+    m.add_function(wrap_pyfunction!(encoding_name_for_model, m)?)?;
+    m.add_function(wrap_pyfunction!(get_encoding_for_model, m)?)?;
+    m.add_function(wrap_pyfunction!(get_encoding, m)?)?;
+    m.add_function(wrap_pyfunction!(encoding_for_model, m)?)?;
+    m.add_class::<PyEncoding>()?;
+    
+    // Register exception types
+    m.add("PasswordRequiredError", m.py().get_type::<PasswordRequiredError>())?;
+    m.add("IncorrectPasswordError", m.py().get_type::<IncorrectPasswordError>())?;
+    m.add("ExtractionNotPermittedError", m.py().get_type::<ExtractionNotPermittedError>())?;
     
     // Add version info
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
@@ -35,49 +94,836 @@ fn myrustchunker(m: &Bound<'_, PyModule>) -> PyResult<()> {
 /// Main Python-exposed function for processing PDFs
 /// 
 /// This function takes a PDF file path and returns chunk metadata as a list of dictionaries
-/// Each dictionary contains: page, chunk_id, text, source, and token_count
+/// Each dictionary contains: page, page_label, chunk_id, content_id, document_id, text,
+/// source, token_count, bboxes, section, anchor, chapter, spine_index, start_line and
+/// end_line (`page` is the 1-based physical index, `page_label` the printed label or None,
+/// `bboxes` a list of `(page, x0, y0, x1, y1)` line rectangles in PDF user-space
+/// coordinates; the last six are only set for other formats, see `process_file`)
+/// 
+/// Chunk identity:
+/// - `chunk_id`: index of the chunk on its page (renumbered by any edit before it)
+/// - `content_id`: 32 hex digits hashed from source, page and whitespace-normalized text;
+///   identical for unchanged chunks across runs, so a vector store can upsert only new or
+///   changed chunks and delete IDs that no longer appear
+/// - `document_id`: SHA-256 of the file, to skip documents that did not change at all
 /// 
 /// Architecture:
-/// 1. Load PDF using pdfium-render
+/// 1. Load PDF using pdfium-render (or the pure-Rust lopdf backend)
 /// 2. Extract text from all pages in parallel batches
 /// 3. Apply semantic-aware chunking with tiktoken tokenization (256 tokens, 16 overlap)
 /// 4. Return structured metadata for Python consumption
 /// 
 /// Features:
-/// - Real tiktoken o200k_base tokenization for GPT-4 compatibility
+/// - Real tiktoken tokenization (o200k_base by default, see `encoding`)
 /// - Semantic awareness: preserves paragraphs, sentences, and headings
 /// - RecursiveTextSplitter-style hierarchical splitting
 /// - Your Python regex cleaning: r'\n\s*\n\s*\n+' -> '\n\n'
 /// - Token-level overlap for context preservation
+/// 
+/// Encrypted PDFs:
+/// - `password` unlocks documents with a user (open) password
+/// - Raises `PasswordRequiredError` / `IncorrectPasswordError` when it is missing or wrong
+/// - Owner-password (print/copy) restrictions are ignored unless `respect_permissions=True`,
+///   in which case `ExtractionNotPermittedError` is raised
+/// 
+/// Extraction backend (`backend`):
+/// - "auto" (default): pdfium when its shared library is found, otherwise lopdf
+/// - "pdfium": native pdfium only; fails if the library cannot be loaded
+/// - "lopdf": pure-Rust extraction without bounding boxes
+/// 
+/// pdfium library search order: `pdfium_path` (library file or directory), the
+/// `PDFIUM_LIBRARY_PATH` environment variable, the current directory, then the system
+/// library path. The error lists every location tried when none of them loads.
+/// 
+/// Page selection and filtering:
+/// - `pages`: 1-based spec such as "1-10,45,100-"; other pages are never extracted
+/// - `min_chars`: skip pages with fewer non-whitespace characters (blank pages)
+/// - `skip_pattern`: skip pages whose text matches this regular expression
+/// - `page_filter`: callable `(page, page_label, text) -> bool`, False skips the page
+/// Content filters run on each page as soon as its text is extracted, so a skipped
+/// page is never dehyphenated, tokenized or chunked; only `pages` also saves its
+/// extraction cost. `page_filter` may be called from several threads and not in
+/// page order. The first exception it raises skips the remaining pages and is
+/// re-raised once the run ends.
+/// 
+/// Text normalization (`normalization`, a dict; every key is optional):
+/// - "unicode_form": "nfc" (default), "nfkc" (also folds full-width forms) or "none"
+/// - "ligatures": expand `ﬁ`, `ﬂ`, ... (default True)
+/// - "quotes": fold curly quotes to `'` and `"` (default True)
+/// - "dashes": fold en/em dashes and minus signs to `-` (default True)
+/// - "invisible": drop zero-width characters and soft hyphens (default True)
+/// - "spaces": turn non-breaking and fixed-width spaces into plain spaces (default True)
+/// - "dehyphenate": rejoin words hyphenated at line ends and page breaks (default True)
+/// 
+/// Tokenizer (`encoding`): chunk sizes and `token_count` are measured in "o200k_base"
+/// (default; GPT-4o and later), "cl100k_base" (GPT-4, GPT-3.5, text-embedding-3),
+/// "p50k_base" or "r50k_base" tokens. `encoding_name_for_model` maps a model name to
+/// its encoding. A `<encoding>.tiktoken` rank file in the directory named by the
+/// `MYRUSTCHUNKER_VOCAB_DIR` environment variable replaces the built-in vocabulary
+/// (malformed files raise ValueError with the offending line). `vocabulary_path`
+/// names such a rank file directly (a custom or fine-tuned vocabulary); the pattern
+/// and special tokens of `encoding` still apply.
 #[pyfunction]
-fn process_pdf(py: Python, pdf_path: String) -> PyResult<Vec<PyObject>> {
-    // Create tokio runtime with correct API
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to create runtime: {}", e)))?;
+#[pyo3(signature = (
+    pdf_path,
+    password=None,
+    respect_permissions=false,
+    backend="auto",
+    pdfium_path=None,
+    pages=None,
+    min_chars=0,
+    skip_pattern=None,
+    page_filter=None,
+    normalization=None,
+    encoding="o200k_base",
+    vocabulary_path=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn process_pdf(
+    py: Python,
+    pdf_path: String,
+    password: Option<String>,
+    respect_permissions: bool,
+    backend: &str,
+    pdfium_path: Option<PathBuf>,
+    pages: Option<&str>,
+    min_chars: usize,
+    skip_pattern: Option<&str>,
+    page_filter: Option<PyObject>,
+    normalization: Option<&Bound<'_, PyDict>>,
+    encoding: &str,
+    vocabulary_path: Option<PathBuf>,
+) -> PyResult<Vec<PyObject>> {
+    let settings = PipelineSettings::from_args(
+        password,
+        respect_permissions,
+        backend,
+        pdfium_path,
+        pages,
+        min_chars,
+        skip_pattern,
+        page_filter,
+        normalization,
+        encoding,
+        vocabulary_path,
+    )?;
+    run_pipeline(py, &pdf_path, settings, false)
+}
+
+/// Process any supported file, dispatching on its extension
+/// 
+/// - `.pdf`: same as `process_pdf` (all keyword arguments apply)
+/// - `.txt`: read as UTF-8 and chunked like extracted PDF text
+/// - `.md` / `.markdown`: chunked along the markdown block structure; headings,
+///   fenced code blocks, lists and tables are kept whole when they fit in a chunk,
+///   and code keeps its indentation
+/// - `.docx`: heading styles, numbered/bulleted paragraphs and tables are converted
+///   to markdown and chunked the same way
+/// - `.html` / `.htm`: scripts, styles, navigation and site headers/footers are dropped;
+///   headings, paragraphs, lists, `pre` and tables become the same markdown units and
+///   link text is kept
+/// - `.epub`: XHTML chapters are read in spine order and converted like HTML
+/// - Source code (`.rs`, `.py`, `.js`/`.ts`, `.go`, `.java`, `.c`/`.cpp`, `.cs`, `.kt`,
+///   `.scala`, `.swift`, `.php`, `.rb`, `.sh`, ...): kept verbatim and split along
+///   top-level functions, classes and blocks (brackets and indentation balanced), then
+///   at blank lines and single lines only when a unit exceeds the chunk size
+/// 
+/// Chunks of structured formats carry their heading path in `section` ("Setup > Install");
+/// HTML and EPUB chunks also carry `anchor`, the id of the closest element at or before
+/// the chunk. EPUB chunks report `chapter`, the title of the closest table of contents
+/// entry, and `spine_index`, the 0-based spine position of the chapter document; their
+/// `page` is that position plus one and `chunk_id` restarts with each chapter.
+/// Source code chunks report the 1-based inclusive line range they cover in
+/// `start_line` / `end_line` (the overlap prepended from the previous chunk excluded).
+/// Other non-PDF files are treated as a single page 1; the PDF-only arguments
+/// (password, pages, page filters) are ignored for them. Unsupported extensions
+/// raise ValueError.
+#[pyfunction]
+#[pyo3(signature = (
+    file_path,
+    password=None,
+    respect_permissions=false,
+    backend="auto",
+    pdfium_path=None,
+    pages=None,
+    min_chars=0,
+    skip_pattern=None,
+    page_filter=None,
+    normalization=None,
+    encoding="o200k_base",
+    vocabulary_path=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn process_file(
+    py: Python,
+    file_path: String,
+    password: Option<String>,
+    respect_permissions: bool,
+    backend: &str,
+    pdfium_path: Option<PathBuf>,
+    pages: Option<&str>,
+    min_chars: usize,
+    skip_pattern: Option<&str>,
+    page_filter: Option<PyObject>,
+    normalization: Option<&Bound<'_, PyDict>>,
+    encoding: &str,
+    vocabulary_path: Option<PathBuf>,
+) -> PyResult<Vec<PyObject>> {
+    let settings = PipelineSettings::from_args(
+        password,
+        respect_permissions,
+        backend,
+        pdfium_path,
+        pages,
+        min_chars,
+        skip_pattern,
+        page_filter,
+        normalization,
+        encoding,
+        vocabulary_path,
+    )?;
+    run_pipeline(py, &file_path, settings, true)
+}
+
+/// Process every supported file in a directory tree
+/// 
+/// `include` / `exclude` are glob patterns matched against paths relative to
+/// `root` (with `/` separators): `*` stays within a directory, `**` crosses them,
+/// so `["**/*.pdf", "**/*.md"]` selects PDFs and markdown files anywhere. Without
+/// `include` every file is a candidate; excluded directories (`"**/node_modules"`
+/// or `"**/node_modules/**"`) are not entered. Each file is read as by `process_file`,
+/// files are processed in parallel, and chunk `source` is the relative path.
+/// 
+/// Returns a dict with:
+/// - "chunks": chunk dicts of all files in file name order (empty when `on_file` is given)
+/// - "files": `{"path", "chunk_count"}` for every file processed
+/// - "skipped": `{"path", "reason"}` for included files that were excluded or are of an
+///   unsupported type
+/// - "failed": `{"path", "error"}` for files (or directories) that could not be processed
+/// 
+/// `on_file`: callable `(path, chunks)` invoked as soon as each file is done (in
+/// completion order) to stream results instead of collecting them; an exception it
+/// raises is re-raised once all files are processed.
+/// 
+/// The remaining keyword arguments apply to every file as in `process_file`
+/// (there is no `password`, since it would differ per document).
+#[pyfunction]
+#[pyo3(signature = (
+    root,
+    include=None,
+    exclude=None,
+    on_file=None,
+    respect_permissions=false,
+    backend="auto",
+    pdfium_path=None,
+    pages=None,
+    min_chars=0,
+    skip_pattern=None,
+    page_filter=None,
+    normalization=None,
+    encoding="o200k_base",
+    vocabulary_path=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn process_directory(
+    py: Python,
+    root: PathBuf,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    on_file: Option<PyObject>,
+    respect_permissions: bool,
+    backend: &str,
+    pdfium_path: Option<PathBuf>,
+    pages: Option<&str>,
+    min_chars: usize,
+    skip_pattern: Option<&str>,
+    page_filter: Option<PyObject>,
+    normalization: Option<&Bound<'_, PyDict>>,
+    encoding: &str,
+    vocabulary_path: Option<PathBuf>,
+) -> PyResult<PyObject> {
+    let settings = PipelineSettings::from_args(
+        None,
+        respect_permissions,
+        backend,
+        pdfium_path,
+        pages,
+        min_chars,
+        skip_pattern,
+        page_filter,
+        normalization,
+        encoding,
+        vocabulary_path,
+    )?;
+    let rules = FileRules::new(&include.unwrap_or_default(), &exclude.unwrap_or_default())
+        .map_err(|e| to_py_err("Invalid file patterns", e))?;
+    
+    let collected: Mutex<HashMap<String, Vec<ChunkMetadata>>> = Mutex::new(HashMap::new());
+    let callback_error: Mutex<Option<PyErr>> = Mutex::new(None);
+    let filter_error = settings.filter_error.clone();
+    
+    // Hand each finished file to the callback (under the GIL) or keep it for the result
+    let on_file_done = |relative: &str, chunks: Vec<ChunkMetadata>| match &on_file {
+        Some(callback) => Python::with_gil(|py| {
+            let result = chunks
+                .into_iter()
+                .map(|chunk| chunk_to_dict(py, chunk))
+                .collect::<PyResult<Vec<_>>>()
+                .and_then(|dicts| callback.call1(py, (relative, dicts)));
+            if let Err(e) = result {
+                callback_error.lock().get_or_insert(e);
+            }
+        }),
+        None => {
+            collected.lock().insert(relative.to_string(), chunks);
+        }
+    };
+    
+    // Release the GIL while working so page filters and callbacks can take it from worker threads
+    let summary = py.allow_threads(|| {
+        let rt = new_runtime()?;
+        rt.block_on(async {
+            let processor = settings.processor().await?;
+            processor.process_directory(&root, &rules, &settings.options, on_file_done).await
+                .map_err(|e| to_py_err("Directory processing failed", e))
+        })
+    })?;
+    if let Some(e) = callback_error.into_inner() {
+        return Err(e);
+    }
+    check_filter_error(&filter_error)?;
+    
+    let mut collected = collected.into_inner();
+    let mut chunks = Vec::new();
+    let mut files = Vec::new();
+    for (path, chunk_count) in summary.processed {
+        for chunk in collected.remove(&path).unwrap_or_default() {
+            chunks.push(chunk_to_dict(py, chunk)?);
+        }
+        let file = PyDict::new(py);
+        file.set_item("path", path)?;
+        file.set_item("chunk_count", chunk_count)?;
+        files.push(file);
+    }
+    let skipped = summary.skipped
+        .into_iter()
+        .map(|skipped| {
+            let entry = PyDict::new(py);
+            entry.set_item("path", skipped.path)?;
+            entry.set_item("reason", skipped.reason)?;
+            Ok(entry)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let failed = summary.failed
+        .into_iter()
+        .map(|(path, error)| {
+            let entry = PyDict::new(py);
+            entry.set_item("path", path)?;
+            entry.set_item("error", error)?;
+            Ok(entry)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    
+    let result = PyDict::new(py);
+    result.set_item("chunks", chunks)?;
+    result.set_item("files", files)?;
+    result.set_item("skipped", skipped)?;
+    result.set_item("failed", failed)?;
+    Ok(result.into())
+}
+
+/// Name of the encoding an OpenAI model uses
+/// 
+/// `encoding_name_for_model("gpt-4o")` returns "o200k_base" and `"gpt-4"` returns
+/// "cl100k_base"; dated snapshots and fine-tunes resolve like their base model.
+/// Raises ValueError for unknown models.
+#[pyfunction]
+fn encoding_name_for_model(model: &str) -> PyResult<&'static str> {
+    encodings::encoding_for_model(model)
+        .map(EncodingKind::name)
+        .map_err(|e| to_py_err("Unknown model", e))
+}
+
+/// Deprecated alias of `encoding_name_for_model`
+/// 
+/// Kept so code written against the earlier name keeps importing; emits a
+/// DeprecationWarning on each call.
+#[pyfunction]
+fn get_encoding_for_model(py: Python<'_>, model: &str) -> PyResult<&'static str> {
+    PyErr::warn(
+        py,
+        &py.get_type::<PyDeprecationWarning>(),
+        c"get_encoding_for_model is deprecated, use encoding_name_for_model",
+        1,
+    )?;
+    encoding_name_for_model(model)
+}
+
+/// Tokenizer of an encoding by name ("o200k_base", "cl100k_base", "p50k_base", "r50k_base")
+/// 
+/// Same as `Encoding(name)`; raises ValueError for unknown names.
+#[pyfunction]
+fn get_encoding(name: &str) -> PyResult<PyEncoding> {
+    PyEncoding::new(name)
+}
+
+/// Tokenizer of the encoding an OpenAI model uses (see `encoding_name_for_model`)
+#[pyfunction]
+fn encoding_for_model(model: &str) -> PyResult<PyEncoding> {
+    let kind = encodings::encoding_for_model(model)
+        .map_err(|e| to_py_err("Unknown model", e))?;
+    PyEncoding::for_kind(kind)
+}
+
+/// Tokenizer exposed to Python, API-compatible with `tiktoken.Encoding` where practical
+/// 
+/// Instances share the process-wide tokenizer of their encoding (the same one the
+/// chunkers use), so only the first one of each encoding loads a vocabulary.
+/// Token IDs are identical to tiktoken's for the same encoding.
+/// 
+/// Special tokens behave as in tiktoken:
+/// - `allowed_special` ("all" or a collection, default empty): encoded as their special ID
+/// - `disallowed_special` ("all" or a collection, default "all" minus the allowed ones):
+///   raise ValueError when found in the text; pass `()` to encode them as plain text
+/// - `encode_ordinary` and `count_tokens` always treat them as plain text
+/// 
+/// Differences from tiktoken: unknown token IDs raise ValueError instead of KeyError,
+/// and `encode_batch` runs on the shared rayon pool (`num_threads` is accepted for
+/// compatibility but not used). Encoding and decoding release the GIL.
+#[pyclass(frozen, name = "Encoding", module = "myrustchunker")]
+struct PyEncoding {
+    kind: EncodingKind,
+    bpe: Arc<CoreBPE>,
+}
+
+impl PyEncoding {
+    fn for_kind(kind: EncodingKind) -> PyResult<Self> {
+        let bpe = CoreBPE::shared(kind)
+            .map_err(|e| to_py_err("Tokenizer initialization failed", e))?;
+        Ok(PyEncoding { kind, bpe })
+    }
+    
+    /// Resolve the `allowed_special` and `disallowed_special` arguments to token sets
+    fn special_sets(
+        &self,
+        allowed_special: Option<&Bound<'_, PyAny>>,
+        disallowed_special: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<(HashSet<String>, HashSet<String>)> {
+        let all = || self.special_tokens_set();
+        let allowed = match allowed_special {
+            Some(value) => special_token_set(value, all)?,
+            None => HashSet::new(),
+        };
+        let disallowed = match disallowed_special {
+            Some(value) => special_token_set(value, all)?,
+            None => all(),
+        };
+        let disallowed = disallowed.into_iter().filter(|token| !allowed.contains(token)).collect();
+        Ok((allowed, disallowed))
+    }
+}
+
+#[pymethods]
+impl PyEncoding {
+    #[new]
+    fn new(name: &str) -> PyResult<Self> {
+        let kind: EncodingKind = name.parse()
+            .map_err(|e| to_py_err("Unknown encoding", e))?;
+        Self::for_kind(kind)
+    }
+    
+    /// Tokenizer reading its ranks from a `.tiktoken` file, with the pattern and
+    /// special tokens of the encoding `name` (custom or fine-tuned vocabularies)
+    /// 
+    /// Raises ValueError for unknown names and malformed files (with the offending line).
+    #[staticmethod]
+    fn from_file(py: Python, path: PathBuf, name: &str) -> PyResult<Self> {
+        let kind: EncodingKind = name.parse()
+            .map_err(|e| to_py_err("Unknown encoding", e))?;
+        let bpe = py.allow_threads(|| CoreBPE::from_tiktoken_file(&path, kind))
+            .map_err(|e| to_py_err("Vocabulary loading failed", e))?;
+        Ok(PyEncoding { kind, bpe: Arc::new(bpe) })
+    }
+    
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.kind.name()
+    }
     
-    rt.block_on(async {
-        // Initialize the PDF processor with dynamic core detection
-        let processor = PdfProcessor::new().await
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Processor initialization failed: {}", e)))?;
+    /// Number of token IDs, ordinary and special (`max_token_value + 1`)
+    #[getter]
+    fn n_vocab(&self) -> Rank {
+        self.bpe.max_token_value() + 1
+    }
+    
+    #[getter]
+    fn max_token_value(&self) -> Rank {
+        self.bpe.max_token_value()
+    }
+    
+    /// ID of "<|endoftext|>"
+    #[getter]
+    fn eot_token(&self) -> Option<Rank> {
+        self.bpe.special_token_rank("<|endoftext|>")
+    }
+    
+    #[getter]
+    fn special_tokens_set(&self) -> HashSet<String> {
+        self.bpe.special_tokens().into_iter().map(str::to_string).collect()
+    }
+    
+    /// Encode text, treating special tokens as plain text
+    fn encode_ordinary(&self, py: Python, text: &str) -> Vec<Rank> {
+        let bpe = &self.bpe;
+        py.allow_threads(|| bpe.encode_ordinary(text))
+    }
+    
+    /// Encode text; see the class documentation for the special token arguments
+    #[pyo3(signature = (text, *, allowed_special=None, disallowed_special=None))]
+    fn encode(
+        &self,
+        py: Python,
+        text: &str,
+        allowed_special: Option<&Bound<'_, PyAny>>,
+        disallowed_special: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Vec<Rank>> {
+        let (allowed, disallowed) = self.special_sets(allowed_special, disallowed_special)?;
+        check_disallowed_special(text, &disallowed)?;
         
-        // Process the PDF and get chunk metadata
-        let chunks = processor.process_pdf(&pdf_path).await
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("PDF processing failed: {}", e)))?;
+        let bpe = &self.bpe;
+        let allowed: HashSet<&str> = allowed.iter().map(String::as_str).collect();
+        py.allow_threads(|| bpe.encode(text, &allowed))
+            .map_err(|e| to_py_err("Encoding failed", e))
+    }
+    
+    /// Encode text like `encode_ordinary`, returning `(token, start, end)` tuples
+    /// 
+    /// `offsets="char"` (default) gives positions in the Python string (`text[start:end]`),
+    /// `"byte"` positions in its UTF-8 encoding. A token that splits a character covers
+    /// the whole character in character offsets, so neighbouring tokens can overlap.
+    #[pyo3(signature = (text, *, offsets="char"))]
+    fn encode_with_offsets(&self, py: Python, text: &str, offsets: &str) -> PyResult<Vec<(Rank, usize, usize)>> {
+        let char_offsets = match offsets {
+            "char" => true,
+            "byte" => false,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid offsets: '{}' (expected 'char' or 'byte')", other
+                )));
+            }
+        };
         
-        // Convert Rust structs to Python dictionaries
-        let mut result = Vec::new();
-        for chunk in chunks {
-            let dict = PyDict::new(py);  // Changed from PyDict::new_bound to PyDict::new
-            dict.set_item("page", chunk.page)?;
-            dict.set_item("chunk_id", chunk.chunk_id)?;
-            dict.set_item("text", chunk.text)?;
-            dict.set_item("source", chunk.source)?;
-            dict.set_item("token_count", chunk.token_count)?;  // Add token count to output
-            result.push(dict.into());
+        let bpe = &self.bpe;
+        let tokens = py.allow_threads(|| {
+            let tokens = bpe.encode_with_offsets(text);
+            if char_offsets { tiktoken_core::to_char_offsets(text, &tokens) } else { tokens }
+        });
+        Ok(tokens.into_iter().map(|(token, range)| (token, range.start, range.end)).collect())
+    }
+    
+    /// Encode a list of texts in parallel
+    #[pyo3(signature = (text, *, num_threads=8, allowed_special=None, disallowed_special=None))]
+    fn encode_batch(
+        &self,
+        py: Python,
+        text: Vec<String>,
+        num_threads: usize,
+        allowed_special: Option<&Bound<'_, PyAny>>,
+        disallowed_special: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Vec<Vec<Rank>>> {
+        let _ = num_threads; // tiktoken compatibility; the rayon pool sizes itself
+        let (allowed, disallowed) = self.special_sets(allowed_special, disallowed_special)?;
+        for item in &text {
+            check_disallowed_special(item, &disallowed)?;
         }
         
-        Ok(result)
+        let bpe = &self.bpe;
+        let allowed: HashSet<&str> = allowed.iter().map(String::as_str).collect();
+        py.allow_threads(|| {
+            text.par_iter()
+                .map(|item| bpe.encode(item, &allowed))
+                .collect::<Result<Vec<_>, _>>()
+        })
+            .map_err(|e| to_py_err("Encoding failed", e))
+    }
+    
+    /// Number of tokens of `encode_ordinary(text)`, without building a list
+    fn count_tokens(&self, py: Python, text: &str) -> PyResult<usize> {
+        let bpe = &self.bpe;
+        py.allow_threads(|| bpe.count_tokens(text))
+            .map_err(|e| to_py_err("Encoding failed", e))
+    }
+    
+    /// Decode tokens to text; `errors` is the `bytes.decode` error handler for
+    /// byte sequences that are not valid UTF-8 ("replace" as in tiktoken, or "strict")
+    #[pyo3(signature = (tokens, errors="replace"))]
+    fn decode<'py>(&self, py: Python<'py>, tokens: Vec<Rank>, errors: &str) -> PyResult<Bound<'py, PyAny>> {
+        let bpe = &self.bpe;
+        let bytes = py.allow_threads(|| bpe.decode_bytes(&tokens))
+            .map_err(|e| to_py_err("Decoding failed", e))?;
+        PyBytes::new(py, &bytes).call_method1("decode", ("utf-8", errors))
+    }
+    
+    /// Concatenated bytes of the tokens, without UTF-8 decoding
+    fn decode_bytes<'py>(&self, py: Python<'py>, tokens: Vec<Rank>) -> PyResult<Bound<'py, PyBytes>> {
+        let bpe = &self.bpe;
+        let bytes = py.allow_threads(|| bpe.decode_bytes(&tokens))
+            .map_err(|e| to_py_err("Decoding failed", e))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+    
+    /// Bytes of a single token
+    fn decode_single_token_bytes<'py>(&self, py: Python<'py>, token: Rank) -> PyResult<Bound<'py, PyBytes>> {
+        self.bpe
+            .token_bytes(token)
+            .map(|bytes| PyBytes::new(py, bytes))
+            .ok_or_else(|| PyValueError::new_err(format!("Invalid token: {}", token)))
+    }
+    
+    fn __repr__(&self) -> String {
+        format!("<Encoding '{}'>", self.kind)
+    }
+}
+
+/// Read an `allowed_special` / `disallowed_special` argument: "all" or a collection of tokens
+fn special_token_set(value: &Bound<'_, PyAny>, all: impl FnOnce() -> HashSet<String>) -> PyResult<HashSet<String>> {
+    if let Ok(text) = value.extract::<&str>() {
+        return match text {
+            "all" => Ok(all()),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid special tokens: expected \"all\" or a collection of tokens, got {:?}", text
+            ))),
+        };
+    }
+    value.try_iter()?.map(|item| item?.extract::<String>()).collect()
+}
+
+/// Raise ValueError, worded as in tiktoken, when the text contains a disallowed special token
+fn check_disallowed_special(text: &str, disallowed: &HashSet<String>) -> PyResult<()> {
+    let first = disallowed
+        .iter()
+        .filter_map(|token| text.find(token.as_str()).map(|position| (position, token)))
+        .min();
+    match first {
+        Some((_, token)) => Err(PyValueError::new_err(format!(
+            "Encountered text corresponding to disallowed special token '{}'.\n\
+             If you want this text to be encoded as a special token, pass it to `allowed_special`, \
+             e.g. `allowed_special={{'{}', ...}}`.\n\
+             If you want this text to be encoded as normal text, disable the check for this token \
+             by passing `disallowed_special=(enc.special_tokens_set - {{'{}'}})`.\n\
+             To disable this check for all special tokens, pass `disallowed_special=()`.",
+            token, token, token
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Processor configuration and per-document options parsed from the keyword
+/// arguments shared by `process_pdf` and `process_file`
+struct PipelineSettings {
+    backend_kind: BackendKind,
+    pdfium_path: Option<PathBuf>,
+    normalization: NormalizationOptions,
+    encoding: EncodingKind,
+    tokenizer: Option<Arc<CoreBPE>>, // Loaded from `vocabulary_path`, replaces the built-in vocabulary
+    options: ProcessOptions,
+    filter_error: Arc<Mutex<Option<PyErr>>>, // First exception raised by `page_filter`
+}
+
+impl PipelineSettings {
+    #[allow(clippy::too_many_arguments)]
+    fn from_args(
+        password: Option<String>,
+        respect_permissions: bool,
+        backend: &str,
+        pdfium_path: Option<PathBuf>,
+        pages: Option<&str>,
+        min_chars: usize,
+        skip_pattern: Option<&str>,
+        page_filter: Option<PyObject>,
+        normalization: Option<&Bound<'_, PyDict>>,
+        encoding: &str,
+        vocabulary_path: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let backend_kind: BackendKind = backend.parse()
+            .map_err(|e| to_py_err("Invalid backend", e))?;
+        let encoding: EncodingKind = encoding.parse()
+            .map_err(|e| to_py_err("Invalid encoding", e))?;
+        let pages = pages
+            .map(str::parse::<PageSelection>)
+            .transpose()
+            .map_err(|e| to_py_err("Invalid pages", e))?;
+        let skip_pattern = skip_pattern
+            .map(regex::Regex::new)
+            .transpose()
+            .map_err(|e| PyValueError::new_err(format!("Invalid skip_pattern: {}", e)))?;
+        let filter_error = Arc::new(Mutex::new(None));
+        let page_filter = PageFilter {
+            min_chars,
+            skip_pattern,
+            predicate: page_filter.map(|callable| python_page_predicate(callable, filter_error.clone())),
+        };
+        let normalization = normalization
+            .map(normalization_options)
+            .transpose()?
+            .unwrap_or_default();
+        let tokenizer = vocabulary_path
+            .map(|path| CoreBPE::from_tiktoken_file(&path, encoding).map(Arc::new))
+            .transpose()
+            .map_err(|e| to_py_err("Vocabulary loading failed", e))?;
+        
+        Ok(PipelineSettings {
+            backend_kind,
+            pdfium_path,
+            normalization,
+            encoding,
+            tokenizer,
+            options: ProcessOptions {
+                password,
+                respect_permissions,
+                pages,
+                page_filter,
+            },
+            filter_error,
+        })
+    }
+    
+    /// Build the processor these settings describe
+    async fn processor(&self) -> PyResult<PdfProcessor> {
+        let processor = PdfProcessor::new(
+            self.backend_kind,
+            self.pdfium_path.as_deref(),
+            self.normalization.clone(),
+            self.encoding,
+        ).await
+            .map_err(|e| to_py_err("Processor initialization failed", e))?;
+        Ok(match &self.tokenizer {
+            Some(tokenizer) => processor.with_tokenizer(tokenizer.clone()),
+            None => processor,
+        })
+    }
+}
+
+/// Raise the exception a `page_filter` call stored during the run, if any
+fn check_filter_error(filter_error: &Mutex<Option<PyErr>>) -> PyResult<()> {
+    match filter_error.lock().take() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Run the processing pipeline on one file and convert the chunks to Python dicts
+/// 
+/// `any_format` dispatches on the file extension; otherwise the file is read as a PDF.
+fn run_pipeline(py: Python, path: &str, settings: PipelineSettings, any_format: bool) -> PyResult<Vec<PyObject>> {
+    let filter_error = settings.filter_error.clone();
+    
+    // Release the GIL while working so page filters can take it from extraction threads
+    let result = py.allow_threads(|| {
+        let rt = new_runtime()?;
+        rt.block_on(async {
+            // Initialize the PDF processor with dynamic core detection
+            let processor = settings.processor().await?;
+        
+            // Process the file and get chunk metadata
+            if any_format {
+                processor.process_file(path, &settings.options).await
+                    .map_err(|e| to_py_err("File processing failed", e))
+            } else {
+                processor.process_pdf(path, &settings.options).await
+                    .map_err(|e| to_py_err("PDF processing failed", e))
+            }
+        })
+    });
+    check_filter_error(&filter_error)?;
+        
+    // Convert Rust structs to Python dictionaries
+    result?.into_iter().map(|chunk| chunk_to_dict(py, chunk)).collect()
+}
+
+/// Create the tokio runtime the async pipeline runs on
+fn new_runtime() -> PyResult<tokio::runtime::Runtime> {
+    // Multi-threaded: directory processing blocks a runtime worker while rayon works
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to create runtime: {}", e)))
+}
+
+/// Convert a chunk to the Python dictionary returned to callers
+fn chunk_to_dict(py: Python, chunk: ChunkMetadata) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);  // Changed from PyDict::new_bound to PyDict::new
+    dict.set_item("page", chunk.page)?;
+    dict.set_item("page_label", chunk.page_label)?;
+    dict.set_item("chunk_id", chunk.chunk_id)?;
+    dict.set_item("content_id", chunk.content_id)?;
+    dict.set_item("document_id", chunk.document_id)?;
+    dict.set_item("text", chunk.text)?;
+    dict.set_item("source", chunk.source)?;
+    dict.set_item("token_count", chunk.token_count)?;  // Add token count to output
+    let bboxes: Vec<(usize, f32, f32, f32, f32)> = chunk.bboxes
+        .iter()
+        .map(|b| (b.page, b.x0, b.y0, b.x1, b.y1))
+        .collect();
+    dict.set_item("bboxes", bboxes)?;
+    dict.set_item("section", chunk.section)?;
+    dict.set_item("anchor", chunk.anchor)?;
+    dict.set_item("chapter", chunk.chapter)?;
+    dict.set_item("spine_index", chunk.spine_index)?;
+    dict.set_item("start_line", chunk.start_line)?;
+    dict.set_item("end_line", chunk.end_line)?;
+    Ok(dict.into())
+}
+
+/// Wrap a Python callable `(page, page_label, text) -> bool` as a page predicate
+/// 
+/// The first exception raised by the callable is stored in `error` for the caller
+/// to raise after the run; that page and all later ones are skipped without calling
+/// it again, so a broken filter neither passes every page nor fails once per page.
+fn python_page_predicate(callable: PyObject, error: Arc<Mutex<Option<PyErr>>>) -> page_selection::PagePredicate {
+    Arc::new(move |page: &text_extractor::PageText| {
+        if error.lock().is_some() {
+            return false;
+        }
+        Python::with_gil(|py| {
+            callable
+                .call1(py, (page.page_index + 1, page.page_label.as_deref(), page.text.as_str()))
+                .and_then(|keep| keep.is_truthy(py))
+                .unwrap_or_else(|e| {
+                    error.lock().get_or_insert(e);
+                    false
+                })
+        })
     })
-}
\ No newline at end of file
+}
+
+/// Build normalization options from the `normalization` dict of `process_pdf`
+/// 
+/// Missing keys keep their defaults; unknown keys raise ValueError.
+fn normalization_options(dict: &Bound<'_, PyDict>) -> PyResult<NormalizationOptions> {
+    let mut options = NormalizationOptions::default();
+    for (key, value) in dict.iter() {
+        let key: String = key.extract()?;
+        match key.as_str() {
+            "unicode_form" => {
+                options.unicode_form = value.extract::<String>()?
+                    .parse()
+                    .map_err(|e| to_py_err("Invalid normalization", e))?;
+            }
+            "ligatures" => options.expand_ligatures = value.is_truthy()?,
+            "quotes" => options.fold_quotes = value.is_truthy()?,
+            "dashes" => options.fold_dashes = value.is_truthy()?,
+            "invisible" => options.remove_invisible = value.is_truthy()?,
+            "spaces" => options.normalize_spaces = value.is_truthy()?,
+            "dehyphenate" => options.dehyphenate = value.is_truthy()?,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid normalization: unknown option '{}' (expected unicode_form, ligatures, \
+                     quotes, dashes, invisible, spaces or dehyphenate)",
+                    other
+                )));
+            }
+        }
+    }
+    Ok(options)
+}
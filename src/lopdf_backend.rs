@@ -0,0 +1,936 @@
+use crate::error::ProcessingError;
+use crate::pdf_backend::{check_extraction_permission, PdfBackend};
+use crate::pdf_processor::ProcessOptions;
+use crate::text_extractor::PageText;
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId, Permissions};
+use rayon::prelude::*;
+use rustc_hash::FxHashMap as HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use log::{debug, error, info};
+
+/// Maximum nesting of form XObjects followed while reading a page
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Maximum depth of the /PageLabels number tree followed (guards against reference
+/// cycles; real trees are one or two levels deep)
+const MAX_NUMBER_TREE_DEPTH: usize = 8;
+
+/// Horizontal gaps wider than this fraction of the font size are word gaps
+/// (pen moves between text runs and TJ adjustments alike)
+const WORD_GAP: f32 = 0.2;
+
+/// Glyph width (thousandths of an em) assumed for fonts without width metrics
+const DEFAULT_GLYPH_WIDTH: f32 = 500.0;
+
+/// Identity text matrix `[a b c d e f]`
+const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Pure-Rust extraction backend built on lopdf
+/// 
+/// Interprets page content streams directly:
+/// - Text showing operators (`Tj`, `TJ`, `'`, `"`) decoded through the font's
+///   ToUnicode CMap, falling back to its simple encoding
+/// - Text positioning operators (`Td`, `TD`, `T*`, `Tm`) to recover line and word breaks;
+///   the pen position after each run comes from the font's glyph widths, so text
+///   placed glyph by glyph is not split into letters
+/// - Form XObjects (`Do`) so text placed in forms is not lost
+/// 
+/// It needs no native library, but does not report character bounding boxes.
+pub struct LopdfBackend;
+
+impl LopdfBackend {
+    pub fn new() -> Self {
+        LopdfBackend
+    }
+}
+
+impl PdfBackend for LopdfBackend {
+    fn name(&self) -> &'static str {
+        "lopdf"
+    }
+    
+    /// Extract text from all pages in parallel (lopdf documents are immutable once loaded)
+    fn extract_pages(&self, path: &Path, options: &ProcessOptions) -> Result<Vec<PageText>, ProcessingError> {
+        let document = load_document(path, options)?;
+        
+        let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+        info!("PDF loaded successfully. Pages: {}", pages.len());
+        
+        let labels = page_labels(&document, pages.len());
+        
+        // Only selected pages are decoded at all
+        let page_texts = options.page_indices(pages.len())
+            .into_par_iter()
+            .filter_map(|page_idx| {
+                match extract_page_text(&document, pages[page_idx]) {
+                    Ok(text) if !text.is_empty() => Some(PageText {
+                        page_index: page_idx,
+                        page_label: labels[page_idx].clone(),
+                        text,
+                        char_boxes: Vec::new(),
+                    }).filter(|page| options.keep_page(page)),
+                    Ok(_) => {
+                        debug!("Page {} contains no text", page_idx);
+                        None
+                    }
+                    Err(e) => {
+                        error!("Failed to extract text from page {}: {}", page_idx, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        
+        Ok(page_texts)
+    }
+}
+
+/// Load and, if necessary, decrypt a document
+fn load_document(path: &Path, options: &ProcessOptions) -> Result<Document, ProcessingError> {
+    let pdf_path = path.display().to_string();
+    
+    let mut document = Document::load(path)
+        .map_err(|e| ProcessingError::PdfLoadError(format!("Failed to load {}: {}", pdf_path, e)))?;
+    
+    // lopdf has already decrypted documents that open with an empty user password
+    if document.is_encrypted() {
+        let password = options.password.as_deref()
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| ProcessingError::PasswordRequired(pdf_path.clone()))?;
+        
+        document.authenticate_password(password)
+            .map_err(|_| ProcessingError::IncorrectPassword(pdf_path.clone()))?;
+        document.decrypt(password)
+            .map_err(|e| ProcessingError::PdfLoadError(format!("Failed to decrypt {}: {}", pdf_path, e)))?;
+    }
+    
+    let can_extract = document.encryption_state
+        .as_ref()
+        .is_none_or(|state| state.permissions().contains(Permissions::COPYABLE));
+    check_extraction_permission(can_extract, &pdf_path, options)?;
+    
+    Ok(document)
+}
+
+/// Extract the whitespace-normalized text of one page (one text line per `\n`)
+fn extract_page_text(document: &Document, page_id: ObjectId) -> Result<String, ProcessingError> {
+    let content = document.get_and_decode_page_content(page_id)
+        .map_err(|e| ProcessingError::PdfLoadError(format!("Failed to decode page content: {}", e)))?;
+    
+    let (direct, inherited) = document.get_page_resources(page_id)
+        .map_err(|e| ProcessingError::PdfLoadError(format!("Failed to read page resources: {}", e)))?;
+    let resources: Vec<&Dictionary> = direct
+        .into_iter()
+        .chain(inherited.iter().filter_map(|id| document.get_dictionary(*id).ok()))
+        .collect();
+    
+    let mut walker = ContentWalker::new(document);
+    walker.walk(&content, &resources, 0);
+    
+    // Same whitespace normalization as the pdfium extractor
+    Ok(walker.text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Follow a reference (if any) to the object it points at
+fn deref<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    document.dereference(object).ok().map(|(_, object)| object)
+}
+
+/// Look up a named resource (font, XObject, ...) in a resource dictionary stack
+fn lookup_resource<'a>(
+    document: &'a Document,
+    resources: &[&'a Dictionary],
+    category: &[u8],
+    name: &[u8],
+) -> Option<&'a Object> {
+    resources.iter().find_map(|dict| {
+        let entries = deref(document, dict.get(category).ok()?)?.as_dict().ok()?;
+        deref(document, entries.get(name).ok()?)
+    })
+}
+
+/// Walks a content stream, collecting decoded text with line and word breaks
+struct ContentWalker<'a> {
+    document: &'a Document,
+    fonts: HashMap<*const Dictionary, Rc<PdfFont<'a>>>,
+    font: Option<Rc<PdfFont<'a>>>,
+    font_size: f32,
+    char_spacing: f32,          // Tc
+    word_spacing: f32,          // Tw
+    horizontal_scale: f32,      // Tz / 100
+    leading: f32,               // TL
+    text_matrix: [f32; 6],
+    line_matrix: [f32; 6],
+    text_end: Option<[f32; 2]>, // Pen position after the last text shown
+    text: String,
+}
+
+impl<'a> ContentWalker<'a> {
+    fn new(document: &'a Document) -> Self {
+        Self {
+            document,
+            fonts: HashMap::default(),
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            text_matrix: IDENTITY,
+            line_matrix: IDENTITY,
+            text_end: None,
+            text: String::new(),
+        }
+    }
+    
+    fn walk(&mut self, content: &Content, resources: &[&'a Dictionary], depth: usize) {
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            let number = |i: usize| operands.get(i).and_then(|o| o.as_float().ok());
+            
+            match operation.operator.as_str() {
+                "Tf" => {
+                    self.font = operands.first()
+                        .and_then(|o| o.as_name().ok())
+                        .and_then(|name| lookup_resource(self.document, resources, b"Font", name))
+                        .and_then(|o| o.as_dict().ok())
+                        .map(|font| self.font_for(font));
+                    self.font_size = number(1).unwrap_or(self.font_size);
+                }
+                "Tc" => self.char_spacing = number(0).unwrap_or(0.0),
+                "Tw" => self.word_spacing = number(0).unwrap_or(0.0),
+                "Tz" => self.horizontal_scale = number(0).unwrap_or(100.0) / 100.0,
+                "TL" => self.leading = number(0).unwrap_or(0.0),
+                "BT" => {
+                    self.text_matrix = IDENTITY;
+                    self.line_matrix = IDENTITY;
+                }
+                "Td" | "TD" => {
+                    let (tx, ty) = (number(0).unwrap_or(0.0), number(1).unwrap_or(0.0));
+                    if operation.operator == "TD" {
+                        self.leading = -ty;
+                    }
+                    self.next_line(tx, ty);
+                    self.moved();
+                }
+                "Tm" => {
+                    if let Some(matrix) = (0..6).map(number).collect::<Option<Vec<f32>>>() {
+                        self.line_matrix.copy_from_slice(&matrix);
+                        self.text_matrix = self.line_matrix;
+                        self.moved();
+                    }
+                }
+                "T*" => {
+                    self.next_line(0.0, -self.leading);
+                    self.newline();
+                }
+                "Tj" => self.show(operands.first()),
+                "'" => {
+                    self.next_line(0.0, -self.leading);
+                    self.newline();
+                    self.show(operands.first());
+                }
+                "\"" => {
+                    self.word_spacing = number(0).unwrap_or(self.word_spacing);
+                    self.char_spacing = number(1).unwrap_or(self.char_spacing);
+                    self.next_line(0.0, -self.leading);
+                    self.newline();
+                    self.show(operands.get(2));
+                }
+                "TJ" => {
+                    for element in operands.first().and_then(|o| o.as_array().ok()).into_iter().flatten() {
+                        match element {
+                            Object::String(..) => self.show(Some(element)),
+                            _ => {
+                                // Adjustments are in thousandths of an em, positive moving left
+                                let Ok(adjust) = element.as_float() else { continue };
+                                self.advance(-adjust / 1000.0 * self.font_size * self.horizontal_scale);
+                                if -adjust / 1000.0 > WORD_GAP {
+                                    self.space();
+                                }
+                            }
+                        }
+                    }
+                }
+                "ET" => self.space(),
+                "Do" if depth < MAX_FORM_DEPTH => {
+                    let form = operands.first()
+                        .and_then(|o| o.as_name().ok())
+                        .and_then(|name| lookup_resource(self.document, resources, b"XObject", name))
+                        .and_then(|o| o.as_stream().ok())
+                        .filter(|stream| stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Form"));
+                    
+                    if let Some(form) = form {
+                        let Ok(content) = form.get_plain_content().and_then(|data| Content::decode(&data)) else {
+                            continue;
+                        };
+                        
+                        // Form resources take precedence over the enclosing ones
+                        let mut form_resources: Vec<&'a Dictionary> = form.dict.get(b"Resources").ok()
+                            .and_then(|o| deref(self.document, o))
+                            .and_then(|o| o.as_dict().ok())
+                            .into_iter()
+                            .collect();
+                        form_resources.extend_from_slice(resources);
+                        
+                        self.walk(&content, &form_resources, depth + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    /// Build (or reuse) the decoder and metrics for a font dictionary
+    fn font_for(&mut self, font: &'a Dictionary) -> Rc<PdfFont<'a>> {
+        let document = self.document;
+        self.fonts
+            .entry(font as *const Dictionary)
+            .or_insert_with(|| Rc::new(PdfFont {
+                decoder: FontDecoder::new(document, font),
+                widths: GlyphWidths::new(document, font),
+            }))
+            .clone()
+    }
+    
+    fn show(&mut self, operand: Option<&Object>) {
+        let (Some(font), Some(Object::String(bytes, _))) = (self.font.clone(), operand) else {
+            return;
+        };
+        self.text.push_str(&font.decoder.decode(bytes));
+        
+        let code_len = font.widths.code_len;
+        let mut distance = 0.0;
+        for code in bytes.chunks(code_len) {
+            let code = code.iter().fold(0u32, |code, &byte| (code << 8) | byte as u32);
+            distance += font.widths.width(code) / 1000.0 * self.font_size + self.char_spacing;
+            // Word spacing applies to the single-byte code 32 only
+            if code_len == 1 && code == 32 {
+                distance += self.word_spacing;
+            }
+        }
+        self.advance(distance * self.horizontal_scale);
+        self.text_end = Some([self.text_matrix[4], self.text_matrix[5]]);
+    }
+    
+    /// Move the pen `distance` along the baseline (text space units)
+    fn advance(&mut self, distance: f32) {
+        self.text_matrix = translate(&self.text_matrix, distance, 0.0);
+    }
+    
+    /// Start a new line offset from the start of the current one (`Td`)
+    fn next_line(&mut self, tx: f32, ty: f32) {
+        self.line_matrix = translate(&self.line_matrix, tx, ty);
+        self.text_matrix = self.line_matrix;
+    }
+    
+    /// Break the text after a positioning operator moved the pen: a newline when
+    /// it left the baseline of the previous text, a space when it skipped a word gap
+    fn moved(&mut self) {
+        let Some([end_x, end_y]) = self.text_end else {
+            return;
+        };
+        let [a, b, ..] = self.text_matrix;
+        let scale = a.hypot(b);
+        if scale == 0.0 {
+            return;
+        }
+        
+        // Distances along and across the (possibly rotated) baseline
+        let (dx, dy) = (self.text_matrix[4] - end_x, self.text_matrix[5] - end_y);
+        let along = (dx * a + dy * b) / scale;
+        let across = (dy * a - dx * b) / scale;
+        if across.abs() > 0.01 {
+            self.newline();
+        } else if along > WORD_GAP * self.font_size * scale {
+            self.space();
+        }
+    }
+    
+    fn newline(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+    
+    fn space(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+            self.text.push(' ');
+        }
+    }
+}
+
+/// Translate a text matrix by `(tx, ty)` in its own coordinates
+fn translate(matrix: &[f32; 6], tx: f32, ty: f32) -> [f32; 6] {
+    let [a, b, c, d, e, f] = *matrix;
+    [a, b, c, d, tx * a + ty * c + e, tx * b + ty * d + f]
+}
+
+/// A font as used by the content walker
+struct PdfFont<'a> {
+    decoder: FontDecoder<'a>,
+    widths: GlyphWidths,
+}
+
+/// Horizontal glyph advances of a font, in thousandths of an em
+struct GlyphWidths {
+    code_len: usize, // Bytes per character code (2 for composite fonts)
+    widths: HashMap<u32, f32>,
+    default: f32,
+}
+
+impl GlyphWidths {
+    /// Read `/Widths` of simple fonts or the descendant's `/W` and `/DW` of composite
+    /// (Type0) fonts; fonts without metrics (the standard 14) get an average width
+    fn new(document: &Document, font: &Dictionary) -> Self {
+        fn array<'a>(document: &'a Document, dict: &'a Dictionary, key: &[u8]) -> Option<&'a Vec<Object>> {
+            dict.get(key).ok().and_then(|o| deref(document, o)).and_then(|o| o.as_array().ok())
+        }
+        let number = |object: &Object| deref(document, object).and_then(|o| o.as_float().ok());
+        let mut widths = HashMap::default();
+        
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0") {
+            let descendant = array(document, font, b"DescendantFonts")
+                .and_then(|fonts| fonts.first())
+                .and_then(|o| deref(document, o))
+                .and_then(|o| o.as_dict().ok());
+            let default = descendant
+                .and_then(|d| d.get(b"DW").ok())
+                .and_then(number)
+                .unwrap_or(1000.0);
+            
+            // `/W [c [w1 w2 ...] c_first c_last w ...]`
+            let entries: Vec<&Object> = descendant
+                .and_then(|d| array(document, d, b"W"))
+                .into_iter()
+                .flatten()
+                .filter_map(|o| deref(document, o))
+                .collect();
+            let mut index = 0;
+            while index + 1 < entries.len() {
+                let Some(first) = number(entries[index]) else { break };
+                let first = first as u32;
+                if let Ok(list) = entries[index + 1].as_array() {
+                    for (code, width) in (first..).zip(list.iter().filter_map(number)) {
+                        widths.insert(code, width);
+                    }
+                    index += 2;
+                } else {
+                    let (Some(last), Some(width)) = (number(entries[index + 1]), entries.get(index + 2).and_then(|o| number(o))) else {
+                        break;
+                    };
+                    for code in first..=(last as u32).min(first.saturating_add(0xFFFF)) {
+                        widths.insert(code, width);
+                    }
+                    index += 3;
+                }
+            }
+            return Self { code_len: 2, widths, default };
+        }
+        
+        let first_char = font.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32;
+        for (code, width) in (first_char..).zip(array(document, font, b"Widths").into_iter().flatten().filter_map(number)) {
+            widths.insert(code, width);
+        }
+        let descriptor = font.get(b"FontDescriptor").ok().and_then(|o| deref(document, o)).and_then(|o| o.as_dict().ok());
+        let monospaced = font.get(b"BaseFont").and_then(Object::as_name).is_ok_and(|name| name.starts_with(b"Courier"));
+        let default = descriptor
+            .and_then(|d| d.get(b"MissingWidth").or_else(|_| d.get(b"AvgWidth")).ok())
+            .and_then(number)
+            .filter(|&width| width > 0.0)
+            .unwrap_or(if monospaced { 600.0 } else { DEFAULT_GLYPH_WIDTH });
+        Self { code_len: 1, widths, default }
+    }
+    
+    fn width(&self, code: u32) -> f32 {
+        self.widths.get(&code).copied().unwrap_or(self.default)
+    }
+}
+
+/// Maps character codes of one font to Unicode text
+enum FontDecoder<'a> {
+    ToUnicode(ToUnicodeMap),
+    Encoding(Encoding<'a>),
+    Undecodable,
+}
+
+impl<'a> FontDecoder<'a> {
+    /// Prefer the font's ToUnicode CMap, then its named or built-in encoding
+    fn new(document: &'a Document, font: &'a Dictionary) -> Self {
+        let to_unicode = font.get(b"ToUnicode").ok()
+            .and_then(|o| deref(document, o))
+            .and_then(|o| o.as_stream().ok())
+            .and_then(|stream| stream.get_plain_content().ok())
+            .map(|data| ToUnicodeMap::parse(&data))
+            .filter(|map| !map.is_empty());
+        
+        if let Some(map) = to_unicode {
+            return FontDecoder::ToUnicode(map);
+        }
+        
+        match font.get_font_encoding(document) {
+            Ok(encoding) => FontDecoder::Encoding(encoding),
+            Err(e) => {
+                debug!("Font has no usable encoding: {}", e);
+                FontDecoder::Undecodable
+            }
+        }
+    }
+    
+    fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            FontDecoder::ToUnicode(map) => map.decode(bytes),
+            FontDecoder::Encoding(encoding) => encoding.bytes_to_string(bytes).unwrap_or_default(),
+            FontDecoder::Undecodable => String::new(),
+        }
+    }
+}
+
+/// Parsed ToUnicode CMap (`bfchar` / `bfrange` mappings)
+#[derive(Debug, Default)]
+pub struct ToUnicodeMap {
+    code_lengths: Vec<usize>, // Byte lengths allowed by the codespace ranges, ascending
+    mappings: HashMap<(usize, u32), String>, // (code length, code) -> text
+}
+
+/// Token of the PostScript-like CMap syntax
+#[derive(Debug)]
+enum CMapToken {
+    Hex(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    Keyword(String),
+}
+
+impl ToUnicodeMap {
+    /// Parse the relevant sections of a ToUnicode CMap stream
+    pub fn parse(data: &[u8]) -> Self {
+        let tokens = Self::tokenize(data);
+        let mut map = ToUnicodeMap::default();
+        let mut i = 0;
+        
+        while i < tokens.len() {
+            match &tokens[i] {
+                CMapToken::Keyword(k) if k == "begincodespacerange" => {
+                    i += 1;
+                    while let (Some(CMapToken::Hex(low)), Some(CMapToken::Hex(_))) = (tokens.get(i), tokens.get(i + 1)) {
+                        if !map.code_lengths.contains(&low.len()) {
+                            map.code_lengths.push(low.len());
+                        }
+                        i += 2;
+                    }
+                }
+                CMapToken::Keyword(k) if k == "beginbfchar" => {
+                    i += 1;
+                    while let (Some(CMapToken::Hex(src)), Some(CMapToken::Hex(dst))) = (tokens.get(i), tokens.get(i + 1)) {
+                        map.insert(src, Self::utf16_units(dst), 0);
+                        i += 2;
+                    }
+                }
+                CMapToken::Keyword(k) if k == "beginbfrange" => {
+                    i += 1;
+                    while let (Some(CMapToken::Hex(low)), Some(CMapToken::Hex(high))) = (tokens.get(i), tokens.get(i + 1)) {
+                        let (low_code, high_code) = (Self::code(low), Self::code(high));
+                        match tokens.get(i + 2) {
+                            // <low> <high> <dst>: consecutive codes increment the last UTF-16 unit
+                            Some(CMapToken::Hex(dst)) => {
+                                let units = Self::utf16_units(dst);
+                                for (step, code) in (low_code..=high_code).take(0x10000).enumerate() {
+                                    map.insert(&Self::code_bytes(code, low.len()), units.clone(), step as u16);
+                                }
+                                i += 3;
+                            }
+                            // <low> <high> [<dst1> <dst2> ...]: one destination per code
+                            Some(CMapToken::ArrayStart) => {
+                                i += 3;
+                                let mut code = low_code;
+                                while let Some(CMapToken::Hex(dst)) = tokens.get(i) {
+                                    if code <= high_code {
+                                        map.insert(&Self::code_bytes(code, low.len()), Self::utf16_units(dst), 0);
+                                    }
+                                    code = code.saturating_add(1);
+                                    i += 1;
+                                }
+                                if let Some(CMapToken::ArrayEnd) = tokens.get(i) {
+                                    i += 1;
+                                }
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        
+        // Without codespace ranges, fall back to the code lengths actually mapped
+        if map.code_lengths.is_empty() {
+            map.code_lengths = map.mappings.keys().map(|(len, _)| *len).collect();
+            map.code_lengths.dedup();
+        }
+        map.code_lengths.sort_unstable();
+        map.code_lengths.dedup();
+        map
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+    
+    /// Decode a shown string, matching the shortest mapped code at each position
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let mut text = String::new();
+        let mut pos = 0;
+        let min_len = self.code_lengths.first().copied().unwrap_or(1).max(1);
+        
+        while pos < bytes.len() {
+            let matched = self.code_lengths.iter().find_map(|&len| {
+                let code_bytes = bytes.get(pos..pos + len)?;
+                self.mappings.get(&(len, Self::code(code_bytes))).map(|s| (len, s))
+            });
+            
+            match matched {
+                Some((len, mapped)) => {
+                    text.push_str(mapped);
+                    pos += len;
+                }
+                // Unmapped code: skip it rather than emit garbage
+                None => pos += min_len,
+            }
+        }
+        text
+    }
+    
+    fn insert(&mut self, src: &[u8], mut units: Vec<u16>, increment: u16) {
+        if let Some(last) = units.last_mut() {
+            *last = last.wrapping_add(increment);
+        }
+        let text = String::from_utf16_lossy(&units);
+        self.mappings.insert((src.len(), Self::code(src)), text);
+    }
+    
+    fn code(bytes: &[u8]) -> u32 {
+        bytes.iter().take(4).fold(0, |acc, &b| (acc << 8) | b as u32)
+    }
+    
+    fn code_bytes(code: u32, len: usize) -> Vec<u8> {
+        (0..len).rev().map(|shift| (code >> (8 * shift)) as u8).collect()
+    }
+    
+    fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+        bytes.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect()
+    }
+    
+    fn tokenize(data: &[u8]) -> Vec<CMapToken> {
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        
+        while i < data.len() {
+            match data[i] {
+                b'%' => {
+                    while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                        i += 1;
+                    }
+                }
+                b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+                b'>' if data.get(i + 1) == Some(&b'>') => i += 2,
+                b'<' => {
+                    let end = data[i..].iter().position(|&b| b == b'>').map_or(data.len(), |p| i + p);
+                    let digits: Vec<u8> = data[i + 1..end].iter().copied().filter(u8::is_ascii_hexdigit).collect();
+                    let bytes = digits
+                        .chunks(2)
+                        .map(|pair| {
+                            let hex = std::str::from_utf8(pair).unwrap_or("0");
+                            // A trailing odd digit is padded with zero per the PDF spec
+                            u8::from_str_radix(&format!("{:0<2}", hex), 16).unwrap_or(0)
+                        })
+                        .collect();
+                    tokens.push(CMapToken::Hex(bytes));
+                    i = end + 1;
+                }
+                b'[' => {
+                    tokens.push(CMapToken::ArrayStart);
+                    i += 1;
+                }
+                b']' => {
+                    tokens.push(CMapToken::ArrayEnd);
+                    i += 1;
+                }
+                b if b.is_ascii_whitespace() => i += 1,
+                _ => {
+                    let start = i;
+                    while i < data.len() && !data[i].is_ascii_whitespace() && !b"<>[]%".contains(&data[i]) {
+                        i += 1;
+                    }
+                    tokens.push(CMapToken::Keyword(String::from_utf8_lossy(&data[start..i]).into_owned()));
+                }
+            }
+        }
+        tokens
+    }
+}
+
+/// Resolve the document's /PageLabels number tree into one label per page
+fn page_labels(document: &Document, page_count: usize) -> Vec<Option<String>> {
+    let mut ranges: Vec<(usize, &Dictionary)> = Vec::new();
+    
+    let root = document.catalog().ok()
+        .and_then(|catalog| catalog.get(b"PageLabels").ok())
+        .and_then(|o| deref(document, o))
+        .and_then(|o| o.as_dict().ok());
+    if let Some(root) = root {
+        collect_label_ranges(document, root, &mut ranges, 0);
+    }
+    ranges.sort_by_key(|(start, _)| *start);
+    
+    (0..page_count)
+        .map(|page_idx| {
+            let range = ranges.partition_point(|(start, _)| *start <= page_idx);
+            let (start, style) = ranges.get(range.checked_sub(1)?)?;
+            Some(format_page_label(document, style, page_idx - start))
+        })
+        .collect()
+}
+
+/// Collect `(first page index, label dictionary)` pairs from a number tree node
+fn collect_label_ranges<'a>(
+    document: &'a Document,
+    node: &'a Dictionary,
+    ranges: &mut Vec<(usize, &'a Dictionary)>,
+    depth: usize,
+) {
+    if depth > MAX_NUMBER_TREE_DEPTH {
+        return;
+    }
+    
+    if let Some(nums) = node.get(b"Nums").ok().and_then(|o| deref(document, o)).and_then(|o| o.as_array().ok()) {
+        for pair in nums.chunks(2) {
+            let start = pair[0].as_i64().ok().and_then(|n| usize::try_from(n).ok());
+            let style = pair.get(1).and_then(|o| deref(document, o)).and_then(|o| o.as_dict().ok());
+            if let (Some(start), Some(style)) = (start, style) {
+                ranges.push((start, style));
+            }
+        }
+    }
+    
+    if let Some(kids) = node.get(b"Kids").ok().and_then(|o| deref(document, o)).and_then(|o| o.as_array().ok()) {
+        for kid in kids.iter().filter_map(|o| deref(document, o)).filter_map(|o| o.as_dict().ok()) {
+            collect_label_ranges(document, kid, ranges, depth + 1);
+        }
+    }
+}
+
+/// Format the label of the page `offset` pages into a labelling range
+fn format_page_label(document: &Document, style: &Dictionary, offset: usize) -> String {
+    let prefix = style.get(b"P").ok()
+        .and_then(|o| deref(document, o))
+        .and_then(|o| lopdf::decode_text_string(o).ok())
+        .unwrap_or_default();
+    let first = style.get(b"St").ok().and_then(|o| o.as_i64().ok()).unwrap_or(1).max(1) as usize;
+    let number = first + offset;
+    
+    let numeral = match style.get(b"S").ok().and_then(|o| o.as_name().ok()) {
+        Some(b"D") => number.to_string(),
+        Some(b"R") => to_roman(number),
+        Some(b"r") => to_roman(number).to_lowercase(),
+        Some(b"A") => to_letters(number),
+        Some(b"a") => to_letters(number).to_lowercase(),
+        // No numbering style: the label is just the prefix
+        _ => String::new(),
+    };
+    format!("{}{}", prefix, numeral)
+}
+
+/// Upper-case Roman numeral
+fn to_roman(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+    roman
+}
+
+/// Upper-case letter label: A..Z, then AA..ZZ, AAA..ZZZ and so on
+fn to_letters(number: usize) -> String {
+    let index = number.saturating_sub(1);
+    let letter = (b'A' + (index % 26) as u8) as char;
+    std::iter::repeat_n(letter, index / 26 + 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+    
+    #[test]
+    fn test_to_unicode_cmap() {
+        let cmap = b"/CIDInit /ProcSet findresource begin
+            begincmap
+            1 begincodespacerange <0000> <FFFF> endcodespacerange
+            2 beginbfchar
+            <0003> <0020>
+            <0011> <00660069>
+            endbfchar
+            2 beginbfrange
+            <0024> <0026> <0041>
+            <0030> <0031> [<0078> <0079>]
+            endbfrange
+            endcmap";
+        let map = ToUnicodeMap::parse(cmap);
+        
+        // <0024><0025><0026> -> ABC, <0003> -> space, <0011> -> "fi" ligature text, [..] -> x, y
+        let shown = [0x00, 0x24, 0x00, 0x25, 0x00, 0x26, 0x00, 0x03, 0x00, 0x11, 0x00, 0x30, 0x00, 0x31];
+        assert_eq!(map.decode(&shown), "ABC fixy");
+    }
+    
+    #[test]
+    fn test_page_label_numerals() {
+        assert_eq!(to_roman(4), "IV");
+        assert_eq!(to_roman(1994), "MCMXCIV");
+        assert_eq!(to_letters(1), "A");
+        assert_eq!(to_letters(28), "BB");
+    }
+    
+    #[test]
+    fn test_extract_generated_pdf() {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        
+        let mut kids = Vec::new();
+        for content in [
+            "BT /F1 12 Tf 72 720 Td (Front) Tj 0 -14 Td (matter) Tj ET",
+            "BT /F1 12 Tf 72 720 Td [(Body) -250 (text)] TJ ET",
+        ] {
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+            kids.push(Object::Reference(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            })));
+        }
+        
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => 2,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        }));
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "PageLabels" => dictionary! {
+                "Nums" => vec![
+                    0.into(), Object::Dictionary(dictionary! { "S" => "r" }),
+                    1.into(), Object::Dictionary(dictionary! {
+                        "S" => "D",
+                        "P" => Object::string_literal("A-"),
+                        "St" => 3,
+                    }),
+                ],
+            },
+        });
+        doc.trailer.set("Root", catalog_id);
+        
+        let path = std::env::temp_dir().join(format!("lopdf_backend_test_{}.pdf", std::process::id()));
+        doc.save(&path).unwrap();
+        let pages = LopdfBackend::new().extract_pages(&path, &ProcessOptions::default());
+        // The content filter drops rejected pages during extraction
+        let filter = crate::page_selection::PageFilter {
+            skip_pattern: Some(regex::Regex::new("^Front").unwrap()),
+            ..Default::default()
+        };
+        let filtered = LopdfBackend::new().extract_pages(&path, &ProcessOptions { page_filter: filter, ..Default::default() });
+        std::fs::remove_file(&path).ok();
+        let pages = pages.unwrap();
+        
+        let filtered = filtered.unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].page_index, 1);
+        
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].text, "Front\nmatter");
+        assert_eq!(pages[0].page_label.as_deref(), Some("i"));
+        assert_eq!(pages[1].text, "Body text");
+        assert_eq!(pages[1].page_label.as_deref(), Some("A-3"));
+    }
+    
+    #[test]
+    fn test_glyph_positioned_text() {
+        // Each glyph placed with its own Tm, at 10pt and as a 1pt font scaled by the matrix
+        let widths: HashMap<u8, f32> = [(b' ', 278.0), (b'H', 722.0), (b'W', 944.0), (b'd', 556.0), (b'e', 556.0), (b'l', 222.0), (b'o', 556.0), (b'r', 333.0)]
+            .into_iter()
+            .collect();
+        let mut content = String::from("BT ");
+        for (y, size, scale) in [(700, 10, 1), (680, 1, 10)] {
+            content.push_str(&format!("/F1 {} Tf ", size));
+            let mut x = 72.0;
+            for byte in "Hello World".bytes() {
+                if byte != b' ' {
+                    content.push_str(&format!("{scale} 0 0 {scale} {x:.2} {y} Tm ({}) Tj ", byte as char));
+                }
+                x += widths[&byte] / 100.0;
+            }
+        }
+        content.push_str("ET");
+        
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "Arial",
+            "Encoding" => "WinAnsiEncoding",
+            "FirstChar" => 32,
+            "LastChar" => 122,
+            "Widths" => (32..=122u8).map(|c| Object::Real(widths.get(&c).copied().unwrap_or(500.0))).collect::<Vec<_>>(),
+        });
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        
+        let path = std::env::temp_dir().join(format!("lopdf_backend_glyphs_{}.pdf", std::process::id()));
+        doc.save(&path).unwrap();
+        let pages = LopdfBackend::new().extract_pages(&path, &ProcessOptions::default());
+        std::fs::remove_file(&path).ok();
+        
+        assert_eq!(pages.unwrap()[0].text, "Hello World\nHello World");
+    }
+}
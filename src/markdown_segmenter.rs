@@ -0,0 +1,232 @@
+use regex::Regex;
+use std::ops::Range;
+use log::debug;
+
+/// Kind of a top-level markdown block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    /// ATX (`## Title`) or setext (`Title` underlined with `===`/`---`) heading
+    Heading,
+    /// Fenced code block, including its ``` / ~~~ fences
+    CodeFence,
+    /// Bulleted or numbered list, including continuation lines and nested items
+    List,
+    /// Pipe table
+    Table,
+    /// Anything else (paragraphs, block quotes, HTML, ...)
+    Paragraph,
+}
+
+/// A top-level markdown block and its byte range in the source text
+/// (the range excludes the trailing line break)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownBlock {
+    pub kind: BlockKind,
+    pub range: Range<usize>,
+}
+
+/// Line-based markdown block scanner
+/// 
+/// Splits a markdown document into the blocks that make natural chunk units,
+/// so a code block, list or table is only broken up when it alone exceeds the
+/// chunk size. This recognizes the block structure only (no inline parsing):
+/// 1. Fenced code blocks run to their matching closing fence (or end of text)
+/// 2. ATX and setext headings are single-line blocks
+/// 3. Consecutive list items, their indented continuations and blank lines
+///    between items form one list
+/// 4. Consecutive pipe-table rows form one table
+/// 5. Remaining non-blank lines form paragraphs separated by blank lines
+pub struct MarkdownSegmenter {
+    fence_regex: Regex,
+    atx_heading_regex: Regex,
+    setext_underline_regex: Regex,
+    list_item_regex: Regex,
+    table_delimiter_regex: Regex,
+}
+
+impl MarkdownSegmenter {
+    pub fn new() -> Self {
+        Self {
+            fence_regex: Regex::new(r"^ {0,3}(`{3,}|~{3,})").expect("Invalid fence regex"),
+            atx_heading_regex: Regex::new(r"^ {0,3}#{1,6}(\s|$)").expect("Invalid heading regex"),
+            setext_underline_regex: Regex::new(r"^ {0,3}(=+|-+)\s*$").expect("Invalid setext regex"),
+            list_item_regex: Regex::new(r"^\s*([-*+]|\d{1,9}[.)])\s+\S").expect("Invalid list item regex"),
+            table_delimiter_regex: Regex::new(r"^\s*\|?\s*:?-+:?\s*(\|\s*:?-+:?\s*)+\|?\s*$")
+                .expect("Invalid table delimiter regex"),
+        }
+    }
+    
+    /// Split markdown text into top-level blocks in document order
+    pub fn blocks(&self, text: &str) -> Vec<MarkdownBlock> {
+        let mut blocks: Vec<MarkdownBlock> = Vec::new();
+        let mut current: Option<MarkdownBlock> = None;
+        let mut fence: Option<&str> = None; // Opening fence of the code block being read
+        let mut blank_in_list = false;
+        
+        let mut line_start = 0;
+        for raw_line in text.split_inclusive('\n') {
+            let line = raw_line.trim_end_matches(['\n', '\r']);
+            let line_range = line_start..line_start + line.len();
+            line_start += raw_line.len();
+            
+            // Inside a code fence everything up to the closing fence belongs to it
+            if let Some(opening) = fence {
+                let block = current.as_mut().expect("code fence without block");
+                block.range.end = line_range.end;
+                let trimmed = line.trim();
+                if trimmed.starts_with(opening) && trimmed.trim_start_matches(&opening[..1]).is_empty() {
+                    fence = None;
+                    blocks.extend(current.take());
+                }
+                continue;
+            }
+            
+            if line.trim().is_empty() {
+                // Blank lines end every block except a list that continues after them
+                match &current {
+                    Some(block) if block.kind == BlockKind::List => blank_in_list = true,
+                    _ => blocks.extend(current.take()),
+                }
+                continue;
+            }
+            
+            let kind = self.line_kind(line);
+            let continues_list = blank_in_list && (kind == BlockKind::List || line.starts_with([' ', '\t']));
+            if blank_in_list && !continues_list {
+                blocks.extend(current.take());
+            }
+            blank_in_list = false;
+            
+            match (current.as_mut(), kind) {
+                (_, BlockKind::CodeFence) => {
+                    blocks.extend(current.take());
+                    fence = self.fence_regex.captures(line).and_then(|caps| caps.get(1)).map(|m| m.as_str());
+                    current = Some(MarkdownBlock { kind, range: line_range });
+                }
+                (_, BlockKind::Heading) => {
+                    blocks.extend(current.take());
+                    blocks.push(MarkdownBlock { kind, range: line_range });
+                }
+                // A setext underline turns the paragraph above it into a heading
+                (Some(block), _) if block.kind == BlockKind::Paragraph && self.setext_underline_regex.is_match(line) => {
+                    block.kind = BlockKind::Heading;
+                    block.range.end = line_range.end;
+                    blocks.extend(current.take());
+                }
+                // A delimiter row turns a one-line paragraph into a table header
+                (Some(block), _)
+                    if block.kind == BlockKind::Paragraph
+                        && !text[block.range.clone()].contains('\n')
+                        && self.table_delimiter_regex.is_match(line) =>
+                {
+                    block.kind = BlockKind::Table;
+                    block.range.end = line_range.end;
+                }
+                (Some(block), BlockKind::Table) if block.kind == BlockKind::Table => block.range.end = line_range.end,
+                // List items, indented continuations and lazy paragraph lines stay in the list
+                (Some(block), BlockKind::List | BlockKind::Paragraph) if block.kind == BlockKind::List => {
+                    block.range.end = line_range.end;
+                }
+                (Some(block), BlockKind::Paragraph) if block.kind == BlockKind::Paragraph => {
+                    block.range.end = line_range.end;
+                }
+                _ => {
+                    blocks.extend(current.take());
+                    current = Some(MarkdownBlock { kind, range: line_range });
+                }
+            }
+        }
+        blocks.extend(current);
+        
+        debug!("Markdown text split into {} blocks", blocks.len());
+        blocks
+    }
+    
+    /// Escape block markup at the start of each line of plain (non-markdown) text
+    /// 
+    /// Paragraphs converted from other formats can start with `#`, `- `, `1. `,
+    /// `|` or a fence by accident; a backslash keeps [`blocks`](Self::blocks)
+    /// from reading them as headings, lists, tables or code blocks.
+    pub fn escape_paragraph(&self, text: &str) -> String {
+        text.split('\n')
+            .map(|line| {
+                let is_markup = self.line_kind(line) != BlockKind::Paragraph
+                    || self.setext_underline_regex.is_match(line)
+                    || self.table_delimiter_regex.is_match(line);
+                if !is_markup {
+                    return line.to_string();
+                }
+                // `\1` is no escape, so ordered list markers escape their `.` or `)`
+                let start = line.len() - line.trim_start().len();
+                let at = start + line[start..].bytes().take_while(u8::is_ascii_digit).count();
+                format!("{}\\{}", &line[..at], &line[at..])
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    
+    /// Block kind a non-blank line would start
+    fn line_kind(&self, line: &str) -> BlockKind {
+        if self.fence_regex.is_match(line) {
+            BlockKind::CodeFence
+        } else if self.atx_heading_regex.is_match(line) {
+            BlockKind::Heading
+        } else if line.trim_start().starts_with('|') {
+            BlockKind::Table
+        } else if self.list_item_regex.is_match(line) {
+            BlockKind::List
+        } else {
+            BlockKind::Paragraph
+        }
+    }
+}
+
+/// Level (1-6) and title of a heading block (ATX or setext)
+pub fn heading_title(block: &str) -> (usize, String) {
+    let trimmed = block.trim();
+    if trimmed.starts_with('#') {
+        let level = trimmed.chars().take_while(|&c| c == '#').count().min(6);
+        let title = trimmed.trim_start_matches('#').trim_end().trim_end_matches('#').trim();
+        return (level, title.to_string());
+    }
+    
+    // Setext: title lines followed by an `===` (level 1) or `---` (level 2) underline
+    let (title, underline) = trimmed.rsplit_once('\n').unwrap_or((trimmed, ""));
+    let level = if underline.trim_start().starts_with('=') { 1 } else { 2 };
+    (level, title.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_markdown_blocks() {
+        let text = "# Title\n\nIntro line one\nline two\n\n```rust\nfn main() {\n\n    println!(\"hi\");\n}\n```\n\n\
+                    - item one\n  continued\n\n- item two\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\nSetext\n------\nTail";
+        let blocks = MarkdownSegmenter::new().blocks(text);
+        let summary: Vec<(BlockKind, &str)> = blocks.iter().map(|b| (b.kind, &text[b.range.clone()])).collect();
+        
+        assert_eq!(summary, vec![
+            (BlockKind::Heading, "# Title"),
+            (BlockKind::Paragraph, "Intro line one\nline two"),
+            (BlockKind::CodeFence, "```rust\nfn main() {\n\n    println!(\"hi\");\n}\n```"),
+            (BlockKind::List, "- item one\n  continued\n\n- item two"),
+            (BlockKind::Table, "| a | b |\n|---|---|\n| 1 | 2 |"),
+            (BlockKind::Heading, "Setext\n------"),
+            (BlockKind::Paragraph, "Tail"),
+        ]);
+        
+        assert_eq!(heading_title("## Install ##"), (2, "Install".to_string()));
+        assert_eq!(heading_title("Setext\n------"), (2, "Setext".to_string()));
+        assert_eq!(heading_title("Top\n==="), (1, "Top".to_string()));
+        
+        // Escaped plain text stays one paragraph
+        let segmenter = MarkdownSegmenter::new();
+        let escaped = segmenter.escape_paragraph("# one\n- two\n1. three\n| four\n```\n---\n#hashtag");
+        assert_eq!(escaped, "\\# one\n\\- two\n1\\. three\n\\| four\n\\```\n\\---\n#hashtag");
+        let blocks = segmenter.blocks(&escaped);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].kind, BlockKind::Paragraph);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::ProcessingError;
+use crate::text_extractor::PageText;
+use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Set of 1-based page numbers to process, parsed from a spec like `"1-10,45,100-"`
+/// 
+/// Syntax:
+/// - `N`     single page
+/// - `N-M`   inclusive range
+/// - `N-`    from page N to the end of the document
+/// - `-M`    from the first page to page M
+/// 
+/// Pages past the end of the document are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSelection {
+    ranges: Vec<(usize, Option<usize>)>, // Inclusive 1-based (start, end); None = open-ended
+}
+
+impl PageSelection {
+    /// Whether a 1-based page number is selected
+    pub fn contains(&self, page_number: usize) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| page_number >= start && end.is_none_or(|end| page_number <= end))
+    }
+    
+    /// Selected 0-based page indices of a document with `page_count` pages, in page order
+    pub fn indices(&self, page_count: usize) -> Vec<usize> {
+        (0..page_count).filter(|idx| self.contains(idx + 1)).collect()
+    }
+}
+
+impl FromStr for PageSelection {
+    type Err = ProcessingError;
+    
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = |part: &str, reason: &str| {
+            ProcessingError::InvalidArgument(format!("Invalid page selection '{}' in '{}': {}", part, spec, reason))
+        };
+        let page_number = |part: &str, value: &str| -> Result<usize, ProcessingError> {
+            match value.trim().parse::<usize>() {
+                Ok(0) => Err(invalid(part, "page numbers start at 1")),
+                Ok(n) => Ok(n),
+                Err(_) => Err(invalid(part, "expected a page number")),
+            }
+        };
+        
+        let mut ranges = Vec::new();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let range = match part.split_once('-') {
+                None => {
+                    let page = page_number(part, part)?;
+                    (page, Some(page))
+                }
+                Some((start, end)) => {
+                    let start = if start.trim().is_empty() { 1 } else { page_number(part, start)? };
+                    let end = if end.trim().is_empty() { None } else { Some(page_number(part, end)?) };
+                    if end.is_some_and(|end| end < start) {
+                        return Err(invalid(part, "range end is before its start"));
+                    }
+                    (start, end)
+                }
+            };
+            ranges.push(range);
+        }
+        
+        if ranges.is_empty() {
+            return Err(ProcessingError::InvalidArgument(format!("Empty page selection '{}'", spec)));
+        }
+        Ok(PageSelection { ranges })
+    }
+}
+
+/// Caller-supplied page predicate: return `true` to keep the page
+pub type PagePredicate = Arc<dyn Fn(&PageText) -> bool + Send + Sync>;
+
+/// Content-based page filter, applied to each page as it is extracted
+/// 
+/// A page is skipped when any of these hold:
+/// 1. It has fewer than `min_chars` non-whitespace characters (blank or near-blank pages)
+/// 2. Its text matches `skip_pattern`
+/// 3. `predicate` returns `false` for it
+#[derive(Clone, Default)]
+pub struct PageFilter {
+    pub min_chars: usize,
+    pub skip_pattern: Option<Regex>,
+    pub predicate: Option<PagePredicate>,
+}
+
+impl PageFilter {
+    /// Whether a page should be chunked
+    pub fn keep(&self, page: &PageText) -> bool {
+        if self.min_chars > 0 {
+            let chars = page.text.chars().filter(|c| !c.is_whitespace()).take(self.min_chars).count();
+            if chars < self.min_chars {
+                return false;
+            }
+        }
+        if self.skip_pattern.as_ref().is_some_and(|pattern| pattern.is_match(&page.text)) {
+            return false;
+        }
+        self.predicate.as_ref().is_none_or(|predicate| predicate(page))
+    }
+}
+
+impl fmt::Debug for PageFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageFilter")
+            .field("min_chars", &self.min_chars)
+            .field("skip_pattern", &self.skip_pattern)
+            .field("predicate", &self.predicate.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_page_selection_parsing() {
+        let selection: PageSelection = "1-3, 5,8-".parse().unwrap();
+        assert_eq!(selection.indices(10), vec![0, 1, 2, 4, 7, 8, 9]);
+        
+        let selection: PageSelection = "-2".parse().unwrap();
+        assert_eq!(selection.indices(10), vec![0, 1]);
+        
+        // Pages beyond the document are ignored
+        let selection: PageSelection = "4-100".parse().unwrap();
+        assert_eq!(selection.indices(5), vec![3, 4]);
+        
+        assert!("0".parse::<PageSelection>().is_err());
+        assert!("5-2".parse::<PageSelection>().is_err());
+        assert!("abc".parse::<PageSelection>().is_err());
+        assert!(" , ".parse::<PageSelection>().is_err());
+    }
+    
+    #[test]
+    fn test_page_filter() {
+        let page = |page_index: usize, text: &str| PageText {
+            page_index,
+            page_label: None,
+            text: text.to_string(),
+            char_boxes: Vec::new(),
+        };
+        
+        let filter = PageFilter {
+            min_chars: 5,
+            skip_pattern: Some(Regex::new(r"(?i)table of contents").unwrap()),
+            predicate: Some(Arc::new(|page: &PageText| page.page_index != 0)),
+        };
+        
+        assert!(!filter.keep(&page(0, "Cover page of the report")));
+        assert!(!filter.keep(&page(1, "- 2 -")));
+        assert!(!filter.keep(&page(2, "Table of Contents ....... 1")));
+        assert!(filter.keep(&page(3, "Actual body text")));
+    }
+}
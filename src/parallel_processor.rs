@@ -1,7 +1,6 @@
 use crate::error::ProcessingError;
-use crate::text_extractor::TextExtractor;
+use crate::text_extractor::PageText;
 use crate::chunking::{ChunkMetadata, TextChunker};
-use pdfium_render::prelude::*;
 use rayon::prelude::*;
 use std::sync::Arc;
 use log::{info, debug, error};
@@ -63,59 +62,26 @@ impl ParallelProcessor {
         })
     }
     
-    /// Process all PDF pages in parallel batches
+    /// Process all extracted PDF pages in parallel batches
     /// 
     /// Processing Strategy:
-    /// 1. Pre-extract text from all pages sequentially (pdfium limitation)
+    /// 1. Take page text pre-extracted by the extraction backend
     /// 2. Process text chunks in parallel using Rayon
     /// 3. Collect and merge results maintaining page order
     /// 4. Handle errors gracefully with detailed reporting
-    pub async fn process_pages_parallel<'a>(
+    pub fn process_pages_parallel(
         &self,
-        document: &PdfDocument<'a>,
+        page_texts: &[PageText],
         source_filename: &str,
-        text_extractor: &TextExtractor,
         text_chunker: &TextChunker,
     ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
-        let page_count = document.pages().len() as usize;
         let source = source_filename.to_string();
         
         info!("Starting parallel processing: {} pages in batches of {}", 
-              page_count, self.batch_size);
-        
-        // Step 1: Extract text from all pages sequentially (pdfium is not thread-safe)
-        let mut page_texts: Vec<(usize, String)> = Vec::with_capacity(page_count);
-        
-        for page_idx in 0..page_count {
-            let page_idx_u16 = page_idx as u16;
-            
-            match document.pages().get(page_idx_u16) {
-                Ok(page) => {
-                    match text_extractor.extract_page_text(&page, page_idx) {
-                        Ok(text) => {
-                            if !text.trim().is_empty() {
-                                page_texts.push((page_idx, text));
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to extract text from page {}: {}", page_idx, e);
-                            continue;
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to get page {}: {}", page_idx, e);
-                    continue;
-                }
-            }
-        }
+              page_texts.len(), self.batch_size);
         
-        info!("Text extraction complete: {} pages with content", page_texts.len());
-        
-        // Step 2: Process extracted text in parallel (thread-safe)
+        // Step 1: Process extracted text in parallel (thread-safe)
         let source_arc = Arc::new(source);
-        let text_extractor_arc = Arc::new(text_extractor);
-        let text_chunker_arc = Arc::new(text_chunker);
         
         let batch_results: Result<Vec<Vec<ChunkMetadata>>, ProcessingError> = 
             page_texts
@@ -128,13 +94,12 @@ impl ParallelProcessor {
                     self.process_text_batch(
                         batch_pages, 
                         &source_arc, 
-                        &text_extractor_arc, 
-                        &text_chunker_arc
+                        text_chunker
                     )
                 })
                 .collect();
         
-        // Step 3: Flatten batch results and maintain page order
+        // Step 2: Flatten batch results and maintain page order
         let batch_results = batch_results?;
         let mut all_chunks: Vec<ChunkMetadata> = batch_results
             .into_iter()
@@ -160,23 +125,22 @@ impl ParallelProcessor {
     /// - Memory management for large documents
     fn process_text_batch(
         &self,
-        page_texts: &[(usize, String)], // (page_index, text)
+        page_texts: &[PageText],
         source: &Arc<String>,
-        text_extractor: &Arc<&TextExtractor>,
-        text_chunker: &Arc<&TextChunker>,
+        text_chunker: &TextChunker,
     ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
         debug!("Processing text batch: {} pages", page_texts.len());
         
         let mut batch_chunks = Vec::new();
         
         // Process each page's text in the batch
-        for (page_idx, text) in page_texts {
-            match self.process_single_page_text(*page_idx, text, source, text_extractor, text_chunker) {
+        for page_text in page_texts {
+            match self.process_single_page_text(page_text, source, text_chunker) {
                 Ok(mut page_chunks) => {
                     batch_chunks.append(&mut page_chunks);
                 }
                 Err(e) => {
-                    error!("Failed to process page {} text: {}", page_idx, e);
+                    error!("Failed to process page {} text: {}", page_text.page_index, e);
                     // Continue processing other pages rather than failing the entire batch
                     continue;
                 }
@@ -192,25 +156,32 @@ impl ParallelProcessor {
     /// Token-based processing pipeline:
     /// 1. Apply tiktoken o200k_base tokenization for accurate GPT-4 token counting
     /// 2. Apply chunking logic (300/60 token rules)
-    /// 3. Generate chunk metadata with token counts
+    /// 3. Generate chunk metadata with token counts, the logical page label and
+    ///    line bounding boxes for citation highlighting
     /// 4. Handle edge cases (empty text, processing errors)
     fn process_single_page_text(
         &self,
-        page_idx: usize,
-        text: &str,
+        page_text: &PageText,
         source: &Arc<String>,
-        _text_extractor: &Arc<&TextExtractor>,
-        text_chunker: &Arc<&TextChunker>,
+        text_chunker: &TextChunker,
     ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        let page_idx = page_text.page_index;
         debug!("Processing page {} text with token-based chunking", page_idx);
         
         // Apply token-based chunking logic using tiktoken
-        let chunks = text_chunker.chunk_page_text(
+        let mut chunks = text_chunker.chunk_page_text(
             page_idx + 1, // Convert to 1-based page numbers for user output
-            text,
+            &page_text.text,
             source,
         )?;
         
+        // Attach the printed page label so citations match the document, and the
+        // line rectangles covering each chunk's span of the page text
+        for chunk in &mut chunks {
+            chunk.page_label = page_text.page_label.clone();
+            chunk.bboxes = page_text.line_boxes(chunk.start_offset..chunk.end_offset);
+        }
+        
         debug!("Page {} generated {} token-based chunks", page_idx, chunks.len());
         Ok(chunks)
     }
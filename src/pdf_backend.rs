@@ -0,0 +1,256 @@
+use crate::error::ProcessingError;
+use crate::pdf_processor::ProcessOptions;
+use crate::text_extractor::{PageText, TextExtractor};
+use pdfium_render::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::{info, warn, error};
+
+/// Which extraction backend to use for reading PDFs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    /// Use pdfium when its shared library can be loaded, otherwise fall back to lopdf
+    #[default]
+    Auto,
+    /// Native pdfium library (best text quality, character bounding boxes)
+    Pdfium,
+    /// Pure-Rust lopdf parser (no native dependency, no bounding boxes)
+    Lopdf,
+}
+
+impl FromStr for BackendKind {
+    type Err = ProcessingError;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" => Ok(BackendKind::Auto),
+            "pdfium" => Ok(BackendKind::Pdfium),
+            "lopdf" => Ok(BackendKind::Lopdf),
+            other => Err(ProcessingError::InvalidArgument(format!(
+                "Unknown extraction backend '{}' (expected 'auto', 'pdfium' or 'lopdf')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Text extraction backend for PDF documents
+/// 
+/// A backend opens a document (handling passwords and permissions according to
+/// `ProcessOptions`) and returns the cleaned text of every page that has any.
+/// Chunking happens afterwards and is backend independent. Backends are shared
+/// by the worker threads of directory processing.
+pub trait PdfBackend: Send + Sync {
+    /// Short backend name for logging
+    fn name(&self) -> &'static str;
+    
+    /// Open the document at `path` and extract the text of its pages in page order
+    fn extract_pages(&self, path: &Path, options: &ProcessOptions) -> Result<Vec<PageText>, ProcessingError>;
+}
+
+/// Extraction backend using the native pdfium library
+pub struct PdfiumBackend {
+    pdfium: Pdfium,
+    text_extractor: TextExtractor,
+}
+
+/// Environment variable naming the pdfium shared library (or its directory)
+pub const PDFIUM_LIBRARY_PATH_ENV: &str = "PDFIUM_LIBRARY_PATH";
+
+/// A place the pdfium shared library may be loaded from
+#[derive(Debug, Clone, PartialEq)]
+enum LibraryLocation {
+    /// Library file at an explicit path (description says where the path came from)
+    Path { description: &'static str, path: PathBuf },
+    /// Platform library search (`LD_LIBRARY_PATH`, system directories, ...)
+    System,
+}
+
+impl PdfiumBackend {
+    /// Bind to the pdfium shared library
+    /// 
+    /// Search order (first library that loads wins):
+    /// 1. `pdfium_path` argument
+    /// 2. `PDFIUM_LIBRARY_PATH` environment variable
+    /// 3. The current working directory
+    /// 4. The system library search path
+    /// 
+    /// Paths may name the library file itself or a directory containing the
+    /// platform library (`libpdfium.so`, `libpdfium.dylib`, `pdfium.dll`).
+    pub fn new(pdfium_path: Option<&Path>) -> Result<Self, ProcessingError> {
+        let env_path = std::env::var_os(PDFIUM_LIBRARY_PATH_ENV).map(PathBuf::from);
+        let mut failures = Vec::new();
+        
+        for location in library_locations(pdfium_path, env_path.as_deref()) {
+            let bindings = match &location {
+                LibraryLocation::Path { path, .. } => Pdfium::bind_to_library(path),
+                LibraryLocation::System => Pdfium::bind_to_system_library(),
+            };
+            
+            match bindings {
+                Ok(bindings) => {
+                    info!("Loaded pdfium from {}", location);
+                    return Ok(PdfiumBackend {
+                        pdfium: Pdfium::new(bindings),
+                        text_extractor: TextExtractor::new(),
+                    });
+                }
+                Err(e) => {
+                    info!("Failed to load pdfium from {}: {}", location, e);
+                    failures.push(format!("  - {}: {}", location, e));
+                }
+            }
+        }
+        
+        Err(ProcessingError::SystemError(format!(
+            "Failed to initialize pdfium. Tried:\n{}\nPass pdfium_path or set {} to the pdfium library, \
+             or use the lopdf backend",
+            failures.join("\n"),
+            PDFIUM_LIBRARY_PATH_ENV
+        )))
+    }
+}
+
+impl std::fmt::Display for LibraryLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibraryLocation::Path { description, path } => write!(f, "{} ({})", description, path.display()),
+            LibraryLocation::System => write!(f, "system library search path"),
+        }
+    }
+}
+
+/// Candidate library locations in search order
+fn library_locations(pdfium_path: Option<&Path>, env_path: Option<&Path>) -> Vec<LibraryLocation> {
+    // A directory means "the platform library inside it"
+    let library_file = |path: &Path| {
+        if path.is_dir() {
+            Pdfium::pdfium_platform_library_name_at_path(path)
+        } else {
+            path.to_path_buf()
+        }
+    };
+    
+    let mut locations = Vec::new();
+    if let Some(path) = pdfium_path {
+        locations.push(LibraryLocation::Path { description: "pdfium_path argument", path: library_file(path) });
+    }
+    if let Some(path) = env_path.filter(|p| !p.as_os_str().is_empty()) {
+        locations.push(LibraryLocation::Path { description: PDFIUM_LIBRARY_PATH_ENV, path: library_file(path) });
+    }
+    locations.push(LibraryLocation::Path {
+        description: "current directory",
+        path: Pdfium::pdfium_platform_library_name_at_path("./"),
+    });
+    locations.push(LibraryLocation::System);
+    locations
+}
+
+impl PdfBackend for PdfiumBackend {
+    fn name(&self) -> &'static str {
+        "pdfium"
+    }
+    
+    /// Extract text from all pages sequentially (pdfium is not thread-safe)
+    fn extract_pages(&self, path: &Path, options: &ProcessOptions) -> Result<Vec<PageText>, ProcessingError> {
+        let pdf_path = path.display().to_string();
+        
+        // Load PDF document using pdfium (an empty password is treated as no password)
+        let password = options.password.as_deref().filter(|p| !p.is_empty());
+        let document = self.pdfium
+            .load_pdf_from_file(path, password)
+            .map_err(|e| match e {
+                // pdfium reports a missing and a wrong password with the same error code
+                PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => {
+                    if password.is_some() {
+                        ProcessingError::IncorrectPassword(pdf_path.clone())
+                    } else {
+                        ProcessingError::PasswordRequired(pdf_path.clone())
+                    }
+                }
+                e => ProcessingError::PdfLoadError(format!("Failed to load {}: {}", pdf_path, e)),
+            })?;
+        
+        // Owner-password restrictions do not stop pdfium from reading text
+        let can_extract = document.permissions().can_extract_text_and_graphics().unwrap_or(true);
+        check_extraction_permission(can_extract, &pdf_path, options)?;
+        
+        let page_count = document.pages().len() as usize;
+        info!("PDF loaded successfully. Pages: {}", page_count);
+        
+        // Only selected pages are loaded at all
+        let page_indices = options.page_indices(page_count);
+        let mut page_texts: Vec<PageText> = Vec::with_capacity(page_indices.len());
+        
+        for page_idx in page_indices {
+            let page_idx_u16 = page_idx as u16;
+            
+            match document.pages().get(page_idx_u16) {
+                Ok(page) => {
+                    match self.text_extractor.extract_page_text(&page, page_idx) {
+                        Ok(page_text) => {
+                            if !page_text.text.trim().is_empty() && options.keep_page(&page_text) {
+                                page_texts.push(page_text);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to extract text from page {}: {}", page_idx, e);
+                            continue;
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to get page {}: {}", page_idx, e);
+                    continue;
+                }
+            }
+        }
+        
+        Ok(page_texts)
+    }
+}
+
+/// Enforce (or just report) an owner-password restriction on text extraction
+pub fn check_extraction_permission(
+    can_extract: bool,
+    pdf_path: &str,
+    options: &ProcessOptions,
+) -> Result<(), ProcessingError> {
+    if !can_extract {
+        if options.respect_permissions {
+            return Err(ProcessingError::ExtractionNotPermitted(pdf_path.to_string()));
+        }
+        warn!("{} restricts text extraction via owner password; processing anyway", pdf_path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_search_order() {
+        let locations = library_locations(Some(Path::new("/opt/pdfium/libpdfium.so")), Some(Path::new("/env/libpdfium.so")));
+        
+        assert_eq!(locations.len(), 4);
+        assert_eq!(locations[0], LibraryLocation::Path {
+            description: "pdfium_path argument",
+            path: PathBuf::from("/opt/pdfium/libpdfium.so"),
+        });
+        assert_eq!(locations[1], LibraryLocation::Path {
+            description: PDFIUM_LIBRARY_PATH_ENV,
+            path: PathBuf::from("/env/libpdfium.so"),
+        });
+        assert_eq!(locations[3], LibraryLocation::System);
+        
+        // Directories resolve to the platform library file inside them
+        let dir = std::env::temp_dir();
+        let locations = library_locations(Some(&dir), None);
+        assert_eq!(locations[0], LibraryLocation::Path {
+            description: "pdfium_path argument",
+            path: Pdfium::pdfium_platform_library_name_at_path(&dir),
+        });
+        assert_eq!(locations.len(), 3);
+    }
+}
@@ -1,48 +1,157 @@
 use crate::error::ProcessingError;
 use crate::parallel_processor::ParallelProcessor;
-use crate::text_extractor::TextExtractor;
 use crate::chunking::{ChunkMetadata, TextChunker};
-use pdfium_render::prelude::*;
+use crate::pdf_backend::{BackendKind, PdfBackend, PdfiumBackend};
+use crate::lopdf_backend::LopdfBackend;
+use crate::docx_reader::DocxReader;
+use crate::epub_reader::EpubReader;
+use crate::code_segmenter::CodeLanguage;
+use crate::encodings::EncodingKind;
+use crate::tiktoken_core::CoreBPE;
+use crate::chunk_ids::{assign_chunk_ids, document_fingerprint};
+use crate::directory_walker::{list_files, FileRules, SkippedFile};
+use crate::html_reader::HtmlReader;
+use crate::page_selection::{PageFilter, PageSelection};
+use crate::text_extractor::PageText;
+use crate::text_preprocessor::{dehyphenate_page_breaks, NormalizationOptions};
+use rayon::prelude::*;
 use std::path::Path;
-use log::{info, warn};
+use std::sync::Arc;
+use log::{debug, info, warn};
+
+/// Per-document options for opening and processing a PDF
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    /// User or owner password for encrypted PDFs
+    pub password: Option<String>,
+    /// Refuse documents whose permissions forbid text extraction
+    /// (by default owner-password restrictions are reported and ignored)
+    pub respect_permissions: bool,
+    /// Pages to process; unselected pages are never extracted (None = all pages)
+    pub pages: Option<PageSelection>,
+    /// Content filter deciding which extracted pages get chunked (backends apply it
+    /// to each page as soon as its text is extracted, see [`keep_page`](Self::keep_page))
+    pub page_filter: PageFilter,
+}
+
+impl ProcessOptions {
+    /// 0-based indices of the pages to extract from a document with `page_count` pages
+    pub fn page_indices(&self, page_count: usize) -> Vec<usize> {
+        match &self.pages {
+            Some(selection) => selection.indices(page_count),
+            None => (0..page_count).collect(),
+        }
+    }
+    
+    /// Whether an extracted page passes the content filter
+    /// 
+    /// Backends call this right after extracting each page, so the text of a
+    /// rejected page is dropped before the next page is read.
+    pub fn keep_page(&self, page: &PageText) -> bool {
+        let keep = self.page_filter.keep(page);
+        if !keep {
+            debug!("Page {} skipped by the page filter", page.page_index + 1);
+        }
+        keep
+    }
+}
+
+/// Input formats understood by [`PdfProcessor::process_file`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Pdf,
+    /// Plain text (`.txt`), chunked like extracted PDF text
+    Text,
+    /// Markdown (`.md`, `.markdown`), chunked along its block structure
+    Markdown,
+    /// Word document (`.docx`), converted to markdown and chunked like it
+    Docx,
+    /// Web page (`.html`, `.htm`), boilerplate stripped and converted to markdown
+    Html,
+    /// E-book (`.epub`), chunked chapter by chapter in spine order
+    Epub,
+    /// Source code, chunked along top-level units with line ranges
+    Code(CodeLanguage),
+}
+
+impl FileKind {
+    /// Detect the format from the file extension (case-insensitive)
+    pub fn from_path(path: &Path) -> Result<Self, ProcessingError> {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        match extension.as_str() {
+            "pdf" => Ok(FileKind::Pdf),
+            "txt" | "text" => Ok(FileKind::Text),
+            "md" | "markdown" => Ok(FileKind::Markdown),
+            "docx" => Ok(FileKind::Docx),
+            "html" | "htm" | "xhtml" => Ok(FileKind::Html),
+            "epub" => Ok(FileKind::Epub),
+            extension => CodeLanguage::from_extension(extension).map(FileKind::Code).ok_or_else(|| {
+                ProcessingError::InvalidArgument(format!(
+                    "Unsupported file type '{}' (expected .pdf, .txt, .md, .docx, .html, .epub or source code)",
+                    path.display()
+                ))
+            }),
+        }
+    }
+}
+
+/// Outcome of [`PdfProcessor::process_directory`], in file name order
+#[derive(Debug, Clone, Default)]
+pub struct DirectorySummary {
+    /// Relative path and chunk count of every file processed successfully
+    pub processed: Vec<(String, usize)>,
+    /// Included files that were excluded or have an unsupported type
+    pub skipped: Vec<SkippedFile>,
+    /// Relative path and error message of files (or directories) that failed
+    pub failed: Vec<(String, String)>,
+}
 
 /// Main PDF processor that orchestrates the entire pipeline
 pub struct PdfProcessor {
     parallel_processor: ParallelProcessor,
-    text_extractor: TextExtractor,
     text_chunker: TextChunker,
-    pdfium: Pdfium,
+    backend: Box<dyn PdfBackend>,
+    dehyphenate: bool, // Rejoin words hyphenated across page breaks before chunking
 }
 
 impl PdfProcessor {
     /// Initialize the PDF processor with dynamic system configuration
-    pub async fn new() -> Result<Self, ProcessingError> {
+    /// 
+    /// Backend selection:
+    /// - `Pdfium`: bind the native library or fail
+    /// - `Lopdf`: pure-Rust extraction, pdfium is never loaded
+    /// - `Auto`: pdfium when it can be bound, otherwise lopdf
+    /// 
+    /// `pdfium_path` overrides where the pdfium library is looked for first
+    /// (see `PdfiumBackend::new` for the full search order).
+    /// 
+    /// `normalization` controls the Unicode and typographic cleanup applied to
+    /// page text before it is segmented.
+    /// 
+    /// `encoding` is the tokenizer chunk sizes are measured in.
+    pub async fn new(
+        backend_kind: BackendKind,
+        pdfium_path: Option<&Path>,
+        normalization: NormalizationOptions,
+        encoding: EncodingKind,
+    ) -> Result<Self, ProcessingError> {
         info!("Initializing PDF processor...");
         
-        // Try multiple paths for pdfium library
-        // Try to load pdfium from multiple paths
-        let pdfium = Pdfium::new(
-            Pdfium::bind_to_library(
-                Pdfium::pdfium_platform_library_name_at_path("../")
-            )
-            .or_else(|_| {
-                info!("Failed to load pdfium from '../', trying current directory");
-                Pdfium::bind_to_library(
-                    Pdfium::pdfium_platform_library_name_at_path("./")
-                )
-            })
-            .or_else(|_| {
-                info!("Failed to load pdfium from './', trying absolute path");
-                Pdfium::bind_to_library(
-                    Pdfium::pdfium_platform_library_name_at_path("C:/Users/harsh/Desktop/HackCentral/rustmethods/")
-                )
-            })
-            .or_else(|_| {
-                info!("Failed to load pdfium from absolute path, trying system library");
-                Pdfium::bind_to_system_library()
-            })
-            .map_err(|e| ProcessingError::SystemError(format!("Failed to initialize pdfium: {}", e)))?
-        );
+        let backend: Box<dyn PdfBackend> = match backend_kind {
+            BackendKind::Pdfium => Box::new(PdfiumBackend::new(pdfium_path)?),
+            BackendKind::Lopdf => Box::new(LopdfBackend::new()),
+            BackendKind::Auto => match PdfiumBackend::new(pdfium_path) {
+                Ok(pdfium) => Box::new(pdfium),
+                Err(e) => {
+                    warn!("{}; falling back to the lopdf backend", e);
+                    Box::new(LopdfBackend::new())
+                }
+            },
+        };
+        info!("Using {} extraction backend", backend.name());
         
         // Detect system capabilities for optimal parallel processing
         let logical_cores = num_cpus::get();
@@ -50,19 +159,39 @@ impl PdfProcessor {
         
         // Initialize components with system-aware configuration
         let parallel_processor = ParallelProcessor::new(logical_cores).await?;
-        let text_extractor = TextExtractor::new();
-        let text_chunker = TextChunker::new(256, 16)?; // 256 tokens per chunk, 16 token overlap
+        let dehyphenate = normalization.dehyphenate;
+        let text_chunker = TextChunker::new(256, 16, encoding)? // 256 tokens per chunk, 16 token overlap
+            .with_normalization(normalization);
         
         Ok(PdfProcessor {
             parallel_processor,
-            text_extractor,
             text_chunker,
-            pdfium,
+            backend,
+            dehyphenate,
         })
     }
     
+    /// Measure chunk sizes with `tokenizer` instead of the built-in vocabulary of the encoding
+    pub fn with_tokenizer(mut self, tokenizer: Arc<CoreBPE>) -> Self {
+        self.text_chunker = self.text_chunker.with_tokenizer(tokenizer);
+        self
+    }
+    
     /// Process a PDF file and return chunk metadata
-    pub async fn process_pdf(&self, pdf_path: &str) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+    /// 
+    /// Encrypted documents are opened with `options.password`. Documents that only
+    /// carry an owner password (print/copy restrictions) open without one; their
+    /// extraction restriction is enforced only when `options.respect_permissions` is set.
+    pub async fn process_pdf(
+        &self,
+        pdf_path: &str,
+        options: &ProcessOptions,
+    ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        self.chunk_pdf(pdf_path, options)
+    }
+    
+    /// Synchronous body of [`process_pdf`](Self::process_pdf)
+    fn chunk_pdf(&self, pdf_path: &str, options: &ProcessOptions) -> Result<Vec<ChunkMetadata>, ProcessingError> {
         let path = Path::new(pdf_path);
         let filename = path.file_name()
             .and_then(|n| n.to_str())
@@ -71,25 +200,188 @@ impl PdfProcessor {
         
         info!("Processing PDF: {}", pdf_path);
         
-        // Load PDF document using pdfium
-        let document = self.pdfium
-            .load_pdf_from_file(path, None)
-            .map_err(|e| ProcessingError::PdfLoadError(format!("Failed to load {}: {}", pdf_path, e)))?;
-        
-        let page_count = document.pages().len();
-        info!("PDF loaded successfully. Pages: {}", page_count);
+        // Step 1: Load the document and extract text of the selected pages with the backend,
+        // which drops pages rejected by the content filter as it goes
+        let mut page_texts = self.backend.extract_pages(path, options)?;
         
-        if page_count == 0 {
-            warn!("PDF contains no pages");
+        if page_texts.is_empty() {
+            warn!("PDF contains no pages with text");
             return Ok(vec![]);
         }
         
-        // Process pages in parallel batches
-        let all_chunks = self.parallel_processor
-            .process_pages_parallel(&document, &filename, &self.text_extractor, &self.text_chunker)
-            .await?;
+        info!("Text extraction complete: {} pages with content", page_texts.len());
+        
+        // Step 2: Document-level cleanup that needs neighbouring pages; within-page
+        // line-end hyphenation is handled by the chunker's preprocessor
+        if self.dehyphenate {
+            dehyphenate_page_breaks(&mut page_texts);
+        }
+        
+        // Step 3: Chunk pages in parallel batches
+        let mut all_chunks = self.parallel_processor
+            .process_pages_parallel(&page_texts, &filename, &self.text_chunker)?;
+        
+        // Step 4: Stable content IDs, so re-processing an edited document only changes edited chunks
+        assign_chunk_ids(&mut all_chunks, &document_fingerprint(path)?);
         
         info!("Processing complete. Generated {} total chunks", all_chunks.len());
         Ok(all_chunks)
     }
-}
\ No newline at end of file
+    
+    /// Process any supported file, dispatching on its extension (see [`FileKind`])
+    /// 
+    /// PDFs go through [`process_pdf`](Self::process_pdf) with `options`. Other
+    /// formats have no pages: they are chunked as a single page 1, with the heading
+    /// path in `section` for structured formats (plus the closest element id in
+    /// `anchor` for HTML), and page selection, filters and passwords do not apply.
+    /// EPUB chapters are numbered by spine position instead (`page` is the 1-based
+    /// position, `spine_index` the 0-based one) and carry their `chapter` title.
+    /// Source code chunks carry their line range in `start_line`/`end_line`.
+    pub async fn process_file(
+        &self,
+        file_path: &str,
+        options: &ProcessOptions,
+    ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        self.process_file_sync(file_path, options)
+    }
+    
+    /// Same as [`process_file`](Self::process_file), without a future
+    /// 
+    /// The pipeline never waits on I/O or timers, so this is what `process_file`
+    /// runs; rayon workers call it directly (see [`process_directory`](Self::process_directory)).
+    pub fn process_file_sync(&self, file_path: &str, options: &ProcessOptions) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        let path = Path::new(file_path);
+        match FileKind::from_path(path)? {
+            FileKind::Pdf => self.chunk_pdf(file_path, options),
+            kind => self.process_text_document(path, kind),
+        }
+    }
+    
+    /// Process every supported file below `root` selected by `rules`
+    /// 
+    /// Process:
+    /// 1. Walk the tree and select files (see [`list_files`])
+    /// 2. Process the files in parallel on the rayon pool with [`process_file_sync`](Self::process_file_sync),
+    ///    each with the same `options`; pages within a PDF are still chunked in parallel
+    /// 3. Set each chunk's `source` to the file's path relative to `root` and hand the
+    ///    file's chunks to `on_file` as soon as it is done (files finish in any order)
+    /// 4. Record failures per file instead of aborting the whole run
+    /// 
+    /// Must run on a multi-threaded tokio runtime: the calling task blocks (via
+    /// `block_in_place`) while the rayon pool does the work.
+    pub async fn process_directory<F>(
+        &self,
+        root: &Path,
+        rules: &FileRules,
+        options: &ProcessOptions,
+        on_file: F,
+    ) -> Result<DirectorySummary, ProcessingError>
+    where
+        F: Fn(&str, Vec<ChunkMetadata>) + Sync,
+    {
+        let listing = list_files(root, rules)?;
+        
+        let outcomes: Vec<Result<usize, String>> = tokio::task::block_in_place(|| {
+            listing.files
+                .par_iter()
+                .map(|(relative, path)| {
+                    let Some(file_path) = path.to_str() else {
+                        return Err("path is not valid UTF-8".to_string());
+                    };
+                    match self.process_file_sync(file_path, options) {
+                        Ok(mut chunks) => {
+                            for chunk in &mut chunks {
+                                chunk.source = relative.clone();
+                            }
+                            // Content IDs hash the source, so derive them again from the relative path
+                            let document_id = chunks.first().map(|chunk| chunk.document_id.clone()).unwrap_or_default();
+                            assign_chunk_ids(&mut chunks, &document_id);
+                            let count = chunks.len();
+                            on_file(relative, chunks);
+                            Ok(count)
+                        }
+                        Err(e) => {
+                            warn!("Failed to process {}: {}", relative, e);
+                            Err(e.to_string())
+                        }
+                    }
+                })
+                .collect()
+        });
+        
+        let mut summary = DirectorySummary {
+            processed: Vec::new(),
+            skipped: listing.skipped,
+            failed: listing.failed,
+        };
+        for ((relative, _), outcome) in listing.files.into_iter().zip(outcomes) {
+            match outcome {
+                Ok(count) => summary.processed.push((relative, count)),
+                Err(error) => summary.failed.push((relative, error)),
+            }
+        }
+        
+        info!("Directory processing complete: {} files processed, {} skipped, {} failed",
+              summary.processed.len(), summary.skipped.len(), summary.failed.len());
+        Ok(summary)
+    }
+    
+    /// Read a non-PDF document and chunk it
+    fn process_text_document(&self, path: &Path, kind: FileKind) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        let filename = path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown.txt")
+            .to_string();
+        
+        info!("Processing {:?} file: {}", kind, path.display());
+        
+        let mut chunks = match kind {
+            FileKind::Docx => {
+                let markdown = DocxReader::new().read_markdown(path)?;
+                self.text_chunker.chunk_markdown(1, &markdown, &filename)?
+            }
+            FileKind::Html => {
+                let document = HtmlReader::new().convert(&read_text_file(path)?);
+                let mut chunks = self.text_chunker.chunk_markdown(1, &document.markdown, &filename)?;
+                for chunk in &mut chunks {
+                    chunk.anchor = document.anchor_at(chunk.start_offset).map(str::to_string);
+                }
+                chunks
+            }
+            FileKind::Epub => {
+                let mut chunks = Vec::new();
+                for chapter in EpubReader::new().read_chapters(path)? {
+                    let markdown = &chapter.document.markdown;
+                    let mut chapter_chunks = self.text_chunker.chunk_markdown(chapter.spine_index + 1, markdown, &filename)?;
+                    for chunk in &mut chapter_chunks {
+                        chunk.anchor = chapter.document.anchor_at(chunk.start_offset).map(str::to_string);
+                        chunk.chapter = chapter.title_at(chunk.start_offset).map(str::to_string);
+                        chunk.spine_index = Some(chapter.spine_index);
+                    }
+                    debug!("Chapter {} ({}): {} chunks", chapter.spine_index, chapter.href, chapter_chunks.len());
+                    chunks.extend(chapter_chunks);
+                }
+                chunks
+            }
+            FileKind::Code(language) => self.text_chunker.chunk_code(1, &read_text_file(path)?, &filename, language)?,
+            FileKind::Markdown => self.text_chunker.chunk_markdown(1, &read_text_file(path)?, &filename)?,
+            _ => self.text_chunker.chunk_page_text(1, &read_text_file(path)?, &filename)?,
+        };
+        assign_chunk_ids(&mut chunks, &document_fingerprint(path)?);
+        
+        info!("Processing complete. Generated {} total chunks", chunks.len());
+        Ok(chunks)
+    }
+}
+
+/// Read a text file as UTF-8, replacing invalid bytes
+fn read_text_file(path: &Path) -> Result<String, ProcessingError> {
+    let bytes = std::fs::read(path)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("{} is not valid UTF-8; replacing invalid bytes", path.display());
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    })
+}
@@ -1,10 +1,14 @@
 use crate::error::ProcessingError;
 use crate::tiktoken_core::CoreBPE;
+use crate::encodings::EncodingKind;
 use crate::chunking::ChunkMetadata;
-use crate::text_preprocessor::TextPreprocessor;
-use crate::semantic_segmenter::SemanticSegmenter;
+use crate::text_preprocessor::{NormalizationOptions, TextPreprocessor};
+use crate::semantic_segmenter::{Segment, SemanticSegmenter};
+use crate::markdown_segmenter::{heading_title, BlockKind, MarkdownSegmenter};
+use crate::code_segmenter::{CodeLanguage, CodeSegmenter};
 use crate::chunk_merger::ChunkMerger;
 use crate::chunk_overlapper::ChunkOverlapper;
+use std::sync::Arc;
 use log::debug;
 
 /// Advanced semantic-aware text chunker
@@ -16,7 +20,7 @@ use log::debug;
 /// 4. Token-level overlap for context preservation
 /// 
 /// Architecture:
-/// - Preprocess: Clean excessive whitespace (your Python regex)
+/// - Preprocess: Unicode normalization, clean excessive whitespace (your Python regex)
 /// - Segment: Recursive semantic splitting (paragraphs -> sentences -> words)
 /// - Merge: Greedy merging to target token size
 /// - Overlap: Add token-level overlap between chunks
@@ -25,26 +29,44 @@ pub struct SemanticChunker {
     overlap_tokens: usize,
     preprocessor: TextPreprocessor,
     segmenter: SemanticSegmenter,
-    tokenizer: CoreBPE,
+    markdown: MarkdownSegmenter,
+    code: CodeSegmenter,
+    tokenizer: Arc<CoreBPE>, // Shared with every other chunker of the same encoding
 }
 
 impl SemanticChunker {
     /// Create new semantic chunker with tiktoken integration
-    pub fn new(target_tokens: usize, overlap_tokens: usize) -> Result<Self, ProcessingError> {
-        debug!("Initializing semantic chunker: target={} tokens, overlap={} tokens", 
-               target_tokens, overlap_tokens);
+    /// 
+    /// Token budgets are measured with `encoding`.
+    pub fn new(target_tokens: usize, overlap_tokens: usize, encoding: EncodingKind) -> Result<Self, ProcessingError> {
+        debug!("Initializing semantic chunker: target={} tokens, overlap={} tokens, encoding={}", 
+               target_tokens, overlap_tokens, encoding);
         
-        let tokenizer = CoreBPE::new_o200k_base()?;
+        let tokenizer = CoreBPE::shared(encoding)?;
         
         Ok(Self {
             target_tokens,
             overlap_tokens,
             preprocessor: TextPreprocessor::new(),
             segmenter: SemanticSegmenter::new(),
+            markdown: MarkdownSegmenter::new(),
+            code: CodeSegmenter::new(),
             tokenizer,
         })
     }
     
+    /// Configure the Unicode/typographic normalization applied during preprocessing
+    pub fn with_normalization(mut self, normalization: NormalizationOptions) -> Self {
+        self.preprocessor = self.preprocessor.with_normalization(normalization);
+        self
+    }
+    
+    /// Measure token budgets with `tokenizer` instead of the shared one of the encoding
+    pub fn with_tokenizer(mut self, tokenizer: Arc<CoreBPE>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+    
     /// Apply semantic-aware chunking to page text
     /// 
     /// Process:
@@ -52,7 +74,7 @@ impl SemanticChunker {
     /// 2. Segment: Recursive splitting by semantic boundaries
     /// 3. Merge: Greedily combine segments up to target tokens
     /// 4. Overlap: Add token-level overlap between chunks
-    /// 5. Return: Standard ChunkMetadata format
+    /// 5. Return: Standard ChunkMetadata format, with offsets mapped back onto `text`
     pub fn chunk_page_text(
         &self,
         page_num: usize,
@@ -62,7 +84,7 @@ impl SemanticChunker {
         debug!("Semantic chunking page {}: {} characters", page_num, text.len());
         
         // Step 1: Preprocess text (your Python regex + cleanup)
-        let cleaned_text = self.preprocessor.preprocess(text);
+        let (cleaned_text, offset_map) = self.preprocessor.preprocess_with_offsets(text);
         
         if cleaned_text.trim().is_empty() {
             debug!("Page {} is empty after preprocessing", page_num);
@@ -77,10 +99,22 @@ impl SemanticChunker {
             // Single chunk case
             return Ok(vec![ChunkMetadata {
                 page: page_num,
+                page_label: None,
                 chunk_id: 0,
-                text: cleaned_text,
+                content_id: String::new(),
+                document_id: String::new(),
                 source: source.to_string(),
                 token_count: total_tokens,
+                start_offset: offset_map[0],
+                end_offset: offset_map[cleaned_text.len()],
+                bboxes: Vec::new(),
+                section: None,
+                anchor: None,
+                chapter: None,
+                spine_index: None,
+                start_line: None,
+                end_line: None,
+                text: cleaned_text,
             }]);
         }
         
@@ -93,13 +127,19 @@ impl SemanticChunker {
         }
         
         // Step 3: Merge segments into chunks
-        let merger = ChunkMerger::new(self.target_tokens, self.tokenizer.clone());
+        let merger = ChunkMerger::new(self.target_tokens, &self.tokenizer);
         let semantic_chunks = merger.merge_segments(segments)?;
         debug!("Page {} merged into {} semantic chunks", page_num, semantic_chunks.len());
         
         // Step 4: Add overlap and convert to final format
-        let overlapper = ChunkOverlapper::new(self.overlap_tokens, self.tokenizer.clone());
-        let final_chunks = overlapper.add_overlap_and_finalize(semantic_chunks, page_num, source)?;
+        let overlapper = ChunkOverlapper::new(self.overlap_tokens, &self.tokenizer);
+        let mut final_chunks = overlapper.add_overlap_and_finalize(semantic_chunks, page_num, source)?;
+        
+        // Map spans in the cleaned text back onto the caller's page text
+        for chunk in &mut final_chunks {
+            chunk.start_offset = offset_map[chunk.start_offset];
+            chunk.end_offset = offset_map[chunk.end_offset];
+        }
         
         debug!("Page {} semantic chunking complete: {} final chunks", page_num, final_chunks.len());
         
@@ -110,6 +150,222 @@ impl SemanticChunker {
         
         Ok(final_chunks)
     }
+    
+    /// Chunk a markdown document, using its block structure as semantic units
+    /// 
+    /// Process:
+    /// 1. Split the raw text into blocks (headings, code fences, lists, tables, paragraphs)
+    /// 2. Clean every block except code fences, which are kept verbatim, and rejoin
+    ///    the blocks with blank lines into one cleaned document
+    /// 3. Make each block one segment (headings stay attached to the block they
+    ///    introduce); only blocks above the target size are split recursively
+    /// 4. Merge segments as spans of the cleaned document, so chunks keep the
+    ///    markdown layout, then add overlap as usual
+    /// 5. Label each chunk with the heading path it starts under and map offsets
+    ///    back onto `text`
+    pub fn chunk_markdown(
+        &self,
+        page_num: usize,
+        text: &str,
+        source: &str,
+    ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        debug!("Markdown chunking page {}: {} characters", page_num, text.len());
+        
+        // Steps 1-2: Block structure on the raw text, cleaned block by block
+        let mut cleaned_text = String::with_capacity(text.len());
+        let mut offset_map: Vec<usize> = Vec::with_capacity(text.len() + 1);
+        let mut end_sentinel = text.len();
+        let mut units: Vec<(BlockKind, usize, usize)> = Vec::new();
+        let mut headings: Vec<(usize, String)> = Vec::new(); // Open (level, title) stack
+        let mut sections: Vec<(usize, Option<String>)> = Vec::new(); // Heading path from each heading on
+        
+        for block in self.markdown.blocks(text) {
+            let raw = &text[block.range.clone()];
+            let (block_text, block_map) = if block.kind == BlockKind::CodeFence {
+                (raw.to_string(), (block.range.start..=block.range.end).collect())
+            } else {
+                let (cleaned, map) = self.preprocessor.preprocess_with_offsets(raw);
+                (cleaned, map.into_iter().map(|offset| offset + block.range.start).collect::<Vec<_>>())
+            };
+            if block_text.trim().is_empty() {
+                continue;
+            }
+            
+            // Separators map to the end of the previous block, so chunk ends stay exact
+            if !cleaned_text.is_empty() {
+                cleaned_text.push_str("\n\n");
+                offset_map.extend([end_sentinel; 2]);
+            }
+            let start = cleaned_text.len();
+            cleaned_text.push_str(&block_text);
+            offset_map.extend_from_slice(&block_map[..block_text.len()]);
+            end_sentinel = block_map[block_text.len()];
+            units.push((block.kind, start, cleaned_text.len()));
+            
+            if block.kind == BlockKind::Heading {
+                let (level, title) = heading_title(&block_text);
+                while headings.last().is_some_and(|(open, _)| *open >= level) {
+                    headings.pop();
+                }
+                headings.push((level, title));
+                let path: Vec<&str> = headings.iter().map(|(_, title)| title.as_str()).collect();
+                sections.push((start, Some(path.join(" > "))));
+            }
+        }
+        offset_map.push(end_sentinel);
+        
+        if units.is_empty() {
+            debug!("Page {} is empty after preprocessing", page_num);
+            return Ok(vec![]);
+        }
+        
+        // Step 3: One segment per block, splitting only oversized blocks
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut after_heading = false;
+        
+        for (kind, start, end) in units {
+            let counts = self.tokenizer.piece_counts(&cleaned_text[start..end]);
+            
+            // A heading joins the block it introduces when both fit in one chunk
+            if after_heading
+                && let Some(previous) = segments.last_mut()
+                && let Some(previous_counts) = &previous.token_counts
+                && let joined = &cleaned_text[previous.start_offset..end]
+                && let joined_counts = self.tokenizer.join_piece_counts(joined, previous_counts, &counts)
+                && joined_counts.token_count() <= self.target_tokens
+            {
+                previous.text = joined.to_string();
+                previous.end_offset = end;
+                previous.token_counts = Some(joined_counts);
+                after_heading = kind == BlockKind::Heading;
+                continue;
+            }
+            after_heading = kind == BlockKind::Heading;
+            
+            if counts.token_count() <= self.target_tokens {
+                segments.push(Segment {
+                    text: cleaned_text[start..end].to_string(),
+                    start_offset: start,
+                    end_offset: end,
+                    semantic_level: 0,
+                    token_counts: Some(counts),
+                });
+            } else {
+                let block_segments = self.segmenter.segment(&cleaned_text[start..end], self.target_tokens, &self.tokenizer);
+                segments.extend(block_segments.into_iter().map(|segment| Segment {
+                    start_offset: segment.start_offset + start,
+                    end_offset: segment.end_offset + start,
+                    ..segment
+                }));
+            }
+        }
+        debug!("Page {} segmented into {} markdown segments", page_num, segments.len());
+        
+        // Step 4: Merge spans and add overlap
+        let merger = ChunkMerger::new(self.target_tokens, &self.tokenizer);
+        let semantic_chunks = merger.merge_spans(segments, &cleaned_text)?;
+        
+        let overlapper = ChunkOverlapper::new(self.overlap_tokens, &self.tokenizer);
+        let mut final_chunks = overlapper.add_overlap_and_finalize(semantic_chunks, page_num, source)?;
+        
+        // Step 5: Section labels, then map spans in the cleaned text back onto the caller's text
+        for chunk in &mut final_chunks {
+            let in_effect = sections.partition_point(|(start, _)| *start <= chunk.start_offset);
+            chunk.section = in_effect.checked_sub(1).and_then(|idx| sections[idx].1.clone());
+            chunk.start_offset = offset_map[chunk.start_offset];
+            chunk.end_offset = offset_map[chunk.end_offset];
+        }
+        
+        debug!("Page {} markdown chunking complete: {} final chunks", page_num, final_chunks.len());
+        Ok(final_chunks)
+    }
+    
+    /// Chunk source code along its structure instead of prose separators
+    /// 
+    /// Process:
+    /// 1. Split the source into top-level units (functions, classes, impl blocks,
+    ///    import groups) with bracket/indentation balancing; code is never cleaned
+    /// 2. Make each unit one segment; a unit above the target size is split at its
+    ///    blank lines, then into single lines, and a single oversized line by words
+    /// 3. Merge segments as spans of the source, so chunks keep their indentation,
+    ///    then add overlap as usual
+    /// 4. Report the 1-based line range each chunk covers
+    pub fn chunk_code(
+        &self,
+        page_num: usize,
+        text: &str,
+        source: &str,
+        language: CodeLanguage,
+    ) -> Result<Vec<ChunkMetadata>, ProcessingError> {
+        debug!("Code chunking page {} as {:?}: {} characters", page_num, language, text.len());
+        
+        // Steps 1-2: Top-level units, split further only when oversized
+        let mut segments: Vec<Segment> = Vec::new();
+        for unit in self.code.units(text, language) {
+            self.push_code_segments(text, unit, 0, &mut segments);
+        }
+        if segments.is_empty() {
+            debug!("Page {} has no code", page_num);
+            return Ok(vec![]);
+        }
+        debug!("Page {} segmented into {} code segments", page_num, segments.len());
+        
+        // Step 3: Merge spans and add overlap
+        let merger = ChunkMerger::new(self.target_tokens, &self.tokenizer);
+        let semantic_chunks = merger.merge_spans(segments, text)?;
+        
+        let overlapper = ChunkOverlapper::new(self.overlap_tokens, &self.tokenizer);
+        let mut final_chunks = overlapper.add_overlap_and_finalize(semantic_chunks, page_num, source)?;
+        
+        // Step 4: Line ranges from the chunk spans (the overlap belongs to the previous chunk)
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        for chunk in &mut final_chunks {
+            let start_line = line_starts.partition_point(|start| *start <= chunk.start_offset);
+            let end_line = line_starts.partition_point(|start| *start < chunk.end_offset);
+            chunk.start_line = Some(start_line);
+            chunk.end_line = Some(end_line.max(start_line));
+        }
+        
+        debug!("Page {} code chunking complete: {} final chunks", page_num, final_chunks.len());
+        Ok(final_chunks)
+    }
+    
+    /// Add `text[range]` as one segment if it fits, otherwise split it one level
+    /// finer (0: unit -> blank-line blocks, 1: block -> lines, 2: line -> words)
+    fn push_code_segments(&self, text: &str, range: std::ops::Range<usize>, level: usize, segments: &mut Vec<Segment>) {
+        let span = &text[range.clone()];
+        let counts = self.tokenizer.piece_counts(span);
+        if counts.token_count() <= self.target_tokens {
+            segments.push(Segment {
+                text: span.to_string(),
+                start_offset: range.start,
+                end_offset: range.end,
+                semantic_level: level,
+                token_counts: Some(counts),
+            });
+            return;
+        }
+        
+        let parts = match level {
+            0 => self.code.blocks(text, range.clone()),
+            1 => self.code.lines(text, range.clone()),
+            _ => {
+                // A single line above the target (e.g. minified code)
+                let line_segments = self.segmenter.segment(span, self.target_tokens, &self.tokenizer);
+                segments.extend(line_segments.into_iter().map(|segment| Segment {
+                    start_offset: segment.start_offset + range.start,
+                    end_offset: segment.end_offset + range.start,
+                    ..segment
+                }));
+                return;
+            }
+        };
+        for part in parts {
+            self.push_code_segments(text, part, level + 1, segments);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -118,7 +374,7 @@ mod tests {
 
     #[test]
     fn test_semantic_chunking() {
-        let chunker = SemanticChunker::new(100, 10).unwrap();
+        let chunker = SemanticChunker::new(100, 10, EncodingKind::O200kBase).unwrap();
         
         let text = "First paragraph with some content.\n\nSecond paragraph with more content.\n\nThird paragraph with even more content to test the chunking logic.";
         
@@ -135,7 +391,7 @@ mod tests {
 
     #[test]
     fn test_excessive_newlines_cleaning() {
-        let chunker = SemanticChunker::new(100, 10).unwrap();
+        let chunker = SemanticChunker::new(100, 10, EncodingKind::O200kBase).unwrap();
         
         let text = "Line 1\n\n\n\nLine 2\n \n \n\nLine 3";
         let chunks = chunker.chunk_page_text(1, text, "test.pdf").unwrap();
@@ -144,4 +400,103 @@ mod tests {
         // Should not contain excessive newlines
         assert!(!chunks[0].text.contains("\n\n\n"));
     }
+    
+    #[test]
+    fn test_markdown_chunking() {
+        let chunker = SemanticChunker::new(25, 0, EncodingKind::O200kBase).unwrap();
+        
+        let code = "```python\ndef add(a, b):\n    return a + b\n```";
+        let text = format!(
+            "# Guide\n\nSome   introductory prose that explains what the following example does in detail.\n\n\
+             ## Example\n\n{}\n\n- first point\n- second point",
+            code
+        );
+        let chunks = chunker.chunk_markdown(1, &text, "guide.md").unwrap();
+        
+        assert!(chunks.len() > 1);
+        // Code keeps its indentation and is never split from its heading
+        let code_chunk = chunks.iter().find(|c| c.text.contains("def add")).unwrap();
+        assert!(code_chunk.text.starts_with("## Example\n\n```python"));
+        assert!(code_chunk.text.contains(code));
+        // Prose is still cleaned
+        assert!(chunks[0].text.starts_with("# Guide\n\nSome introductory"));
+        assert_eq!(chunks[0].section.as_deref(), Some("Guide"));
+        assert_eq!(code_chunk.section.as_deref(), Some("Guide > Example"));
+        // Offsets map back onto the raw markdown
+        assert_eq!(&text[code_chunk.start_offset..code_chunk.end_offset], format!("## Example\n\n{}", code));
+    }
+    
+    #[test]
+    fn test_code_chunking() {
+        let chunker = SemanticChunker::new(40, 0, EncodingKind::O200kBase).unwrap();
+        
+        let function = |name: &str| {
+            format!("def {}(values):\n    total = 0\n\n    for value in values:\n        total += value * 2\n    return total", name)
+        };
+        let text = format!("import math\n\n\n{}\n\n\n{}\n", function("first"), function("second"));
+        let chunks = chunker.chunk_code(1, &text, "util.py", CodeLanguage::Python).unwrap();
+        
+        // Functions are never cut in half, even across their inner blank line
+        let second = chunks.iter().find(|c| c.text.contains("def second")).unwrap();
+        assert!(second.text.starts_with("def second"));
+        assert!(second.text.ends_with("return total"));
+        assert_eq!((second.start_line, second.end_line), (Some(12), Some(17)));
+        assert_eq!(&text[second.start_offset..second.end_offset], function("second"));
+        assert_eq!(chunks[0].start_line, Some(1));
+    }
+    
+    /// Deterministic page of `words` words from a small vocabulary; `prose` adds
+    /// sentence punctuation and paragraph breaks, otherwise the page is one run of
+    /// words (a flattened table or form), which splits into word-sized segments
+    fn benchmark_page(words: usize, prose: bool, seed: u64) -> String {
+        const WORDS: [&str; 16] = [
+            "revenue", "the", "quarterly", "Ünternehmen", "of", "analysis", "2024", "growth",
+            "data", "model", "über", "report", "and", "café", "42.5%", "segment",
+        ];
+        let mut state = seed;
+        let mut page = String::new();
+        for index in 0..words {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            page.push_str(WORDS[(state >> 60) as usize]);
+            page.push_str(match (prose, index % 97, index % 13) {
+                (true, 96, _) => ".\n\n",
+                (true, _, 12) => ". ",
+                _ => " ",
+            });
+        }
+        page
+    }
+
+    /// Per-page throughput of semantic chunking (256-token chunks, 16 overlap)
+    /// 
+    /// Run with `cargo test --release benchmark_chunk_page -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn benchmark_chunk_page() {
+        let chunker = SemanticChunker::new(256, 16, EncodingKind::O200kBase).unwrap();
+        for (name, prose) in [("prose", true), ("dense", false)] {
+            let pages: Vec<String> = (0..20).map(|seed| benchmark_page(700, prose, seed)).collect();
+            let bytes: usize = pages.iter().map(String::len).sum();
+            chunker.chunk_page_text(1, &pages[0], "bench").unwrap(); // Warm up
+            
+            let start = std::time::Instant::now();
+            let mut chunks = 0;
+            let mut tokens = 0;
+            for page in &pages {
+                let page_chunks = chunker.chunk_page_text(1, page, "bench").unwrap();
+                chunks += page_chunks.len();
+                tokens += page_chunks.iter().map(|chunk| chunk.token_count).sum::<usize>();
+            }
+            let elapsed = start.elapsed();
+            println!(
+                "{}: {:.2} ms/page, {:.0} pages/s, {:.2} MB/s ({} chunks, {} tokens)",
+                name,
+                elapsed.as_secs_f64() * 1000.0 / pages.len() as f64,
+                pages.len() as f64 / elapsed.as_secs_f64(),
+                bytes as f64 / elapsed.as_secs_f64() / 1e6,
+                chunks,
+                tokens,
+            );
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::tiktoken_core::{CoreBPE, PieceCounts};
 use regex::Regex;
 use log::debug;
 
@@ -7,7 +8,9 @@ pub struct Segment {
     pub text: String,
     pub start_offset: usize,
     pub end_offset: usize,
+    #[allow(dead_code)]
     pub semantic_level: usize, // Which separator level created this segment
+    pub token_counts: Option<PieceCounts>, // Per-piece token counts of `text`, when already computed
 }
 
 /// Semantic text segmenter using recursive separator strategy
@@ -23,6 +26,7 @@ pub struct SemanticSegmenter {
 #[derive(Debug)]
 struct SeparatorPattern {
     pattern: SeparatorType,
+    #[allow(dead_code)]
     level: usize,
     description: String,
 }
@@ -109,8 +113,9 @@ impl SemanticSegmenter {
     
     /// Segment text using recursive separator strategy
     /// 
-    /// Returns segments that respect semantic boundaries as much as possible
-    pub fn segment(&self, text: &str, max_tokens: usize, tokenizer: &crate::tiktoken_core::CoreBPE) -> Vec<Segment> {
+    /// Returns segments that respect semantic boundaries as much as possible, with
+    /// their token counts (so merging does not encode them again)
+    pub fn segment(&self, text: &str, max_tokens: usize, tokenizer: &CoreBPE) -> Vec<Segment> {
         debug!("Starting semantic segmentation: {} chars, max_tokens={}", text.len(), max_tokens);
         
         let initial_segment = Segment {
@@ -118,6 +123,7 @@ impl SemanticSegmenter {
             start_offset: 0,
             end_offset: text.len(),
             semantic_level: 0,
+            token_counts: None,
         };
         
         self.recursive_split(vec![initial_segment], max_tokens, tokenizer, 0)
@@ -128,7 +134,7 @@ impl SemanticSegmenter {
         &self,
         segments: Vec<Segment>,
         max_tokens: usize,
-        tokenizer: &crate::tiktoken_core::CoreBPE,
+        tokenizer: &CoreBPE,
         separator_level: usize,
     ) -> Vec<Segment> {
         // Base case: no more separators to try
@@ -139,11 +145,14 @@ impl SemanticSegmenter {
         let mut result = Vec::new();
         let mut needs_further_splitting = Vec::new();
         
-        for segment in segments {
-            let token_count = tokenizer.encode_ordinary(&segment.text).len();
+        for mut segment in segments {
+            let token_counts = segment.token_counts
+                .take()
+                .unwrap_or_else(|| tokenizer.piece_counts(&segment.text));
             
-            if token_count <= max_tokens {
+            if token_counts.token_count() <= max_tokens {
                 // Segment is small enough, keep it
+                segment.token_counts = Some(token_counts);
                 result.push(segment);
             } else {
                 // Try to split this segment
@@ -153,7 +162,8 @@ impl SemanticSegmenter {
                     // Successfully split, add smaller segments for further processing
                     needs_further_splitting.extend(split_segments);
                 } else {
-                    // Couldn't split with this separator, try next level
+                    // Couldn't split with this separator, try next level (with its counts)
+                    segment.token_counts = Some(token_counts);
                     needs_further_splitting.push(segment);
                 }
             }
@@ -190,40 +200,52 @@ impl SemanticSegmenter {
             return vec![segment.clone()];
         }
         
-        // Convert splits to segments with proper offsets
-        let mut result = Vec::new();
-        let mut current_offset = segment.start_offset;
-        
-        for split_text in splits {
-            if !split_text.trim().is_empty() {
-                let segment_end = current_offset + split_text.len();
-                result.push(Segment {
+        // Convert splits to segments with offsets relative to the full text
+        let result: Vec<Segment> = splits
+            .into_iter()
+            .map(|(local_start, split_text)| {
+                let start_offset = segment.start_offset + local_start;
+                Segment {
+                    end_offset: start_offset + split_text.len(),
                     text: split_text,
-                    start_offset: current_offset,
-                    end_offset: segment_end,
+                    start_offset,
                     semantic_level: separator_level,
-                });
-                current_offset = segment_end;
-            }
-        }
+                    token_counts: None,
+                }
+            })
+            .collect();
         
         debug!("Split into {} segments using {}", result.len(), separator.description);
         result
     }
     
-    /// Split text by regex pattern
-    fn split_by_regex(&self, text: &str, regex: &Regex) -> Vec<String> {
-        regex.split(text)
-            .map(|s| s.to_string())
-            .filter(|s| !s.trim().is_empty())
-            .collect()
+    /// Split text by regex pattern, returning each non-blank piece with its byte offset
+    fn split_by_regex(&self, text: &str, regex: &Regex) -> Vec<(usize, String)> {
+        let bounds = regex.find_iter(text).map(|m| (m.start(), m.end()));
+        Self::collect_pieces(text, bounds)
+    }
+    
+    /// Split text by literal string, returning each non-blank piece with its byte offset
+    fn split_by_literal(&self, text: &str, separator: &str) -> Vec<(usize, String)> {
+        let bounds = text.match_indices(separator).map(|(start, sep)| (start, start + sep.len()));
+        Self::collect_pieces(text, bounds)
     }
     
-    /// Split text by literal string
-    fn split_by_literal(&self, text: &str, separator: &str) -> Vec<String> {
-        text.split(separator)
-            .map(|s| s.to_string())
-            .filter(|s| !s.trim().is_empty())
+    /// Cut text at the given separator bounds, keeping non-blank pieces
+    fn collect_pieces(text: &str, separator_bounds: impl Iterator<Item = (usize, usize)>) -> Vec<(usize, String)> {
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        
+        for (start, end) in separator_bounds {
+            pieces.push((last, &text[last..start]));
+            last = end;
+        }
+        pieces.push((last, &text[last..]));
+        
+        pieces
+            .into_iter()
+            .filter(|(_, s)| !s.trim().is_empty())
+            .map(|(offset, s)| (offset, s.to_string()))
             .collect()
     }
 }
@@ -231,7 +253,6 @@ impl SemanticSegmenter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tiktoken_core::CoreBPE;
 
     #[test]
     fn test_paragraph_splitting() {
@@ -239,10 +260,26 @@ mod tests {
         let tokenizer = CoreBPE::new_o200k_base().unwrap();
         
         let text = "First paragraph.\n\nSecond paragraph.\n\nThird paragraph.";
-        let segments = segmenter.segment(text, 50, &tokenizer);
+        let segments = segmenter.segment(text, 5, &tokenizer);
         
         assert!(segments.len() >= 3);
         assert!(segments[0].text.contains("First"));
         assert!(segments[1].text.contains("Second"));
     }
+
+    #[test]
+    fn test_segment_offsets() {
+        let segmenter = SemanticSegmenter::new();
+        let tokenizer = CoreBPE::new_o200k_base().unwrap();
+        
+        let text = "Alpha beta gamma.\n\nDelta epsilon. Zeta eta theta.";
+        let segments = segmenter.segment(text, 5, &tokenizer);
+        
+        // Offsets point at the segment text within the original string, and the
+        // counts computed while splitting are those of the segment text
+        for segment in &segments {
+            assert_eq!(&text[segment.start_offset..segment.end_offset], segment.text);
+            assert_eq!(segment.token_counts.as_ref().unwrap().token_count(), tokenizer.encode_ordinary(&segment.text).len());
+        }
+    }
 }
@@ -1,66 +1,194 @@
 use crate::error::ProcessingError;
+use crate::chunking::BoundingBox;
 use pdfium_render::prelude::*;
-use regex::Regex;
+use std::ops::Range;
 use log::{debug};
 
+/// Bounding box of a single extracted character in PDF user-space coordinates
+/// 
+/// PDF user space has its origin at the bottom-left of the page, so
+/// `(x0, y0)` is the lower-left and `(x1, y1)` the upper-right corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharBox {
+    pub offset: usize, // Byte offset of the character in the extracted page text
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+/// Text extracted from a single PDF page, ready for chunking
+#[derive(Debug, Clone)]
+pub struct PageText {
+    pub page_index: usize,          // 0-based physical page index
+    pub page_label: Option<String>, // Logical label from /PageLabels, if the document defines one
+    pub text: String,
+    pub char_boxes: Vec<CharBox>,   // Sorted by offset; whitespace has no box
+}
+
+impl PageText {
+    /// Merge the character boxes covering `range` of the page text into one
+    /// rectangle per text line
+    /// 
+    /// Characters are walked in reading order; a new line starts when a character
+    /// no longer overlaps the current line vertically or jumps back to the left.
+    pub fn line_boxes(&self, range: Range<usize>) -> Vec<BoundingBox> {
+        let page = self.page_index + 1;
+        let first = self.char_boxes.partition_point(|b| b.offset < range.start);
+        
+        let mut lines: Vec<BoundingBox> = Vec::new();
+        let mut current: Option<BoundingBox> = None;
+        let mut last_x0 = f32::MIN;
+        
+        for char_box in self.char_boxes[first..].iter().take_while(|b| b.offset < range.end) {
+            current = Some(match current {
+                Some(line) if Self::continues_line(&line, char_box, last_x0) => BoundingBox {
+                    page,
+                    x0: line.x0.min(char_box.x0),
+                    y0: line.y0.min(char_box.y0),
+                    x1: line.x1.max(char_box.x1),
+                    y1: line.y1.max(char_box.y1),
+                },
+                previous => {
+                    lines.extend(previous);
+                    BoundingBox {
+                        page,
+                        x0: char_box.x0,
+                        y0: char_box.y0,
+                        x1: char_box.x1,
+                        y1: char_box.y1,
+                    }
+                }
+            });
+            last_x0 = char_box.x0;
+        }
+        
+        lines.extend(current);
+        lines
+    }
+    
+    /// Whether a character belongs on the same line as the rectangle built so far
+    fn continues_line(line: &BoundingBox, char_box: &CharBox, last_x0: f32) -> bool {
+        let overlap = line.y1.min(char_box.y1) - line.y0.max(char_box.y0);
+        let char_height = char_box.y1 - char_box.y0;
+        let moved_left = char_box.x0 < last_x0 - char_height.max(1.0);
+        
+        overlap > 0.5 * char_height.min(line.y1 - line.y0) && !moved_left
+    }
+}
+
 /// Text extraction component using pdfium
 /// 
 /// This handles the low-level text extraction from PDF pages
-pub struct TextExtractor {
-    cleanup_regex: Regex,
-}
+pub struct TextExtractor;
 
 impl TextExtractor {
     pub fn new() -> Self {
-        // Cleanup regex: removes excessive whitespace and normalizes text
-        let cleanup_regex = Regex::new(r"\s+").expect("Invalid cleanup regex");
-        
-        TextExtractor {
-            cleanup_regex,
-        }
+        TextExtractor
     }
     
     /// Extract and clean text from a PDF page
     /// 
     /// Process:
-    /// 1. Extract raw text using pdfium's text extraction
-    /// 2. Clean and normalize whitespace
-    /// 3. Handle Unicode and special characters
-    /// 4. Return cleaned text ready for word counting
-    pub fn extract_page_text(&self, page: &PdfPage, page_index: usize) -> Result<String, ProcessingError> {
+    /// 1. Walk pdfium's character list (text plus generated spaces/line breaks)
+    /// 2. Collapse whitespace runs into a single line break (if the run contains one)
+    ///    or a single space, and trim the edges
+    /// 3. Record the loose bounding box of every kept non-whitespace character
+    /// 4. Return cleaned text and boxes ready for chunking
+    pub fn extract_page_text(&self, page: &PdfPage, page_index: usize) -> Result<PageText, ProcessingError> {
         debug!("Extracting text from page {}", page_index);
         
         // Extract text using pdfium - this handles the PDF structure parsing
-        let raw_text = page.text()
+        let page_text = page.text()
             .map_err(|e| ProcessingError::TextExtractionError {
                 page: page_index,
                 error: format!("pdfium extraction failed: {}", e),
-            })?
-            .all();
+            })?;
         
-        if raw_text.is_empty() {
-            debug!("Page {} contains no text", page_index);
-            return Ok(String::new());
+        let mut text = String::new();
+        let mut char_boxes = Vec::new();
+        let mut pending_separator: Option<char> = None;
+        
+        for pdf_char in page_text.chars().iter() {
+            let Some(c) = pdf_char.unicode_char() else {
+                continue;
+            };
+            
+            // Replace multiple whitespace with a single separator, dropping leading/trailing
+            // runs; line breaks are kept so later stages can see the line structure
+            if c.is_whitespace() {
+                if c == '\n' || c == '\r' {
+                    pending_separator = Some('\n');
+                } else {
+                    pending_separator.get_or_insert(' ');
+                }
+                continue;
+            }
+            if let Some(separator) = pending_separator.take()
+                && !text.is_empty()
+            {
+                text.push(separator);
+            }
+            
+            if let Ok(rect) = pdf_char.loose_bounds() {
+                char_boxes.push(CharBox {
+                    offset: text.len(),
+                    x0: rect.left().value,
+                    y0: rect.bottom().value,
+                    x1: rect.right().value,
+                    y1: rect.top().value,
+                });
+            }
+            text.push(c);
         }
         
-        // Clean and normalize the extracted text
-        let cleaned_text = self.cleanup_text(&raw_text);
+        if text.is_empty() {
+            debug!("Page {} contains no text", page_index);
+        } else {
+            debug!("Extracted {} characters ({} boxes) from page {}", text.len(), char_boxes.len(), page_index);
+        }
         
-        debug!("Extracted {} characters from page {}", cleaned_text.len(), page_index);
-        Ok(cleaned_text)
+        Ok(PageText {
+            page_index,
+            page_label: page.label().map(|label| label.to_string()),
+            text,
+            char_boxes,
+        })
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
     
-    /// Clean and normalize extracted text
-    /// 
-    /// Handles:
-    /// - Multiple whitespace normalization
-    /// - Unicode normalization
-    /// - Control character removal
-    fn cleanup_text(&self, text: &str) -> String {
-        // Replace multiple whitespace with single spaces
-        let normalized = self.cleanup_regex.replace_all(text, " ");
+    fn char_box(offset: usize, x0: f32, y0: f32) -> CharBox {
+        CharBox { offset, x0, y0, x1: x0 + 5.0, y1: y0 + 10.0 }
+    }
+    
+    #[test]
+    fn test_line_boxes_split_on_new_line() {
+        // "ab cd" on one line, "ef" on the next line below
+        let page = PageText {
+            page_index: 2,
+            page_label: None,
+            text: "ab cd ef".to_string(),
+            char_boxes: vec![
+                char_box(0, 10.0, 700.0),
+                char_box(1, 15.0, 700.0),
+                char_box(3, 25.0, 700.0),
+                char_box(4, 30.0, 700.0),
+                char_box(6, 10.0, 686.0),
+                char_box(7, 15.0, 686.0),
+            ],
+        };
+        
+        let lines = page.line_boxes(0..page.text.len());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], BoundingBox { page: 3, x0: 10.0, y0: 700.0, x1: 35.0, y1: 710.0 });
+        assert_eq!(lines[1], BoundingBox { page: 3, x0: 10.0, y0: 686.0, x1: 20.0, y1: 696.0 });
         
-        // Trim and return cleaned text
-        normalized.trim().to_string()
+        // A sub-range only covers the characters inside it
+        let lines = page.line_boxes(3..5);
+        assert_eq!(lines, vec![BoundingBox { page: 3, x0: 25.0, y0: 700.0, x1: 35.0, y1: 710.0 }]);
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,111 @@
+use crate::error::ProcessingError;
+use crate::text_extractor::PageText;
 use regex::Regex;
+use std::ops::Range;
+use std::str::FromStr;
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
 use log::debug;
 
+/// Unicode normalization form applied to extracted text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeForm {
+    /// Leave code points as extracted
+    None,
+    /// Canonical composition (`e` + combining acute -> `é`)
+    #[default]
+    Nfc,
+    /// Compatibility composition: additionally folds full-width forms,
+    /// ligatures, superscripts and similar presentation variants
+    Nfkc,
+}
+
+impl FromStr for UnicodeForm {
+    type Err = ProcessingError;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Ok(UnicodeForm::None),
+            "nfc" => Ok(UnicodeForm::Nfc),
+            "nfkc" => Ok(UnicodeForm::Nfkc),
+            other => Err(ProcessingError::InvalidArgument(format!(
+                "Unknown unicode form '{}' (expected 'none', 'nfc' or 'nfkc')",
+                other
+            ))),
+        }
+    }
+}
+
+/// Toggles for the normalization stage of [`TextPreprocessor`]
+/// 
+/// Everything is enabled by default except NFKC (NFC is used instead), since
+/// compatibility folding also rewrites superscripts, fractions and the like.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizationOptions {
+    pub unicode_form: UnicodeForm,
+    /// `ﬁ` -> `fi`, `ﬂ` -> `fl`, ... (implied by NFKC)
+    pub expand_ligatures: bool,
+    /// Curly single/double quotes and primes -> `'` / `"`
+    pub fold_quotes: bool,
+    /// Hyphen, en/em dash, minus sign and friends -> `-`
+    pub fold_dashes: bool,
+    /// Drop zero-width spaces, word joiners, byte order marks and soft hyphens
+    /// (ZWJ/ZWNJ are kept: they change the rendering of emoji and some scripts)
+    pub remove_invisible: bool,
+    /// Non-breaking and fixed-width spaces -> ordinary space
+    pub normalize_spaces: bool,
+    /// Rejoin words hyphenated at line ends (`infor-\nmation` -> `information`)
+    /// and at page breaks; genuine compounds keep their hyphen
+    pub dehyphenate: bool,
+}
+
+impl Default for NormalizationOptions {
+    fn default() -> Self {
+        Self {
+            unicode_form: UnicodeForm::Nfc,
+            expand_ligatures: true,
+            fold_quotes: true,
+            fold_dashes: true,
+            remove_invisible: true,
+            normalize_spaces: true,
+            dehyphenate: true,
+        }
+    }
+}
+
+impl NormalizationOptions {
+    /// Replacement for a single character, or None to keep it unchanged
+    fn fold_char(&self, c: char) -> Option<&'static str> {
+        match c {
+            '\u{00AD}' | '\u{200B}' | '\u{2060}' | '\u{FEFF}' if self.remove_invisible => Some(""),
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' if self.normalize_spaces => {
+                Some(" ")
+            }
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' if self.fold_quotes => Some("'"),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' if self.fold_quotes => Some("\""),
+            '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{FE58}' | '\u{FE63}' | '\u{FF0D}' if self.fold_dashes => Some("-"),
+            _ if self.expand_ligatures => match c {
+                '\u{FB00}' => Some("ff"),
+                '\u{FB01}' => Some("fi"),
+                '\u{FB02}' => Some("fl"),
+                '\u{FB03}' => Some("ffi"),
+                '\u{FB04}' => Some("ffl"),
+                '\u{FB05}' | '\u{FB06}' => Some("st"),
+                '\u{0132}' => Some("IJ"),
+                '\u{0133}' => Some("ij"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
 /// Text preprocessing component for semantic-aware chunking
 /// 
 /// Handles cleaning and normalization before semantic segmentation
 pub struct TextPreprocessor {
+    normalization: NormalizationOptions,
+    hyphenated_break_regex: Regex,
     excessive_newlines_regex: Regex,
     whitespace_cleanup_regex: Regex,
     control_chars_regex: Regex,
@@ -13,6 +114,10 @@ pub struct TextPreprocessor {
 impl TextPreprocessor {
     pub fn new() -> Self {
         Self {
+            normalization: NormalizationOptions::default(),
+            // Letters, a hyphen (or soft hyphen) at the end of a line, letters on the next line
+            hyphenated_break_regex: Regex::new(r"(\p{L}+)([-\u{00AD}\u{2010}])[ \t]*\r?\n[ \t]*(\p{L}+)")
+                .expect("Invalid hyphenation regex"),
             // Your Python regex: r'\n\s*\n\s*\n+' -> '\n\n'
             excessive_newlines_regex: Regex::new(r"\n\s*\n\s*\n+").expect("Invalid newlines regex"),
             // General whitespace cleanup
@@ -22,37 +127,316 @@ impl TextPreprocessor {
         }
     }
     
+    /// Replace the normalization toggles
+    pub fn with_normalization(mut self, normalization: NormalizationOptions) -> Self {
+        self.normalization = normalization;
+        self
+    }
+    
     /// Clean and normalize text before semantic chunking
     /// 
     /// Steps:
     /// 1. Remove control characters
-    /// 2. Normalize excessive newlines (your Python pattern)
-    /// 3. Clean excessive spaces/tabs
-    /// 4. Trim edges
+    /// 2. Rejoin words hyphenated across line ends
+    /// 3. Unicode normalization (NFC/NFKC)
+    /// 4. Typographic cleanup: ligatures, quotes, dashes, invisible characters, odd spaces
+    /// 5. Normalize excessive newlines (your Python pattern)
+    /// 6. Clean excessive spaces/tabs
+    /// 7. Trim edges
+    #[allow(dead_code)]
     pub fn preprocess(&self, text: &str) -> String {
+        self.preprocess_with_offsets(text).0
+    }
+    
+    /// Same as [`preprocess`](Self::preprocess), additionally returning an offset map
+    /// 
+    /// The map has one entry per byte of the cleaned text plus an end sentinel;
+    /// entry `i` is the byte offset in `text` that cleaned byte `i` came from, so
+    /// a cleaned range `a..b` corresponds to `map[a]..map[b]` in the input.
+    pub fn preprocess_with_offsets(&self, text: &str) -> (String, Vec<usize>) {
         debug!("Preprocessing text: {} characters", text.len());
         
+        let offsets: Vec<usize> = (0..=text.len()).collect();
+        
         // Step 1: Remove control characters (preserve \n, \t)
-        let no_control = self.control_chars_regex.replace_all(text, "");
+        let (no_control, offsets) = replace_all_mapped(&self.control_chars_regex, text, &offsets, "");
+        
+        // Step 2: De-hyphenation (before typographic cleanup removes soft hyphens)
+        let edits = if self.normalization.dehyphenate {
+            self.dehyphenation_edits(&no_control)
+        } else {
+            Vec::new()
+        };
+        let (dehyphenated, offsets) = splice_mapped(&no_control, &offsets, &edits);
         
-        // Step 2: Apply your Python newline cleaning: \n\s*\n\s*\n+ -> \n\n
-        let clean_newlines = self.excessive_newlines_regex.replace_all(&no_control, "\n\n");
+        // Step 3: Unicode normalization
+        let (normalized, offsets) = normalize_mapped(&dehyphenated, &offsets, self.normalization.unicode_form);
         
-        // Step 3: Clean excessive spaces/tabs (preserve single spaces)
-        let clean_spaces = self.whitespace_cleanup_regex.replace_all(&clean_newlines, " ");
+        // Step 4: Per-character typographic cleanup (runs before whitespace cleanup so
+        // that folded non-breaking spaces collapse with their neighbours)
+        let (folded, offsets) = fold_chars_mapped(&normalized, &offsets, |c| self.normalization.fold_char(c));
         
-        // Step 4: Trim and normalize
-        let result = clean_spaces.trim().to_string();
+        // Step 5: Apply your Python newline cleaning: \n\s*\n\s*\n+ -> \n\n
+        let (clean_newlines, offsets) =
+            replace_all_mapped(&self.excessive_newlines_regex, &folded, &offsets, "\n\n");
+        
+        // Step 6: Clean excessive spaces/tabs (preserve single spaces)
+        let (clean_spaces, offsets) =
+            replace_all_mapped(&self.whitespace_cleanup_regex, &clean_newlines, &offsets, " ");
+        
+        // Step 7: Trim and normalize
+        let trimmed = clean_spaces.trim();
+        let start = trimmed.as_ptr() as usize - clean_spaces.as_ptr() as usize;
+        let end = start + trimmed.len();
+        let result = trimmed.to_string();
+        let offsets = offsets[start..=end].to_vec();
         
         debug!("Preprocessed text: {} -> {} characters", text.len(), result.len());
-        result
+        (result, offsets)
     }
+    
+    /// Edits rejoining every hyphenated line break in `text`
+    /// 
+    /// A plausible word break drops the hyphen and line break (`infor-\nmation`);
+    /// a compound keeps its hyphen but loses the line break (`self-\naware`).
+    fn dehyphenation_edits(&self, text: &str) -> Vec<(Range<usize>, &'static str)> {
+        let context = text.to_lowercase();
+        self.hyphenated_break_regex
+            .captures_iter(text)
+            .map(|caps| {
+                let (hyphen, right) = (caps.get(2).unwrap(), caps.get(3).unwrap());
+                let replacement = if joins_hyphenated_word(&caps[1], hyphen.as_str(), right.as_str(), &context) {
+                    ""
+                } else {
+                    "-"
+                };
+                (hyphen.start()..right.start(), replacement)
+            })
+            .collect()
+    }
+}
+
+/// Left fragments that usually start a hyphenated compound rather than a broken word
+const COMPOUND_PREFIXES: [&str; 7] = ["all", "cross", "ex", "half", "quasi", "self", "well"];
+
+/// Decide whether `left` + hyphen + line break + `right` is one word broken by
+/// hyphenation (true) or a genuine hyphenated compound (false)
+/// 
+/// Rules, in order:
+/// 1. A soft hyphen only ever marks a discretionary break
+/// 2. Continuations that do not start lowercase are compounds (`Jean-Paul`)
+/// 3. Acronym-like left parts are compounds (`HTTP-based`)
+/// 4. `context` (the surrounding document text, lowercased) writing the compound
+///    with its hyphen, or the word without one, settles it
+/// 5. Common compound prefixes (`self-`, `well-`, ...) keep the hyphen
+/// 6. Anything else is treated as a broken word
+fn joins_hyphenated_word(left: &str, hyphen: &str, right: &str, context: &str) -> bool {
+    if hyphen == "\u{00AD}" {
+        return true;
+    }
+    if !right.chars().next().is_some_and(char::is_lowercase) {
+        return false;
+    }
+    if left.chars().skip(1).any(char::is_uppercase) {
+        return false;
+    }
+    let (left, right) = (left.to_lowercase(), right.to_lowercase());
+    if context.contains(&format!("{}-{}", left, right)) {
+        return false;
+    }
+    if context.contains(&format!("{}{}", left, right)) {
+        return true;
+    }
+    !COMPOUND_PREFIXES.contains(&left.as_str())
+}
+
+/// Rejoin words hyphenated across page breaks (document-level de-hyphenation)
+/// 
+/// When a page ends in `infor-` and the next physical page starts with `mation`,
+/// the continuation is moved onto the earlier page (dropping the hyphen unless the
+/// word is a compound) so the word is chunked whole. Moved characters keep no
+/// bounding boxes, since they are printed on the following page.
+pub fn dehyphenate_page_breaks(pages: &mut [PageText]) {
+    for i in 1..pages.len() {
+        let (before, after) = pages.split_at_mut(i);
+        let (previous, next) = (&mut before[i - 1], &mut after[0]);
+        if next.page_index != previous.page_index + 1 {
+            continue;
+        }
+        
+        // Trailing `letters` + hyphen on the earlier page
+        let Some(hyphen) = previous.text.chars().next_back().filter(|c| matches!(c, '-' | '\u{00AD}' | '\u{2010}')) else {
+            continue;
+        };
+        let hyphen_start = previous.text.len() - hyphen.len_utf8();
+        let left_start = previous.text[..hyphen_start]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphabetic())
+            .last()
+            .map(|(idx, _)| idx);
+        let Some(left_start) = left_start else {
+            continue;
+        };
+        
+        // Leading word on the next page: its letters decide, the whole
+        // whitespace-delimited token (with trailing punctuation) moves
+        let word_end = next.text.find(char::is_whitespace).unwrap_or(next.text.len());
+        let letters_end = next.text.find(|c: char| !c.is_alphabetic()).unwrap_or(next.text.len());
+        if letters_end == 0 {
+            continue;
+        }
+        
+        let context = format!("{}\n{}", previous.text, next.text).to_lowercase();
+        let join = joins_hyphenated_word(
+            &previous.text[left_start..hyphen_start],
+            &previous.text[hyphen_start..],
+            &next.text[..letters_end],
+            &context,
+        );
+        
+        if join {
+            previous.text.truncate(hyphen_start);
+            previous.char_boxes.retain(|b| b.offset < hyphen_start);
+        }
+        previous.text.push_str(&next.text[..word_end]);
+        
+        let cut = next.text.len() - next.text[word_end..].trim_start().len();
+        next.text.drain(..cut);
+        next.char_boxes.retain(|b| b.offset >= cut);
+        for char_box in &mut next.char_boxes {
+            char_box.offset -= cut;
+        }
+        
+        debug!("Rejoined word across pages {} and {}", previous.page_index, next.page_index);
+    }
+}
+
+/// Replace every regex match with `replacement`, carrying the offset map along
+/// 
+/// Replacement bytes map to the start of the match they replaced.
+fn replace_all_mapped(
+    regex: &Regex,
+    text: &str,
+    offsets: &[usize],
+    replacement: &str,
+) -> (String, Vec<usize>) {
+    let edits: Vec<(Range<usize>, &str)> = regex
+        .find_iter(text)
+        .map(|mat| (mat.range(), replacement))
+        .collect();
+    splice_mapped(text, offsets, &edits)
+}
+
+/// Replace sorted, non-overlapping byte ranges of `text`, carrying the offset map along
+/// 
+/// Replacement bytes map to the start of the range they replaced.
+fn splice_mapped(text: &str, offsets: &[usize], edits: &[(Range<usize>, &str)]) -> (String, Vec<usize>) {
+    let mut result = String::with_capacity(text.len());
+    let mut result_offsets = Vec::with_capacity(offsets.len());
+    let mut last = 0;
+    
+    for (range, replacement) in edits {
+        result.push_str(&text[last..range.start]);
+        result_offsets.extend_from_slice(&offsets[last..range.start]);
+        result.push_str(replacement);
+        result_offsets.extend(std::iter::repeat_n(offsets[range.start], replacement.len()));
+        last = range.end;
+    }
+    
+    result.push_str(&text[last..]);
+    result_offsets.extend_from_slice(&offsets[last..]);
+    (result, result_offsets)
+}
+
+/// Apply a Unicode normalization form, carrying the offset map along
+/// 
+/// Text is normalized one segment at a time, split before every character that
+/// nothing can compose with from the left (combining class 0 and quick check Yes
+/// for the form), so every output byte maps to the start of the segment it came
+/// from. A segment holds a starter plus the marks and conjoining characters (such
+/// as Hangul jamo) that may compose with it. Segments that are already normalized
+/// keep their byte-exact offsets.
+fn normalize_mapped(text: &str, offsets: &[usize], form: UnicodeForm) -> (String, Vec<usize>) {
+    let quick = match form {
+        UnicodeForm::None => IsNormalized::Yes,
+        UnicodeForm::Nfc => is_nfc_quick(text.chars()),
+        UnicodeForm::Nfkc => is_nfkc_quick(text.chars()),
+    };
+    if quick == IsNormalized::Yes {
+        return (text.to_string(), offsets.to_vec());
+    }
+    
+    let mut result = String::with_capacity(text.len());
+    let mut result_offsets = Vec::with_capacity(offsets.len());
+    let mut push_sequence = |start: usize, end: usize| {
+        let sequence = &text[start..end];
+        let normalized: String = match form {
+            UnicodeForm::Nfkc => sequence.nfkc().collect(),
+            _ => sequence.nfc().collect(),
+        };
+        if normalized == sequence {
+            result_offsets.extend_from_slice(&offsets[start..end]);
+        } else {
+            result_offsets.extend(std::iter::repeat_n(offsets[start], normalized.len()));
+        }
+        result.push_str(&normalized);
+    };
+    
+    let is_boundary = |c: char| {
+        let quick = match form {
+            UnicodeForm::Nfkc => is_nfkc_quick(std::iter::once(c)),
+            _ => is_nfc_quick(std::iter::once(c)),
+        };
+        canonical_combining_class(c) == 0 && quick == IsNormalized::Yes
+    };
+    
+    let mut sequence_start = 0;
+    for (idx, c) in text.char_indices() {
+        if idx > sequence_start && is_boundary(c) {
+            push_sequence(sequence_start, idx);
+            sequence_start = idx;
+        }
+    }
+    push_sequence(sequence_start, text.len());
+    
+    result_offsets.push(offsets[text.len()]);
+    (result, result_offsets)
+}
+
+/// Replace single characters via `fold` (None keeps the character), carrying the
+/// offset map along
+/// 
+/// Replacement bytes map to the character they replaced.
+fn fold_chars_mapped<'a>(
+    text: &str,
+    offsets: &[usize],
+    fold: impl Fn(char) -> Option<&'a str>,
+) -> (String, Vec<usize>) {
+    let mut result = String::with_capacity(text.len());
+    let mut result_offsets = Vec::with_capacity(offsets.len());
+    
+    for (idx, c) in text.char_indices() {
+        match fold(c) {
+            Some(replacement) => {
+                result.push_str(replacement);
+                result_offsets.extend(std::iter::repeat_n(offsets[idx], replacement.len()));
+            }
+            None => {
+                result.push(c);
+                result_offsets.extend_from_slice(&offsets[idx..idx + c.len_utf8()]);
+            }
+        }
+    }
+    
+    result_offsets.push(offsets[text.len()]);
+    (result, result_offsets)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
+    
     #[test]
     fn test_excessive_newlines() {
         let preprocessor = TextPreprocessor::new();
@@ -60,7 +444,7 @@ mod tests {
         let result = preprocessor.preprocess(input);
         assert_eq!(result, "Line 1\n\nLine 2\n\nLine 3");
     }
-
+    
     #[test]
     fn test_whitespace_cleanup() {
         let preprocessor = TextPreprocessor::new();
@@ -68,4 +452,105 @@ mod tests {
         let result = preprocessor.preprocess(input);
         assert_eq!(result, "Word1 Word2 Word3 Word4");
     }
+    
+    #[test]
+    fn test_offset_map() {
+        let preprocessor = TextPreprocessor::new();
+        let input = "  Word1    Word2\n\n\n\nWord3";
+        let (result, offsets) = preprocessor.preprocess_with_offsets(input);
+        assert_eq!(result, "Word1 Word2\n\nWord3");
+        assert_eq!(offsets.len(), result.len() + 1);
+        
+        // Every cleaned word maps back onto the same word in the input
+        for word in ["Word1", "Word2", "Word3"] {
+            let start = result.find(word).unwrap();
+            let end = start + word.len();
+            assert_eq!(&input[offsets[start]..offsets[end]], word);
+        }
+    }
+    
+    #[test]
+    fn test_typographic_normalization() {
+        let preprocessor = TextPreprocessor::new();
+        let input = "The \u{FB01}rst \u{201C}e\u{FB00}ective\u{201D} pre\u{00AD}view\u{00A0}\u{2014} caf\u{0065}\u{0301}\u{200B}!";
+        let (result, offsets) = preprocessor.preprocess_with_offsets(input);
+        assert_eq!(result, "The first \"effective\" preview - caf\u{00E9}!");
+        assert_eq!(offsets.len(), result.len() + 1);
+        
+        // Expanded text still maps back onto the original characters
+        let start = result.find("first").unwrap();
+        assert_eq!(&input[offsets[start]..offsets[start + "first".len()]], "\u{FB01}rst");
+        let start = result.find("preview").unwrap();
+        assert_eq!(&input[offsets[start]..offsets[start + "preview".len()]], "pre\u{00AD}view");
+    }
+    
+    #[test]
+    fn test_normalization_toggles() {
+        let input = "\u{FF21}\u{FF22}\u{FF23} \u{FB02}ow \u{2018}x\u{2019}";
+        
+        let nfkc = TextPreprocessor::new().with_normalization(NormalizationOptions {
+            unicode_form: UnicodeForm::Nfkc,
+            fold_quotes: false,
+            ..Default::default()
+        });
+        assert_eq!(nfkc.preprocess(input), "ABC flow \u{2018}x\u{2019}");
+        
+        let disabled = TextPreprocessor::new().with_normalization(NormalizationOptions {
+            unicode_form: UnicodeForm::None,
+            expand_ligatures: false,
+            fold_quotes: false,
+            fold_dashes: false,
+            remove_invisible: false,
+            normalize_spaces: false,
+            dehyphenate: false,
+        });
+        assert_eq!(disabled.preprocess(input), input);
+        
+        // Conjoining jamo are all starters but still compose into one syllable
+        let nfc = TextPreprocessor::new();
+        let (result, offsets) = nfc.preprocess_with_offsets("x \u{1100}\u{1161}\u{11A8} y");
+        assert_eq!(result, "x \u{AC01} y");
+        assert_eq!(offsets[2..6], [2, 2, 2, 11]);
+        
+        assert_eq!("NFKC".parse::<UnicodeForm>().unwrap(), UnicodeForm::Nfkc);
+        assert!("nfd".parse::<UnicodeForm>().is_err());
+    }
+    
+    #[test]
+    fn test_dehyphenation() {
+        let preprocessor = TextPreprocessor::new();
+        let input = "The informa-\ntion was self-\naware, per Jean-\nPaul.\nA well-\nknown re-\nsult.";
+        let (result, offsets) = preprocessor.preprocess_with_offsets(input);
+        assert_eq!(result, "The information was self-aware, per Jean-Paul.\nA well-known result.");
+        
+        let start = result.find("information").unwrap();
+        assert_eq!(&input[offsets[start]..offsets[start + "information".len()]], "informa-\ntion");
+        
+        // A compound spelled with a hyphen elsewhere keeps it; a word spelled whole is joined
+        assert_eq!(preprocessor.preprocess("co-operate and co-\noperate"), "co-operate and co-operate");
+        assert_eq!(preprocessor.preprocess("Selfish and self-\nish"), "Selfish and selfish");
+    }
+    
+    #[test]
+    fn test_page_break_dehyphenation() {
+        let page = |page_index: usize, text: &str| PageText {
+            page_index,
+            page_label: None,
+            text: text.to_string(),
+            char_boxes: Vec::new(),
+        };
+        let mut pages = vec![
+            page(0, "the in-"),
+            page(1, "formation. Next page"),
+            page(3, "skipped page-"),
+            page(5, "break"),
+        ];
+        dehyphenate_page_breaks(&mut pages);
+        
+        assert_eq!(pages[0].text, "the information.");
+        assert_eq!(pages[1].text, "Next page");
+        // Pages that are not physically adjacent are left alone
+        assert_eq!(pages[2].text, "skipped page-");
+        assert_eq!(pages[3].text, "break");
+    }
 }
@@ -1,22 +1,29 @@
-use std::collections::HashSet;
-use std::num::NonZeroU64;
-use std::thread;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use fancy_regex::Regex;
+use once_cell::sync::OnceCell;
 use rustc_hash::FxHashMap as HashMap;
+use crate::bpe_cache::BpeCache;
 use crate::error::ProcessingError;
-use crate::o200k_vocab::{load_o200k_base_encoder, load_o200k_base_special_tokens, O200K_BASE_PATTERN};
+use crate::encodings::{encoding_for_model, EncodingKind};
+use crate::vocab_loader::{index_vocabulary, load_tiktoken_file};
+use crate::vocabulary::TokenTable;
 use log::{error, info};
 
 pub type Rank = u32;
 
 /// Core BPE byte pair merge algorithm
-fn _byte_pair_merge(ranks: &HashMap<Vec<u8>, Rank>, piece: &[u8]) -> Vec<(usize, Rank)> {
+fn _byte_pair_merge(ranks: &TokenTable, piece: &[u8]) -> Vec<(usize, Rank)> {
     let mut parts = Vec::with_capacity(piece.len() + 1);
 
     let mut min_rank: (Rank, usize) = (Rank::MAX, usize::MAX);
     for i in 0..piece.len() - 1 {
-        let rank = *ranks.get(&piece[i..i + 2]).unwrap_or(&Rank::MAX);
+        let rank = ranks.rank(&piece[i..i + 2]).unwrap_or(Rank::MAX);
         if rank < min_rank.0 {
             min_rank = (rank, i);
         }
@@ -29,9 +36,9 @@ fn _byte_pair_merge(ranks: &HashMap<Vec<u8>, Rank>, piece: &[u8]) -> Vec<(usize,
         #[inline(always)]
         |parts: &Vec<(usize, Rank)>, i: usize| {
             if (i + 3) < parts.len() {
-                *ranks
-                    .get(&piece[parts[i].0..parts[i + 3].0])
-                    .unwrap_or(&Rank::MAX)
+                ranks
+                    .rank(&piece[parts[i].0..parts[i + 3].0])
+                    .unwrap_or(Rank::MAX)
             } else {
                 Rank::MAX
             }
@@ -56,74 +63,235 @@ fn _byte_pair_merge(ranks: &HashMap<Vec<u8>, Rank>, piece: &[u8]) -> Vec<(usize,
     parts
 }
 
+/// Pieces at least this long are merged with [`byte_pair_merge_large`]
+const LARGE_PIECE_LEN: usize = 128;
+
+/// BPE merge for long pieces in O(n log n) instead of the O(n²) of `_byte_pair_merge`
+/// 
+/// Same merge order (lowest rank first, leftmost on ties): candidate pairs wait in a
+/// min-heap keyed by `(rank, start)` and parts form a linked list of start offsets.
+/// Entries whose pair has changed since they were pushed are skipped when popped.
+/// Returns the part boundaries (the start of every part, then `piece.len()`).
+fn byte_pair_merge_large(ranks: &TokenTable, piece: &[u8]) -> Vec<usize> {
+    let len = piece.len();
+    // next[start] / prev[start]: neighbouring part starts (`len` marks the end)
+    let mut next: Vec<usize> = (1..=len).collect();
+    let mut prev: Vec<usize> = (0..len).map(|start| start.wrapping_sub(1)).collect();
+    let mut alive = vec![true; len];
+    
+    // Candidate pair starting at `start`, as (rank, start, end of the pair)
+    let pair = |next: &[usize], start: usize| -> Option<Reverse<(Rank, usize, usize)>> {
+        let end = *next.get(next[start])?;
+        ranks.rank(&piece[start..end]).map(|rank| Reverse((rank, start, end)))
+    };
+    let mut heap: BinaryHeap<_> = (0..len - 1).filter_map(|start| pair(&next, start)).collect();
+    
+    while let Some(Reverse((_, start, end))) = heap.pop() {
+        if !alive[start] || next.get(next[start]) != Some(&end) {
+            continue; // Stale: one of the two parts was merged since
+        }
+        let second = next[start];
+        alive[second] = false;
+        next[start] = end;
+        if end < len {
+            prev[end] = start;
+        }
+        heap.extend(pair(&next, start));
+        if start > 0 {
+            heap.extend(pair(&next, prev[start]));
+        }
+    }
+    
+    let mut boundaries = vec![0];
+    let mut start = 0;
+    while start < len {
+        start = next[start];
+        boundaries.push(start);
+    }
+    boundaries
+}
+
 /// Convert text piece into tokens using BPE
-pub fn byte_pair_encode(piece: &[u8], ranks: &HashMap<Vec<u8>, Rank>) -> Vec<Rank> {
+pub fn byte_pair_encode(piece: &[u8], ranks: &TokenTable) -> Vec<Rank> {
+    let rank_of = |part: &[u8]| ranks.rank(part).expect("BPE merges only produce tokens of the vocabulary");
     if piece.len() == 1 {
-        return vec![ranks[piece]];
+        return vec![rank_of(piece)];
+    }
+    if piece.len() >= LARGE_PIECE_LEN {
+        return byte_pair_merge_large(ranks, piece)
+            .windows(2)
+            .map(|part| rank_of(&piece[part[0]..part[1]]))
+            .collect();
     }
     _byte_pair_merge(ranks, piece)
         .windows(2)
-        .map(|part| ranks[&piece[part[0].0..part[1].0]])
+        .map(|part| rank_of(&piece[part[0].0..part[1].0]))
         .collect()
 }
 
-/// Thread-safe hash for thread-local regex storage
-struct FakeThreadId(NonZeroU64);
+/// Convert the byte ranges of `encode_with_offsets` to character (`char`) offsets
+/// 
+/// A token that starts inside a character starts at that character, and one that
+/// ends inside a character includes it, so tokens splitting a character overlap by
+/// that character instead of getting an empty or misplaced range.
+pub fn to_char_offsets(text: &str, tokens: &[(Rank, Range<usize>)]) -> Vec<(Rank, Range<usize>)> {
+    // chars_before[b] = number of characters starting before byte b
+    let mut chars_before = Vec::with_capacity(text.len() + 1);
+    let mut count = 0;
+    for &byte in text.as_bytes() {
+        chars_before.push(count);
+        if byte & 0xC0 != 0x80 { // Not a continuation byte
+            count += 1;
+        }
+    }
+    chars_before.push(count);
+    
+    tokens
+        .iter()
+        .map(|(token, range)| {
+            let start = if range.start < text.len() { chars_before[range.start + 1] - 1 } else { count };
+            (*token, start..chars_before[range.end])
+        })
+        .collect()
+}
 
-fn hash_current_thread() -> usize {
-    const _: [u8; 8] = [0; std::mem::size_of::<std::thread::ThreadId>()];
-    const _: [u8; 8] = [0; std::mem::size_of::<FakeThreadId>()];
-    let x = unsafe {
-        std::mem::transmute::<std::thread::ThreadId, FakeThreadId>(thread::current().id()).0
-    };
-    u64::from(x) as usize
+/// Token counts of a text per regex piece, for counting concatenations incrementally
+/// 
+/// Encoding is local to the pieces the pattern splits text into, so joining two
+/// texts only changes the pieces around the join: [`CoreBPE::join_piece_counts`]
+/// re-encodes those and reuses the counts of all others.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PieceCounts {
+    len: usize,                  // Byte length of the text
+    pieces: Vec<(usize, usize)>, // (end offset, tokens up to and including the piece) per piece
 }
 
-const MAX_NUM_THREADS: usize = 128;
+impl PieceCounts {
+    /// Tokens of the whole text (same as `encode_ordinary(text).len()`)
+    pub fn token_count(&self) -> usize {
+        self.pieces.last().map_or(0, |&(_, tokens)| tokens)
+    }
+}
+
+/// Pieces kept in the BPE cache of each tokenizer (at most twice as many are held)
+const BPE_CACHE_CAPACITY: usize = 32 * 1024;
+
+/// Upper bound on the regex clones of a [`RegexPool`]
+const MAX_REGEX_SLOTS: usize = 32;
+
+/// Small number of regex clones, one per thread slot
+/// 
+/// A single regex shared by all threads is correct but slow: the threads that do
+/// not own its internal search cache borrow one from mutex-guarded stacks on every
+/// search (`benchmark_shared_regex` measures it at about half the throughput of
+/// per-thread clones). The pool holds two clones per core (capped at
+/// [`MAX_REGEX_SLOTS`]) and each thread keeps the slot it got on first use, so the
+/// caller and the threads of a rayon pool rarely share one. Sharing a slot is
+/// still correct, only slower.
+struct RegexPool {
+    regexes: Vec<Regex>,
+}
+
+impl RegexPool {
+    fn new(regex: Regex) -> Self {
+        let slots = std::thread::available_parallelism().map_or(1, |n| n.get()) * 2;
+        let slots = slots.clamp(2, MAX_REGEX_SLOTS);
+        // Each clone gets its own search caches
+        Self { regexes: vec![regex; slots] }
+    }
+    
+    /// Regex of the current thread's slot
+    fn get(&self) -> &Regex {
+        static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+        thread_local! {
+            static SLOT: usize = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+        }
+        &self.regexes[SLOT.with(|slot| *slot) % self.regexes.len()]
+    }
+}
 
 /// Core BPE tokenizer implementation for o200k_base
 /// 
 /// This is a fast, thread-safe implementation of OpenAI's tiktoken core
 /// optimized specifically for chunking operations in PDF processing
-#[derive(Clone)]
+/// 
+/// An instance holds the full vocabulary in both directions and a cache of merged
+/// pieces, so it is deliberately not `Clone`: share it with `Arc` (see [`CoreBPE::shared`])
+/// and lend `&CoreBPE` to the components that tokenize.
+/// 
+/// The regexes are shared by all threads. fancy_regex allocates the backtracking
+/// state of its VM per search, and the regex-automata searches it delegates to take
+/// their cache from a pool: the first thread to search owns one without locking,
+/// other threads borrow one from a few mutex-guarded stacks for each search.
+/// `benchmark_shared_regex` compares this with one clone per thread.
 pub struct CoreBPE {
-    encoder: HashMap<Vec<u8>, Rank>,
+    tokens: TokenTable, // Ordinary tokens, looked up by bytes (encode) and by rank (decode)
     special_tokens_encoder: HashMap<String, Rank>,
-    decoder: HashMap<Rank, Vec<u8>>,
     special_tokens_decoder: HashMap<Rank, Vec<u8>>,
-    regex_tls: Vec<Regex>,
-    special_regex_tls: Vec<Regex>,
-    #[allow(dead_code)]
-    sorted_token_bytes: Vec<Vec<u8>>,
+    regex: RegexPool,
+    special_regex: RegexPool,
+    cache: BpeCache, // Tokens of recently merged pieces
 }
 
 impl CoreBPE {
-    /// Get thread-local regex for performance
-    fn _get_tl_regex(&self) -> &Regex {
-        &self.regex_tls[hash_current_thread() % MAX_NUM_THREADS]
-    }
-
-    fn _get_tl_special_regex(&self) -> &Regex {
-        &self.special_regex_tls[hash_current_thread() % MAX_NUM_THREADS]
-    }
-
     /// Create new CoreBPE instance with real o200k_base configuration
+    #[allow(dead_code)]
     pub fn new_o200k_base() -> Result<Self, ProcessingError> {
-        info!("Initializing tiktoken o200k_base tokenizer with real vocabulary...");
+        Self::for_encoding(EncodingKind::O200kBase)
+    }
+    
+    /// Process-wide tokenizer of an encoding, built on first use and shared afterwards
+    /// 
+    /// Every chunker of the process uses the same instance, so the vocabulary is
+    /// loaded once (from `MYRUSTCHUNKER_VOCAB_DIR` or the embedded data, whichever
+    /// applied at that moment) and never copied.
+    pub fn shared(kind: EncodingKind) -> Result<Arc<Self>, ProcessingError> {
+        static SHARED: [OnceCell<Arc<CoreBPE>>; EncodingKind::ALL.len()] = [const { OnceCell::new() }; EncodingKind::ALL.len()];
+        
+        SHARED[kind as usize]
+            .get_or_try_init(|| Self::for_encoding(kind).map(Arc::new))
+            .cloned()
+    }
+    
+    /// Create a tokenizer for one of the registered encodings
+    pub fn for_encoding(kind: EncodingKind) -> Result<Self, ProcessingError> {
+        info!("Initializing tiktoken {} tokenizer with real vocabulary...", kind);
         
-        // Load real o200k_base vocabulary and special tokens
-        let encoder = load_o200k_base_encoder()?;
-        let special_tokens_encoder = load_o200k_base_special_tokens();
+        let tokens = kind.load_token_table()?;
+        let special_tokens_encoder = kind.special_tokens();
         
         info!("Loaded {} regular tokens and {} special tokens", 
-              encoder.len(), special_tokens_encoder.len());
+              tokens.len(), special_tokens_encoder.len());
         
-        Self::new_internal(encoder, special_tokens_encoder, O200K_BASE_PATTERN)
+        Self::new_internal(tokens, special_tokens_encoder, kind.pattern())
+    }
+    
+    /// Create a tokenizer from an encoding name ("o200k_base", "cl100k_base", "p50k_base", "r50k_base")
+    #[allow(dead_code)] // Public API; the pipeline selects encodings by kind
+    pub fn from_name(name: &str) -> Result<Self, ProcessingError> {
+        Self::for_encoding(name.parse()?)
+    }
+    
+    /// Create the tokenizer an OpenAI model uses ("gpt-4o", "gpt-4", "text-embedding-3-small", ...)
+    #[allow(dead_code)]
+    pub fn for_model(model: &str) -> Result<Self, ProcessingError> {
+        Self::for_encoding(encoding_for_model(model)?)
+    }
+    
+    /// Create a tokenizer from a `.tiktoken` rank file with the pattern and special
+    /// tokens of `kind` (custom or fine-tuned vocabularies)
+    /// 
+    /// Fails with the offending line for malformed files, and when a special token
+    /// rank of `kind` is also an ordinary rank of the file.
+    pub fn from_tiktoken_file(path: &Path, kind: EncodingKind) -> Result<Self, ProcessingError> {
+        info!("Initializing {} tokenizer from {}", kind, path.display());
+        let tokens = index_vocabulary(load_tiktoken_file(path)?)?;
+        Self::new_internal(tokens, kind.special_tokens(), kind.pattern())
     }
 
     /// Internal constructor
     fn new_internal(
-        encoder: HashMap<Vec<u8>, Rank>,
+        tokens: TokenTable,
         special_tokens_encoder: HashMap<String, Rank>,
         pattern: &str,
     ) -> Result<Self, ProcessingError> {
@@ -139,67 +307,69 @@ impl CoreBPE {
                 .map_err(|e| ProcessingError::SystemError(format!("Special regex compilation failed: {}", e)))?
         };
 
-        let decoder: HashMap<Rank, Vec<u8>> =
-            encoder.iter().map(|(k, v)| (*v, k.clone())).collect();
-
-        if encoder.len() != decoder.len() {
-            return Err(ProcessingError::SystemError(
-                "Encoder and decoder must be of equal length".to_string()
-            ));
+        // A special token sharing a rank with an ordinary token would make decode ambiguous
+        if let Some((token, rank)) = special_tokens_encoder.iter().find(|(_, rank)| tokens.token(**rank).is_some()) {
+            return Err(ProcessingError::SystemError(format!(
+                "Special token {} has rank {}, which is already an ordinary token", token, rank
+            )));
         }
-
+        
         let special_tokens_decoder: HashMap<Rank, Vec<u8>> = special_tokens_encoder
             .iter()
             .map(|(k, v)| (*v, k.as_bytes().to_vec()))
             .collect();
 
-        let mut sorted_token_bytes: Vec<Vec<u8>> = encoder.keys().cloned().collect();
-        sorted_token_bytes.sort();
-
         Ok(Self {
-            encoder,
+            tokens,
             special_tokens_encoder,
-            decoder,
             special_tokens_decoder,
-            regex_tls: (0..MAX_NUM_THREADS).map(|_| regex.clone()).collect(),
-            special_regex_tls: (0..MAX_NUM_THREADS)
-                .map(|_| special_regex.clone())
-                .collect(),
-            sorted_token_bytes,
+            regex: RegexPool::new(regex),
+            special_regex: RegexPool::new(special_regex),
+            cache: BpeCache::new(BPE_CACHE_CAPACITY),
         })
     }
 
     /// Encode text to tokens (main entry point)
     pub fn encode_ordinary(&self, text: &str) -> Vec<Rank> {
-        let regex = self._get_tl_regex();
         let mut ret = vec![];
+        for piece in self.pieces(text) {
+            self.encode_piece(piece.as_str().as_bytes(), &mut ret);
+        }
+        ret
+    }
+
+    /// Encode text like `encode_ordinary`, with the byte range of `text` each token covers
+    /// 
+    /// Ranges come from the regex piece positions and the byte lengths of the tokens
+    /// of each piece, so they are in order and `text[range]` concatenates to `text`.
+    /// Byte-level tokens can split a multi-byte character: such a range does not lie on
+    /// `char` boundaries; [`to_char_offsets`] converts the ranges to character offsets.
+    pub fn encode_with_offsets(&self, text: &str) -> Vec<(Rank, Range<usize>)> {
+        let mut ret = vec![];
+        let mut piece_tokens = vec![];
         
-        for mat in regex.find_iter(text) {
-            let piece = match mat {
-                Ok(m) => m.as_str().as_bytes(),
-                Err(e) => {
-                    error!("Regex match error: {}", e);
-                    continue;
-                }
-            };
+        for piece in self.pieces(text) {
+            piece_tokens.clear();
+            self.encode_piece(piece.as_str().as_bytes(), &mut piece_tokens);
             
-            match self.encoder.get(piece) {
-                Some(token) => ret.push(*token),
-                None => ret.extend(&byte_pair_encode(piece, &self.encoder)),
+            let mut start = piece.start();
+            for &token in &piece_tokens {
+                let end = start + self.token_byte_len(token);
+                ret.push((token, start..end));
+                start = end;
             }
         }
         ret
     }
 
     /// Encode with special tokens support
-    #[allow(dead_code)]
     pub fn encode(
         &self,
         text: &str,
         allowed_special: &HashSet<&str>,
     ) -> Result<Vec<Rank>, ProcessingError> {
-        let special_regex = self._get_tl_special_regex();
-        let regex = self._get_tl_regex();
+        let special_regex = self.special_regex.get();
+        let regex = self.regex.get();
         let mut ret = vec![];
 
         let mut start = 0;
@@ -225,12 +395,7 @@ impl CoreBPE {
                 let mat = mat_res
                     .map_err(|e| ProcessingError::SystemError(format!("Regex error: {}", e)))?;
 
-                let piece = mat.as_str().as_bytes();
-                if let Some(token) = self.encoder.get(piece) {
-                    ret.push(*token);
-                    continue;
-                }
-                ret.extend(&byte_pair_encode(piece, &self.encoder));
+                self.encode_piece(mat.as_str().as_bytes(), &mut ret);
             }
 
             match next_special {
@@ -247,33 +412,206 @@ impl CoreBPE {
         Ok(ret)
     }
 
+    /// Regex pieces of `text` (matching errors are logged and skipped, as in `encode_ordinary`)
+    fn pieces<'a>(&'a self, text: &'a str) -> impl Iterator<Item = fancy_regex::Match<'a>> + 'a {
+        self.regex.get().find_iter(text).filter_map(|mat| {
+            mat.map_err(|e| error!("Regex match error: {}", e)).ok()
+        })
+    }
+    
+    /// Append the tokens of one regex piece: a single token, cached, or merged
+    fn encode_piece(&self, piece: &[u8], out: &mut Vec<Rank>) {
+        if let Some(token) = self.tokens.rank(piece) {
+            out.push(token);
+        } else if self.cache.get_with(piece, |tokens| out.extend_from_slice(tokens)).is_none() {
+            let tokens = byte_pair_encode(piece, &self.tokens);
+            self.cache.insert(piece, &tokens);
+            out.extend_from_slice(&tokens);
+        }
+    }
+    
+    /// Number of tokens of one regex piece, without building its token vector when
+    /// it is a single token or cached
+    fn piece_token_count(&self, piece: &[u8]) -> usize {
+        if self.tokens.rank(piece).is_some() {
+            return 1;
+        }
+        if let Some(count) = self.cache.get_with(piece, <[Rank]>::len) {
+            return count;
+        }
+        let tokens = byte_pair_encode(piece, &self.tokens);
+        self.cache.insert(piece, &tokens);
+        tokens.len()
+    }
+    
+    /// Per-piece token counts of `text` (one encoding pass)
+    pub fn piece_counts(&self, text: &str) -> PieceCounts {
+        let mut tokens = 0;
+        let pieces = self
+            .pieces(text)
+            .map(|piece| {
+                tokens += self.piece_token_count(piece.as_str().as_bytes());
+                (piece.end(), tokens)
+            })
+            .collect();
+        PieceCounts { len: text.len(), pieces }
+    }
+    
+    /// Per-piece token counts of `joined`, which is the text of `left`, any separator,
+    /// then the text of `right`, re-encoding only the pieces around the join
+    /// 
+    /// Process:
+    /// 1. Keep the counts of `left` before its last two pieces (the end of a text can
+    ///    merge with what follows it, e.g. "abc'r" + "e" splits as "abc're")
+    /// 2. Re-encode `joined` from there until a piece ends where a piece of `right`
+    ///    ends; the patterns have no look-behind, so the split is the same from there on
+    /// 3. Reuse the counts of the remaining pieces of `right`
+    pub fn join_piece_counts(&self, joined: &str, left: &PieceCounts, right: &PieceCounts) -> PieceCounts {
+        debug_assert!(joined.len() >= left.len + right.len);
+        let right_start = joined.len() - right.len;
+        let keep = left.pieces.len().saturating_sub(2);
+        let (window_start, mut tokens) = keep.checked_sub(1).map_or((0, 0), |last| left.pieces[last]);
+        let mut pieces = left.pieces[..keep].to_vec();
+        
+        let mut next_right = 0; // First piece of `right` not ending before the current piece
+        for piece in self.pieces(&joined[window_start..]) {
+            let end = window_start + piece.end();
+            tokens += self.piece_token_count(piece.as_str().as_bytes());
+            pieces.push((end, tokens));
+            if end < right_start {
+                continue;
+            }
+            
+            // In sync when this piece ends at the start of `right` or where one of its pieces ends
+            let end_in_right = end - right_start;
+            while right.pieces.get(next_right).is_some_and(|&(right_end, _)| right_end < end_in_right) {
+                next_right += 1;
+            }
+            let synced_from = match right.pieces.get(next_right) {
+                _ if end_in_right == 0 => Some((0, 0)),
+                Some(&(right_end, right_tokens)) if right_end == end_in_right => Some((next_right + 1, right_tokens)),
+                _ => None,
+            };
+            if let Some((first, right_tokens)) = synced_from {
+                pieces.extend(right.pieces[first..].iter().map(|&(right_end, right_total)| {
+                    (right_start + right_end, tokens + right_total - right_tokens)
+                }));
+                break;
+            }
+        }
+        PieceCounts { len: joined.len(), pieces }
+    }
+    
+    /// The last tokens of `text`, at least `min_tokens` of them when it has that many,
+    /// encoding only the pieces that hold them (`pieces` are the counts of `text`)
+    /// 
+    /// The result is a suffix of `encode_ordinary(text)`.
+    pub fn encode_suffix(&self, text: &str, pieces: &PieceCounts, min_tokens: usize) -> Vec<Rank> {
+        let total = pieces.token_count();
+        let start = pieces.pieces
+            .iter()
+            .rev()
+            .find(|&&(_, tokens)| total - tokens >= min_tokens)
+            .map_or(0, |&(end, _)| end);
+        self.encode_ordinary(&text[start..])
+    }
+    
     /// Count tokens in text - optimized for chunking
-    #[allow(dead_code)]
+    /// 
+    /// Same result as `encode_ordinary(text).len()`, summing piece counts instead of
+    /// collecting the tokens.
     pub fn count_tokens(&self, text: &str) -> Result<usize, ProcessingError> {
-        let tokens = self.encode_ordinary(text);
-        Ok(tokens.len())
+        Ok(self.pieces(text).map(|piece| self.piece_token_count(piece.as_str().as_bytes())).sum())
     }
 
-    /// Decode tokens back to text
+    /// Decode tokens back to text (fails when the tokens split a character, see
+    /// [`decode_lossy`](Self::decode_lossy) and [`floor_char_boundary`](Self::floor_char_boundary))
+    #[allow(dead_code)] // Public API; the chunkers decode character-aligned windows lossily
     pub fn decode(&self, tokens: &[Rank]) -> Result<String, ProcessingError> {
+        String::from_utf8(self.decode_bytes(tokens)?)
+            .map_err(|e| ProcessingError::SystemError(format!("UTF-8 decode error: {}", e)))
+    }
+    
+    /// Decode tokens to their raw bytes (not necessarily valid UTF-8 when a
+    /// character is split across tokens)
+    pub fn decode_bytes(&self, tokens: &[Rank]) -> Result<Vec<u8>, ProcessingError> {
         let mut ret = Vec::with_capacity(tokens.len() * 2);
         for &token in tokens {
-            let token_bytes = match self.decoder.get(&token) {
-                Some(bytes) => bytes,
-                None => self
-                    .special_tokens_decoder
-                    .get(&token)
-                    .ok_or_else(|| ProcessingError::SystemError(format!("Invalid token: {}", token)))?,
-            };
+            let token_bytes = self.token_bytes(token)
+                .ok_or_else(|| ProcessingError::InvalidArgument(format!("Invalid token: {}", token)))?;
             ret.extend(token_bytes);
         }
-        
-        String::from_utf8(ret)
-            .map_err(|e| ProcessingError::SystemError(format!("UTF-8 decode error: {}", e)))
+        Ok(ret)
+    }
+    
+    /// Decode tokens to text, replacing byte sequences that are not valid UTF-8
+    /// (a character split by the ends of `tokens`) with U+FFFD
+    pub fn decode_lossy(&self, tokens: &[Rank]) -> Result<String, ProcessingError> {
+        let bytes = self.decode_bytes(tokens)?;
+        Ok(String::from_utf8(bytes)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
+    }
+    
+    /// Whether splitting `tokens` (the encoding of valid UTF-8 text) before `index`
+    /// leaves every character whole, i.e. token `index` does not start with a UTF-8
+    /// continuation byte
+    pub fn is_char_boundary(&self, tokens: &[Rank], index: usize) -> bool {
+        match tokens.get(index) {
+            Some(&token) if index > 0 => self
+                .token_bytes(token)
+                .and_then(|bytes| bytes.first())
+                .is_none_or(|&byte| byte & 0xC0 != 0x80),
+            _ => true,
+        }
+    }
+    
+    /// Closest character boundary (see [`is_char_boundary`](Self::is_char_boundary))
+    /// at or before `index`
+    pub fn floor_char_boundary(&self, tokens: &[Rank], index: usize) -> usize {
+        let mut index = index.min(tokens.len());
+        while !self.is_char_boundary(tokens, index) {
+            index -= 1;
+        }
+        index
+    }
+    
+    /// Closest character boundary at or after `index`
+    pub fn ceil_char_boundary(&self, tokens: &[Rank], index: usize) -> usize {
+        let mut index = index.min(tokens.len());
+        while !self.is_char_boundary(tokens, index) {
+            index += 1;
+        }
+        index
+    }
+    
+    /// Bytes of a single ordinary or special token
+    pub fn token_bytes(&self, token: Rank) -> Option<&[u8]> {
+        self.tokens
+            .token(token)
+            .or_else(|| self.special_tokens_decoder.get(&token).map(Vec::as_slice))
+    }
+
+    /// Byte length of a single token (0 for unknown ranks)
+    pub fn token_byte_len(&self, token: Rank) -> usize {
+        self.token_bytes(token).map_or(0, <[u8]>::len)
+    }
+    
+    /// Rank of a special token such as "<|endoftext|>"
+    pub fn special_token_rank(&self, token: &str) -> Option<Rank> {
+        self.special_tokens_encoder.get(token).copied()
+    }
+    
+    /// Highest ordinary or special rank
+    pub fn max_token_value(&self) -> Rank {
+        self.special_tokens_encoder
+            .values()
+            .copied()
+            .chain(self.tokens.max_rank())
+            .max()
+            .unwrap_or(0)
     }
 
     /// Get special tokens
-    #[allow(dead_code)]
     pub fn special_tokens(&self) -> HashSet<&str> {
         self.special_tokens_encoder
             .keys()
@@ -299,4 +637,238 @@ mod tests {
         let count = bpe.count_tokens("This is a test").unwrap();
         assert!(count > 0);
     }
+    
+    #[derive(serde::Deserialize)]
+    struct Case {
+        text: String,
+        tokens: Vec<Rank>,
+    }
+    
+    fn conformance_cases(kind: EncodingKind) -> Vec<Case> {
+        let data = match kind {
+            EncodingKind::O200kBase => include_str!("../testdata/o200k_base_conformance.json"),
+            EncodingKind::Cl100kBase => include_str!("../testdata/cl100k_base_conformance.json"),
+            EncodingKind::P50kBase => include_str!("../testdata/p50k_base_conformance.json"),
+            EncodingKind::R50kBase => include_str!("../testdata/r50k_base_conformance.json"),
+        };
+        serde_json::from_str(data).unwrap()
+    }
+    
+    fn assert_conformance(bpe: &CoreBPE, kind: EncodingKind) {
+        let cases = conformance_cases(kind);
+        assert!(cases.len() >= 40);
+        for case in &cases {
+            assert_eq!(bpe.encode_ordinary(&case.text), case.tokens, "{} encode_ordinary({:?})", kind, case.text);
+            assert_eq!(bpe.decode(&case.tokens).unwrap(), case.text, "{} decode of {:?}", kind, case.text);
+        }
+    }
+    
+    /// Expected IDs in testdata/ were produced by OpenAI's tiktoken (o200k_base)
+    #[test]
+    fn test_o200k_base_conformance() {
+        let bpe = CoreBPE::new_o200k_base().unwrap();
+        assert_conformance(&bpe, EncodingKind::O200kBase);
+        
+        // Special tokens are only recognized when allowed, and decode to themselves
+        let text = "Hi<|endoftext|><|endofprompt|>";
+        let allowed: HashSet<&str> = ["<|endoftext|>", "<|endofprompt|>"].into();
+        assert_eq!(bpe.encode(text, &allowed).unwrap(), vec![12194, 199999, 200018]);
+        assert_eq!(bpe.encode(text, &HashSet::new()).unwrap(), bpe.encode_ordinary(text));
+        assert_eq!(bpe.decode(&[199999, 200018]).unwrap(), "<|endoftext|><|endofprompt|>");
+        assert_eq!(bpe.special_tokens().len(), 2);
+        assert_eq!(bpe.special_token_rank("<|endoftext|>"), Some(199999));
+        assert_eq!(bpe.max_token_value(), 200018);
+        
+        // A lone lead byte is a valid token but not valid text
+        let lead = bpe.tokens.rank(&[0xC3]).unwrap();
+        assert_eq!(bpe.decode_bytes(&[lead]).unwrap(), vec![0xC3]);
+        assert!(bpe.decode(&[lead]).is_err());
+        assert!(matches!(bpe.decode_bytes(&[300000]), Err(ProcessingError::InvalidArgument(_))));
+        assert_eq!(bpe.decode_lossy(&[lead]).unwrap(), "\u{FFFD}");
+    }
+    
+    #[test]
+    fn test_char_boundaries() {
+        let bpe = CoreBPE::new_o200k_base().unwrap();
+        // Rare 4-byte characters are encoded as several byte-level tokens
+        let text = "a𝔷𝔷";
+        let tokens = bpe.encode_ordinary(text);
+        let boundaries: Vec<usize> = (0..=tokens.len()).filter(|&i| bpe.is_char_boundary(&tokens, i)).collect();
+        assert!(boundaries.len() < tokens.len() + 1, "expected split characters in {:?}", tokens);
+        
+        for index in 0..=tokens.len() {
+            let floor = bpe.floor_char_boundary(&tokens, index);
+            let ceil = bpe.ceil_char_boundary(&tokens, index);
+            assert!(floor <= index && index <= ceil);
+            assert!(boundaries.contains(&floor) && boundaries.contains(&ceil));
+            // Both halves decode strictly when split on a boundary
+            bpe.decode(&tokens[..floor]).unwrap();
+            bpe.decode(&tokens[floor..]).unwrap();
+        }
+    }
+    
+    #[test]
+    fn test_encode_with_offsets() {
+        let bpe = CoreBPE::new_o200k_base().unwrap();
+        for case in conformance_cases(EncodingKind::O200kBase) {
+            let tokens = bpe.encode_with_offsets(&case.text);
+            assert_eq!(tokens.iter().map(|(token, _)| *token).collect::<Vec<_>>(), case.tokens);
+            let joined: Vec<u8> = tokens.iter().flat_map(|(_, range)| &case.text.as_bytes()[range.clone()]).copied().collect();
+            assert_eq!(joined, case.text.as_bytes(), "offsets of {:?}", case.text);
+        }
+        
+        // "é" (2 bytes) and "🎉" (4 bytes): byte offsets shift, character offsets do not
+        let text = "é🎉 x";
+        let tokens = bpe.encode_with_offsets(text);
+        let chars = to_char_offsets(text, &tokens);
+        assert_eq!(tokens.last().unwrap().1, 6..8);
+        assert_eq!(chars.last().unwrap().1, 2..4);
+        
+        // A character split across byte tokens is covered by every part
+        let split = vec![(1, 0..1), (2, 1..2), (3, 2..3)];
+        assert_eq!(to_char_offsets("éa", &split), vec![(1, 0..1), (2, 0..1), (3, 1..2)]);
+    }
+    
+    /// The heap-based merge for long pieces splits exactly like the quadratic one
+    #[test]
+    fn test_large_piece_merge() {
+        let bpe = CoreBPE::new_o200k_base().unwrap();
+        let mut pieces: Vec<Vec<u8>> = conformance_cases(EncodingKind::O200kBase)
+            .iter()
+            .flat_map(|case| bpe.pieces(&case.text).map(|piece| piece.as_str().as_bytes().to_vec()).collect::<Vec<_>>())
+            .filter(|piece| piece.len() > 1)
+            .collect();
+        let mut state = 7u64;
+        let mut random_piece = |len: usize, alphabet: &[u8]| -> Vec<u8> {
+            (0..len)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    alphabet[(state >> 33) as usize % alphabet.len()]
+                })
+                .collect()
+        };
+        pieces.push(random_piece(3000, b"abcdefghijklmnopqrstuvwxyz"));
+        pieces.push(random_piece(500, b"ab")); // Many equal-rank pairs: ties go leftmost
+        pieces.push(random_piece(400, "é中🎉".as_bytes()));
+        pieces.push(vec![b'a'; 300]);
+        
+        for piece in &pieces {
+            let quadratic: Vec<usize> = _byte_pair_merge(&bpe.tokens, piece).iter().map(|&(start, _)| start).collect();
+            assert_eq!(byte_pair_merge_large(&bpe.tokens, piece), quadratic, "{:?}", String::from_utf8_lossy(piece));
+        }
+        
+        // Both paths and the cache agree with the plain encoding
+        let text = String::from_utf8(pieces[pieces.len() - 4].clone()).unwrap();
+        let tokens = bpe.encode_ordinary(&text);
+        assert_eq!(bpe.encode_ordinary(&text), tokens); // Cached (and above the cached length)
+        assert_eq!(bpe.count_tokens(&text).unwrap(), tokens.len());
+        assert_eq!(bpe.count_tokens("Grundstücksverkehrsgenehmigung").unwrap(), bpe.encode_ordinary("Grundstücksverkehrsgenehmigung").len());
+    }
+    
+    /// Joining at every split point of the fixtures (with and without a separator)
+    /// gives the same counts as encoding the joined text
+    #[test]
+    fn test_join_piece_counts() {
+        for kind in [EncodingKind::O200kBase, EncodingKind::Cl100kBase] {
+            let bpe = CoreBPE::for_encoding(kind).unwrap();
+            for case in conformance_cases(kind) {
+                let text = case.text.as_str();
+                let boundaries: Vec<usize> = (0..=text.len()).filter(|&i| text.is_char_boundary(i)).collect();
+                for (index, &split) in boundaries.iter().enumerate() {
+                    for &resume in &boundaries[index..(index + 2).min(boundaries.len())] {
+                        let joined = format!("{}{}", &text[..split], &text[resume..]);
+                        let left = bpe.piece_counts(&text[..split]);
+                        let right = bpe.piece_counts(&text[resume..]);
+                        let counts = bpe.join_piece_counts(&joined, &left, &right);
+                        assert_eq!(counts, bpe.piece_counts(&joined), "{} {:?} + {:?}", kind, &text[..split], &text[resume..]);
+                        assert_eq!(counts.token_count(), bpe.encode_ordinary(&joined).len());
+                    }
+                }
+                
+                let full = bpe.piece_counts(text);
+                let tokens = bpe.encode_ordinary(text);
+                for min_tokens in [0, 1, 5, tokens.len() + 1] {
+                    let suffix = bpe.encode_suffix(text, &full, min_tokens);
+                    assert!(tokens.ends_with(&suffix) && suffix.len() >= min_tokens.min(tokens.len()));
+                }
+            }
+        }
+    }
+    
+    /// Same fixtures encoded with tiktoken's cl100k_base, p50k_base and r50k_base
+    #[test]
+    fn test_other_encodings_conformance() {
+        for (name, special) in [("cl100k_base", 100257), ("p50k_base", 50256), ("r50k_base", 50256)] {
+            let kind: EncodingKind = name.parse().unwrap();
+            let bpe = CoreBPE::from_name(name).unwrap();
+            assert_conformance(&bpe, kind);
+            
+            let allowed: HashSet<&str> = ["<|endoftext|>"].into();
+            let mut expected = bpe.encode_ordinary("Hi");
+            expected.push(special);
+            assert_eq!(bpe.encode("Hi<|endoftext|>", &allowed).unwrap(), expected, "{}", name);
+            assert_eq!(bpe.special_tokens().len(), kind.special_tokens().len());
+        }
+        assert!(CoreBPE::from_name("gpt-4").is_err());
+    }
+    
+    #[test]
+    fn test_from_tiktoken_file() {
+        use base64::{Engine as _, engine::general_purpose};
+        
+        // Byte-level vocabulary: every byte is its own token, ranked by value
+        let ranks: String = (0..=255u8)
+            .map(|byte| format!("{} {}\n", general_purpose::STANDARD.encode([byte]), byte))
+            .collect();
+        let path = std::env::temp_dir().join(format!("myrustchunker-vocab-{}.tiktoken", std::process::id()));
+        std::fs::write(&path, ranks).unwrap();
+        let bpe = CoreBPE::from_tiktoken_file(&path, EncodingKind::R50kBase);
+        std::fs::remove_file(&path).unwrap();
+        let bpe = bpe.unwrap();
+        
+        assert_eq!(bpe.encode_ordinary("hi"), vec![104, 105]);
+        let allowed: HashSet<&str> = ["<|endoftext|>"].into();
+        assert_eq!(bpe.encode("hi<|endoftext|>", &allowed).unwrap(), vec![104, 105, 50256]);
+    }    
+    /// Regex splitting on several threads: one shared regex, the tokenizer's
+    /// [`RegexPool`] and one clone per thread (the former 128-slot table)
+    /// 
+    /// Run with `cargo test --release benchmark_shared_regex -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn benchmark_shared_regex() {
+        /// Split `text` 20 times on each of `threads` threads with the regex `regex_for` returns there
+        fn split_on_threads<'a>(name: &str, threads: usize, text: &str, regex_for: &(dyn Fn(usize) -> &'a Regex + Sync)) {
+            let start = std::time::Instant::now();
+            let pieces: usize = std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads)
+                    .map(|thread| scope.spawn(move || {
+                        let regex = regex_for(thread);
+                        (0..20).map(|_| regex.find_iter(text).count()).sum::<usize>()
+                    }))
+                    .collect();
+                workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+            });
+            let elapsed = start.elapsed();
+            println!(
+                "{}: {:.1} ms, {:.2} MB/s over {} threads ({} pieces)",
+                name,
+                elapsed.as_secs_f64() * 1000.0,
+                (text.len() * 20 * threads) as f64 / elapsed.as_secs_f64() / 1e6,
+                threads,
+                pieces,
+            );
+        }
+
+        let bpe = CoreBPE::shared(EncodingKind::O200kBase).unwrap();
+        let text = "The quick brown fox doesn't jump over 12,345 lazy dogs; naïve café-owners   wait.\n".repeat(500);
+        let threads = bpe.regex.regexes.len(); // One thread per pool slot
+        let shared = bpe.regex.get().clone();
+        let clones: Vec<Regex> = vec![shared.clone(); 128];
+        
+        split_on_threads("shared", threads, &text, &|_| &shared); // Warm up
+        split_on_threads("shared", threads, &text, &|_| &shared);
+        split_on_threads("pool", threads, &text, &|_| bpe.regex.get());
+        split_on_threads("per-thread clones", threads, &text, &|thread| &clones[thread]);
+    }
 }
@@ -0,0 +1,139 @@
+//! Loader for BPE rank files in the tiktoken format
+//!
+//! Vocabularies are read from embedded data or from `.tiktoken` files at runtime
+//! (custom or fine-tuned vocabularies, or builds without embedded vocabularies).
+//! Parsing is strict: the first malformed line fails the load with its line number.
+
+use rustc_hash::FxHashMap as HashMap;
+use crate::tiktoken_core::Rank;
+use crate::error::ProcessingError;
+use crate::vocabulary::{TokenTable, Vocabulary};
+use std::path::Path;
+use log::info;
+use base64::{Engine as _, engine::general_purpose};
+
+/// Environment variable naming a directory of `<encoding>.tiktoken` files that
+/// take precedence over the embedded vocabularies
+pub const VOCAB_DIR_ENV: &str = "MYRUSTCHUNKER_VOCAB_DIR";
+
+/// Load a `.tiktoken` rank file from disk
+pub fn load_tiktoken_file(path: &Path) -> Result<Vocabulary, ProcessingError> {
+    let data = std::fs::read(path).map_err(|e| ProcessingError::InvalidArgument(
+        format!("Cannot read vocabulary file {}: {}", path.display(), e)
+    ))?;
+    parse_tiktoken_ranks(&data, &path.display().to_string())
+}
+
+/// Build the token index of a vocabulary loaded at runtime
+pub fn index_vocabulary(vocabulary: Vocabulary) -> Result<TokenTable, ProcessingError> {
+    TokenTable::new(vocabulary).map_err(|e| ProcessingError::SystemError(format!("Invalid vocabulary: {}", e)))
+}
+
+/// Parse rank data in the tiktoken file format
+/// 
+/// Format: Each line contains "base64_token rank"
+/// Example: "IQ== 0" means token with bytes [33] has rank 0
+/// 
+/// Blank lines are ignored. Lines that are not UTF-8, do not have exactly two
+/// fields, or have invalid base64 or rank, and tokens or ranks that appear twice,
+/// are reported as `InvalidVocabulary` with `origin` and the 1-based line number.
+/// Every single byte must have a rank (BPE starts from bytes, so a missing one
+/// would make some text unencodable); a missing byte is reported as
+/// `InvalidVocabulary` at the last line of the file.
+pub fn parse_tiktoken_ranks(data: &[u8], origin: &str) -> Result<Vocabulary, ProcessingError> {
+    let invalid = |line: usize, message: String| ProcessingError::InvalidVocabulary {
+        file: origin.to_string(),
+        line,
+        message,
+    };
+    
+    let mut encoder: HashMap<Vec<u8>, Rank> = HashMap::default();
+    let mut rank_lines: HashMap<Rank, usize> = HashMap::default();
+    let mut last_line = 0; // Line number of the last token, where missing bytes are reported
+    
+    for (index, raw) in data.split(|&byte| byte == b'\n').enumerate() {
+        let line_number = index + 1;
+        let line = std::str::from_utf8(raw)
+            .map_err(|e| invalid(line_number, format!("not valid UTF-8 ({})", e)))?
+            .trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        last_line = line_number;
+        
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [base64_token, rank_str] = fields[..] else {
+            return Err(invalid(line_number, format!("expected '<base64 token> <rank>', found '{}'", line)));
+        };
+        let token_bytes = general_purpose::STANDARD.decode(base64_token)
+            .map_err(|e| invalid(line_number, format!("invalid base64 token '{}': {}", base64_token, e)))?;
+        let rank: Rank = rank_str.parse()
+            .map_err(|e| invalid(line_number, format!("invalid rank '{}': {}", rank_str, e)))?;
+        
+        if let Some(first_line) = rank_lines.insert(rank, line_number) {
+            return Err(invalid(line_number, format!("rank {} already assigned on line {}", rank, first_line)));
+        }
+        if let Some(previous) = encoder.insert(token_bytes, rank) {
+            return Err(invalid(line_number, format!("token '{}' already has rank {}", base64_token, previous)));
+        }
+    }
+    
+    let missing: Vec<u8> = (0..=255u8).filter(|&byte| !encoder.contains_key(&[byte][..])).collect();
+    if !encoder.is_empty() && let Some(first) = missing.first() {
+        return Err(invalid(last_line, format!(
+            "no rank for byte 0x{:02x} ({} of the 256 single bytes are missing)",
+            first, missing.len()
+        )));
+    }
+    
+    let vocabulary = Vocabulary::from_tokens(encoder.into_iter().collect()).map_err(ProcessingError::SystemError)?;
+    if vocabulary.is_empty() {
+        return Err(ProcessingError::InvalidArgument(format!("Vocabulary {} contains no tokens", origin)));
+    }
+    info!("Loaded {} tokens from {}", vocabulary.len(), origin);
+    Ok(vocabulary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn error_line(data: &str) -> (usize, String) {
+        match parse_tiktoken_ranks(data.as_bytes(), "test.tiktoken") {
+            Err(ProcessingError::InvalidVocabulary { line, message, .. }) => (line, message),
+            other => panic!("expected a vocabulary error, got {:?}", other.map(|vocabulary| vocabulary.len())),
+        }
+    }
+    
+    #[test]
+    fn test_parse_tiktoken_ranks() {
+        // Every byte is its own token, ranked by value
+        let bytes: String = (0..=255u8)
+            .map(|byte| format!("{} {}\n", general_purpose::STANDARD.encode([byte]), byte))
+            .collect();
+        let vocabulary = parse_tiktoken_ranks(format!("{}\r\n\naGVsbG8= 256\n", bytes).as_bytes(), "test.tiktoken").unwrap();
+        assert_eq!(vocabulary.len(), 257);
+        assert_eq!(vocabulary.get(256), Some(&b"hello"[..]));
+        
+        // A byte without rank would panic the first encode that needs it
+        let without_a = bytes.replace("YQ== 97\n", "");
+        let (line, message) = error_line(&without_a);
+        assert_eq!(line, 255);
+        assert!(message.contains("byte 0x61"), "{}", message);
+        
+        assert_eq!(error_line("IQ== 0\nIg==\n").0, 2);
+        assert_eq!(error_line("IQ== 0\nIg== 1 2\n").0, 2);
+        assert_eq!(error_line("IQ== 0\n\n!!! 1\n").0, 3);
+        assert_eq!(error_line("IQ== x\n").0, 1);
+        assert_eq!(error_line("IQ== 0\nIg== 0\n"), (2, "rank 0 already assigned on line 1".to_string()));
+        assert_eq!(error_line("IQ== 0\nIQ== 1\n").1, "token 'IQ==' already has rank 0");
+        assert!(matches!(
+            parse_tiktoken_ranks(b"IQ== 0\n\xff 1\n", "test.tiktoken"),
+            Err(ProcessingError::InvalidVocabulary { line: 2, .. })
+        ));
+        assert!(parse_tiktoken_ranks(b"\n", "test.tiktoken").is_err());
+        
+        let missing = load_tiktoken_file(Path::new("/nonexistent/custom.tiktoken")).unwrap_err();
+        assert!(missing.to_string().contains("custom.tiktoken"));
+    }
+}
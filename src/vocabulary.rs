@@ -0,0 +1,400 @@
+//! Compact in-memory and binary form of a BPE vocabulary
+//!
+//! Tokens are kept in rank order in one byte buffer with end offsets, instead of
+//! one heap allocation per token. The binary form is written by the build script
+//! for the embedded encodings, together with the token index, so loading them
+//! copies nothing and needs no base64 decoding, text parsing or hashing. This
+//! file is also compiled into the build script: it only uses `std`.
+
+use std::borrow::Cow;
+
+/// Magic bytes (with the format version) at the start of the binary vocabulary
+pub const MAGIC: &[u8; 8] = b"MRCBPE1\0";
+
+/// Magic bytes (with the format version) at the start of the binary token table
+pub const TABLE_MAGIC: &[u8; 8] = b"MRCTBL1\0";
+
+/// Ordinary tokens of an encoding in rank order
+/// 
+/// Binary form (integers are little-endian `u32`):
+/// `MAGIC`, token count `n`, `n` ascending ranks, `n` token end offsets, token bytes
+/// 
+/// The arrays are held in that same form, so a vocabulary read from embedded
+/// data borrows it; one built from tokens owns its arrays.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vocabulary {
+    ranks: U32Array, // Strictly ascending
+    ends: U32Array,  // End of each token in `bytes` (start is the previous end)
+    bytes: Cow<'static, [u8]>,
+}
+
+impl Vocabulary {
+    /// Build from `(token bytes, rank)` pairs in any order; fails on a repeated rank
+    pub fn from_tokens(mut tokens: Vec<(Vec<u8>, u32)>) -> Result<Self, String> {
+        tokens.sort_unstable_by_key(|(_, rank)| *rank);
+        
+        let mut ranks = U32Array::with_capacity(tokens.len());
+        let mut ends = U32Array::with_capacity(tokens.len());
+        let mut bytes = Vec::with_capacity(tokens.iter().map(|(token, _)| token.len()).sum());
+        for (token, rank) in tokens {
+            if ranks.last() == Some(rank) {
+                return Err(format!("rank {} is assigned to more than one token", rank));
+            }
+            bytes.extend_from_slice(&token);
+            ranks.push(rank);
+            ends.push(u32::try_from(bytes.len()).map_err(|_| "vocabulary exceeds 4 GiB")?);
+        }
+        Ok(Vocabulary { ranks, ends, bytes: Cow::Owned(bytes) })
+    }
+    
+    pub fn len(&self) -> usize {
+        self.ranks.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.ranks.len() == 0
+    }
+    
+    /// Bytes of the token with `rank`
+    pub fn get(&self, rank: u32) -> Option<&[u8]> {
+        let index = match self.ranks.get(rank as usize) {
+            Some(found) if found == rank => rank as usize, // Dense ranks (all tiktoken files)
+            _ => self.ranks.binary_search(rank)?,
+        };
+        Some(self.token(index))
+    }
+    
+    /// `(rank, token bytes)` in rank order
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> + '_ {
+        (0..self.len()).map(|index| (self.ranks.at(index), self.token(index)))
+    }
+    
+    fn token(&self, index: usize) -> &[u8] {
+        &self.bytes[self.start(index) as usize..self.ends.at(index) as usize]
+    }
+    
+    /// Offset of the token at `index` in `bytes`
+    fn start(&self, index: usize) -> u32 {
+        if index == 0 { 0 } else { self.ends.at(index - 1) }
+    }
+    
+    /// Serialize to the binary form
+    #[allow(dead_code)] // Used by the build script
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(MAGIC.len() + 4 + 8 * self.len() + self.bytes.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.ranks.0);
+        data.extend_from_slice(&self.ends.0);
+        data.extend_from_slice(&self.bytes);
+        data
+    }
+    
+    /// Read the binary form, checking its structure; the vocabulary borrows `data`
+    pub fn from_bytes(data: &'static [u8]) -> Result<Self, String> {
+        let rest = data.strip_prefix(MAGIC).ok_or("not a binary vocabulary (bad magic bytes)")?;
+        let (count, rest) = split_u32s(rest, 1)?;
+        let count = count.at(0) as usize;
+        let (ranks, rest) = split_u32s(rest, count)?;
+        let (ends, bytes) = split_u32s(rest, count)?;
+        
+        if ranks.iter().zip(ranks.iter().skip(1)).any(|(rank, next)| rank >= next) {
+            return Err("ranks are not strictly ascending".to_string());
+        }
+        if ends.iter().zip(ends.iter().skip(1)).any(|(end, next)| end > next)
+            || ends.last().map_or(0, |end| end as usize) != bytes.len()
+        {
+            return Err("token offsets do not match the token bytes".to_string());
+        }
+        Ok(Vocabulary { ranks, ends, bytes: Cow::Borrowed(bytes) })
+    }
+}
+
+/// Array of little-endian `u32`s, borrowed from embedded data or owned
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct U32Array(Cow<'static, [u8]>);
+
+impl U32Array {
+    fn with_capacity(capacity: usize) -> Self {
+        U32Array(Cow::Owned(Vec::with_capacity(capacity * 4)))
+    }
+    
+    fn len(&self) -> usize {
+        self.0.len() / 4
+    }
+    
+    #[inline]
+    fn get(&self, index: usize) -> Option<u32> {
+        let bytes = self.0.get(index * 4..index * 4 + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().expect("4-byte value")))
+    }
+    
+    /// Value at `index`, which must be in bounds
+    #[inline]
+    fn at(&self, index: usize) -> u32 {
+        self.get(index).expect("index within the array")
+    }
+    
+    fn last(&self) -> Option<u32> {
+        self.len().checked_sub(1).map(|index| self.at(index))
+    }
+    
+    fn push(&mut self, value: u32) {
+        self.0.to_mut().extend_from_slice(&value.to_le_bytes());
+    }
+    
+    fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.0.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().expect("4-byte value")))
+    }
+    
+    /// Index of `value` in an ascending array
+    fn binary_search(&self, value: u32) -> Option<usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.at(mid).cmp(&value) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Equal => return Some(mid),
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        None
+    }
+}
+
+/// Vocabulary with a hash index from token bytes to rank
+/// 
+/// The index is an open-addressing table (linear probing, at most half full) whose
+/// slots point into the vocabulary buffer and carry the token length and rank, so
+/// building it allocates one array instead of one key per token, and most probes
+/// are decided without touching the token bytes.
+/// 
+/// Binary form (integers are little-endian `u32`): `TABLE_MAGIC`, slot count,
+/// `start`, `len` and `rank` of every slot, then the binary form of the
+/// vocabulary. The build script writes it for the embedded encodings, so their
+/// index is loaded as it is instead of being rebuilt at every start.
+#[derive(Debug, Clone)]
+pub struct TokenTable {
+    vocabulary: Vocabulary,
+    slots: Cow<'static, [u8]>, // `SLOT_SIZE` bytes per slot, in the binary form
+    shift: u32, // Slot of a token = top bits of its hash
+}
+
+/// Entry of the token index (`len == 0` marks an empty slot)
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    start: u32, // Token bytes in the vocabulary buffer
+    len: u32,
+    rank: u32,
+}
+
+/// Bytes of a slot in the binary form
+const SLOT_SIZE: usize = 12;
+
+impl TokenTable {
+    /// Index `vocabulary`; fails on empty tokens and when two ranks have the same bytes
+    pub fn new(vocabulary: Vocabulary) -> Result<Self, String> {
+        let capacity = slot_count(vocabulary.len());
+        let mut table = TokenTable { slots: Cow::Borrowed(&[]), shift: slot_shift(capacity), vocabulary };
+        let mut slots = vec![Slot::default(); capacity];
+        
+        // Insert in home slot order: the table is then written front to back
+        // instead of at random, which is several times faster for large vocabularies
+        let mut entries: Vec<(usize, Slot)> = (0..table.vocabulary.len())
+            .map(|position| {
+                let start = table.vocabulary.start(position);
+                let entry = Slot {
+                    start,
+                    len: table.vocabulary.ends.at(position) - start,
+                    rank: table.vocabulary.ranks.at(position),
+                };
+                (table.home_slot(table.bytes_of(entry)), entry)
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(home, _)| *home);
+        
+        for (home, entry) in entries {
+            if entry.len == 0 {
+                return Err(format!("rank {} has an empty token", entry.rank));
+            }
+            let token = table.bytes_of(entry);
+            let mut slot = home;
+            while slots[slot].len != 0 {
+                let other = slots[slot];
+                if table.bytes_of(other) == token {
+                    return Err(format!("ranks {} and {} have the same token bytes", other.rank, entry.rank));
+                }
+                slot = (slot + 1) & (capacity - 1);
+            }
+            slots[slot] = entry;
+        }
+        
+        table.slots = Cow::Owned(
+            slots.iter().flat_map(|slot| [slot.start, slot.len, slot.rank]).flat_map(u32::to_le_bytes).collect(),
+        );
+        Ok(table)
+    }
+    
+    /// Serialize to the binary form
+    #[allow(dead_code)] // Used by the build script
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let vocabulary = self.vocabulary.to_bytes();
+        let mut data = Vec::with_capacity(TABLE_MAGIC.len() + 4 + self.slots.len() + vocabulary.len());
+        data.extend_from_slice(TABLE_MAGIC);
+        data.extend_from_slice(&(self.slot_count() as u32).to_le_bytes());
+        data.extend_from_slice(&self.slots);
+        data.extend_from_slice(&vocabulary);
+        data
+    }
+    
+    /// Read the binary form, checking its structure; the table borrows `data`
+    /// 
+    /// Only the sizes are checked, not the slots: reading them all would page in
+    /// the whole index at startup. The binary form is only written by
+    /// [`to_bytes`](Self::to_bytes), and a corrupt slot fails a bounds check on use.
+    pub fn from_bytes(data: &'static [u8]) -> Result<Self, String> {
+        let rest = data.strip_prefix(TABLE_MAGIC).ok_or("not a binary token table (bad magic bytes)")?;
+        let (capacity, rest) = split_u32s(rest, 1)?;
+        let capacity = capacity.at(0) as usize;
+        let slots_len = capacity.checked_mul(SLOT_SIZE).filter(|&len| len <= rest.len()).ok_or("binary token table is truncated")?;
+        let (slots, rest) = rest.split_at(slots_len);
+        let vocabulary = Vocabulary::from_bytes(rest)?;
+        
+        if capacity != slot_count(vocabulary.len()) {
+            return Err(format!("{} slots do not fit {} tokens", capacity, vocabulary.len()));
+        }
+        Ok(TokenTable { vocabulary, slots: Cow::Borrowed(slots), shift: slot_shift(capacity) })
+    }
+    
+    /// Rank of the token with these bytes
+    #[inline]
+    pub fn rank(&self, token: &[u8]) -> Option<u32> {
+        let mut slot = self.home_slot(token);
+        loop {
+            let entry = self.slot(slot);
+            if entry.len == 0 {
+                return None;
+            }
+            if entry.len as usize == token.len() && self.bytes_of(entry) == token {
+                return Some(entry.rank);
+            }
+            slot = (slot + 1) & (self.slot_count() - 1); // Power-of-two size
+        }
+    }
+    
+    /// Bytes of the token with `rank`
+    #[inline]
+    pub fn token(&self, rank: u32) -> Option<&[u8]> {
+        self.vocabulary.get(rank)
+    }
+    
+    /// Number of ordinary tokens
+    pub fn len(&self) -> usize {
+        self.vocabulary.len()
+    }
+    
+    /// Highest rank (`None` for an empty vocabulary)
+    pub fn max_rank(&self) -> Option<u32> {
+        self.vocabulary.ranks.last()
+    }
+    
+    #[inline]
+    fn slot_count(&self) -> usize {
+        self.slots.len() / SLOT_SIZE
+    }
+    
+    #[inline]
+    fn slot(&self, index: usize) -> Slot {
+        let bytes = &self.slots[index * SLOT_SIZE..(index + 1) * SLOT_SIZE];
+        let field = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().expect("4-byte value"));
+        Slot { start: field(0), len: field(4), rank: field(8) }
+    }
+    
+    #[inline]
+    fn bytes_of(&self, entry: Slot) -> &[u8] {
+        &self.vocabulary.bytes[entry.start as usize..(entry.start + entry.len) as usize]
+    }
+    
+    /// First slot probed for `token`
+    #[inline]
+    fn home_slot(&self, token: &[u8]) -> usize {
+        (hash_bytes(token) >> self.shift) as usize
+    }
+}
+
+/// Slots of the index of a vocabulary with `tokens` tokens (a power of two, at least twice as many)
+fn slot_count(tokens: usize) -> usize {
+    (tokens * 2).next_power_of_two().max(16)
+}
+
+/// Hash shift selecting one of `capacity` slots
+fn slot_shift(capacity: usize) -> u32 {
+    u64::BITS - capacity.trailing_zeros()
+}
+
+/// FxHash-style multiplicative hash of a byte string (only used in memory, never stored)
+/// 
+/// The high bits depend on every input bit, so the table takes its slot from those.
+#[inline]
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    let mut hash = bytes.len() as u64;
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().expect("8-byte chunk"));
+        hash = (hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+    let mut tail = [0u8; 8];
+    tail[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    (hash.rotate_left(5) ^ u64::from_le_bytes(tail)).wrapping_mul(SEED)
+}
+
+/// Split `count` little-endian `u32`s off the front of `data`
+fn split_u32s(data: &'static [u8], count: usize) -> Result<(U32Array, &'static [u8]), String> {
+    let len = count.checked_mul(4).filter(|&len| len <= data.len()).ok_or("binary vocabulary is truncated")?;
+    let (values, rest) = data.split_at(len);
+    Ok((U32Array(Cow::Borrowed(values)), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_vocabulary_binary_form() {
+        let tokens = vec![(b"b".to_vec(), 1), (b"a".to_vec(), 0), (b"hello".to_vec(), 7)];
+        let vocabulary = Vocabulary::from_tokens(tokens).unwrap();
+        assert_eq!(vocabulary.get(0), Some(&b"a"[..]));
+        assert_eq!(vocabulary.get(7), Some(&b"hello"[..]));
+        assert_eq!(vocabulary.get(2), None);
+        assert_eq!(vocabulary.iter().map(|(rank, _)| rank).collect::<Vec<_>>(), vec![0, 1, 7]);
+        
+        let data: &'static [u8] = Vec::leak(vocabulary.to_bytes());
+        assert_eq!(Vocabulary::from_bytes(data).unwrap(), vocabulary);
+        assert!(Vocabulary::from_bytes(&data[..data.len() - 1]).is_err());
+        assert!(Vocabulary::from_bytes(&data[1..]).is_err());
+        assert!(Vocabulary::from_bytes(&data[..20]).is_err());
+        
+        assert!(Vocabulary::from_tokens(vec![(b"a".to_vec(), 3), (b"b".to_vec(), 3)]).is_err());
+        
+        let table = TokenTable::new(vocabulary).unwrap();
+        assert_eq!(table.rank(b"hello"), Some(7));
+        assert_eq!(table.rank(b"a"), Some(0));
+        assert_eq!(table.rank(b"hell"), None);
+        assert_eq!(table.rank(b""), None);
+        assert_eq!(table.token(1), Some(&b"b"[..]));
+        
+        // The binary table is used as written, without re-indexing
+        let data: &'static [u8] = Vec::leak(table.to_bytes());
+        let loaded = TokenTable::from_bytes(data).unwrap();
+        assert!(matches!(loaded.slots, Cow::Borrowed(_)));
+        assert_eq!(loaded.rank(b"hello"), Some(7));
+        assert_eq!(loaded.rank(b"hell"), None);
+        assert_eq!(loaded.token(0), Some(&b"a"[..]));
+        assert!(TokenTable::from_bytes(&data[..data.len() - 1]).is_err());
+        assert!(TokenTable::from_bytes(&data[..40]).is_err());
+        let duplicate = Vocabulary::from_tokens(vec![(b"a".to_vec(), 0), (b"a".to_vec(), 1)]).unwrap();
+        assert!(TokenTable::new(duplicate).is_err());
+    }
+}
@@ -1,8 +1,17 @@
+import warnings
+
 import myrustchunker
 
 # Test the enhanced semantic chunking
 print(f"myrustchunker version: {myrustchunker.__version__}")
 
+# Model lookup: the deprecated get_encoding_for_model name still resolves
+assert myrustchunker.encoding_name_for_model("gpt-4o") == "o200k_base"
+with warnings.catch_warnings(record=True) as caught:
+    warnings.simplefilter("always")
+    assert myrustchunker.get_encoding_for_model("gpt-4o") == "o200k_base"
+assert any(issubclass(w.category, DeprecationWarning) for w in caught)
+
 # Test text with various semantic elements
 test_text = """
 # Main Heading